@@ -1,7 +1,45 @@
+use std::env;
+use std::path::PathBuf;
+
 fn main() {
+    println!("cargo:rerun-if-env-changed=VCToolsInstallDir");
+    println!("cargo:rerun-if-env-changed=LIB");
+
+    // Only MSVC builds need the C++ runtime libs; elsewhere there is nothing to do
+    if env::var("CARGO_CFG_TARGET_ENV").as_deref() != Ok("msvc") {
+        return;
+    }
+
     // Ensure the MSVC C++ runtime libs are available to the final link so ONNX static objects can resolve
-    println!("cargo:rustc-link-search=native=E:\\Applications\\Microsoft Visual Studio\\2022\\Community\\VC\\Tools\\MSVC\\14.42.34433\\lib\\x64");
-    // Try linking both debug and release variants if needed
-    println!("cargo:rustc-link-lib=static=libcpmtd");
-    println!("cargo:rustc-link-lib=static=libcpmt");
+    match msvc_lib_dir() {
+        Some(dir) => {
+            println!("cargo:rustc-link-search=native={}", dir.display());
+            // Try linking both debug and release variants if needed
+            println!("cargo:rustc-link-lib=static=libcpmtd");
+            println!("cargo:rustc-link-lib=static=libcpmt");
+        }
+        None => println!(
+            "cargo:warning=MSVC lib directory not found (VCToolsInstallDir/LIB unset); build from a VS developer shell if linking libcpmt fails"
+        ),
+    }
+}
+
+// MSVC toolset lib directory for the target arch, from the variables the VS developer shell sets
+fn msvc_lib_dir() -> Option<PathBuf> {
+    let arch = match env::var("CARGO_CFG_TARGET_ARCH").ok()?.as_str() {
+        "x86_64" => "x64",
+        "x86" => "x86",
+        "aarch64" => "arm64",
+        _ => return None,
+    };
+
+    if let Ok(tools) = env::var("VCToolsInstallDir") {
+        let dir = PathBuf::from(tools).join("lib").join(arch);
+        if dir.join("libcpmt.lib").exists() {
+            return Some(dir);
+        }
+    }
+
+    // LIB lists the SDK and toolset lib dirs; pick the one that has the C++ runtime
+    env::var_os("LIB").and_then(|lib| env::split_paths(&lib).find(|dir| dir.join("libcpmt.lib").exists()))
 }
@@ -0,0 +1,68 @@
+// Fast-fail admission for OCR requests: past a high-water mark of queued plus running jobs,
+// new requests get an immediate 503 instead of waiting behind the worker pool indefinitely.
+// The same gate caps concurrent draws, which are limited separately.
+//
+// Admitted requests' model calls then queue for one of OCR_MAX_CONCURRENCY inference permits
+// (see inference.rs), so however many are admitted, only that many calls run at once across all
+// loaded models.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub struct JobGate {
+    depth: Arc<AtomicUsize>,
+    high_water: usize,
+}
+
+/// Held for the lifetime of one admitted OCR request
+pub struct JobSlot {
+    depth: Arc<AtomicUsize>,
+}
+
+impl JobGate {
+    pub fn new(high_water: usize) -> Self {
+        JobGate { depth: Arc::new(AtomicUsize::new(0)), high_water: high_water.max(1) }
+    }
+
+    /// High-water mark from OCR_MAX_QUEUE, 32 by default
+    #[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+    pub fn from_env() -> Self {
+        JobGate::from_env_var("OCR_MAX_QUEUE", 32)
+    }
+
+    pub fn from_env_var(var: &str, default: usize) -> Self {
+        let max = std::env::var(var).ok().and_then(|v| v.trim().parse::<usize>().ok()).unwrap_or(default);
+        JobGate::new(max)
+    }
+
+    /// A slot if fewer than the high-water mark of jobs are queued or running, else None
+    pub fn try_enter(&self) -> Option<JobSlot> {
+        self.depth
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |d| (d < self.high_water).then_some(d + 1))
+            .ok()
+            .map(|_| JobSlot { depth: self.depth.clone() })
+    }
+
+    #[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+    pub fn depth(&self) -> usize {
+        self.depth.load(Ordering::Acquire)
+    }
+
+    #[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+    pub fn high_water(&self) -> usize {
+        self.high_water
+    }
+}
+
+/// Model calls allowed to run at once, from OCR_MAX_CONCURRENCY (default: CPU cores)
+#[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+pub fn max_concurrency() -> usize {
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    std::env::var("OCR_MAX_CONCURRENCY").ok().and_then(|v| v.trim().parse::<usize>().ok()).unwrap_or(cores).max(1)
+}
+
+impl Drop for JobSlot {
+    fn drop(&mut self) {
+        self.depth.fetch_sub(1, Ordering::AcqRel);
+    }
+}
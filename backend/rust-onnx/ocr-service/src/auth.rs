@@ -0,0 +1,48 @@
+// Optional bearer-token auth for the OCR endpoints, for when the service is reachable beyond the
+// local machine. With OCR_API_KEY set, every `/api/ocr/` request needs
+// `Authorization: Bearer <key>`; without it nothing is checked, as before. Health, metrics and
+// the admin endpoints (which have their own token) are left alone.
+
+use crate::error::ApiError;
+use actix_web::ResponseError;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::http::header::{AUTHORIZATION, HeaderValue, WWW_AUTHENTICATE};
+use actix_web::middleware::Next;
+use std::sync::OnceLock;
+
+const PROTECTED_PREFIX: &str = "/api/ocr/";
+
+pub async fn require_api_key(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    check_api_key(api_key(), req, next).await
+}
+
+/// `require_api_key` against `key` instead of OCR_API_KEY; None lets everything through
+pub async fn check_api_key(key: Option<&str>, req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    // CORS preflights carry no credentials; the actual request that follows is checked
+    let exempt = !req.path().starts_with(PROTECTED_PREFIX) || req.method() == Method::OPTIONS;
+    if let Some(key) = key
+        && !exempt
+    {
+        let given = req.headers().get(AUTHORIZATION).and_then(|v| v.to_str().ok()).and_then(|v| v.strip_prefix("Bearer "));
+        // Compared as hashes so the time taken doesn't depend on how much of the key matched
+        if !given.is_some_and(|g| blake3::hash(g.trim().as_bytes()) == blake3::hash(key.as_bytes())) {
+            let mut res = ApiError::Unauthorized.error_response();
+            res.headers_mut().insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            return Ok(req.into_response(res));
+        }
+    }
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+/// Whether OCR_API_KEY is set, so the OCR endpoints need it
+pub fn enabled() -> bool {
+    api_key().is_some()
+}
+
+// OCR_API_KEY, read once; unset or empty disables the check
+fn api_key() -> Option<&'static str> {
+    static KEY: OnceLock<Option<String>> = OnceLock::new();
+    KEY.get_or_init(|| std::env::var("OCR_API_KEY").ok().map(|k| k.trim().to_string()).filter(|k| !k.is_empty())).as_deref()
+}
@@ -0,0 +1,127 @@
+// Dictionary correction for proofreading: each token of a recognized line is replaced by the
+// closest word of a caller-supplied list when it is within a small edit distance.
+//
+// Tokens are runs of letters and digits, so punctuation and spacing are kept as recognized. A
+// CJK line without separators is a single token and only matches a whole dictionary entry.
+// Matching ignores case; the replacement is written as it appears in the word list.
+
+use serde_json::Value;
+
+pub struct Dictionary {
+    // (lowercased word, word as given)
+    words: Vec<(Vec<char>, String)>,
+    max_distance: Option<usize>,
+}
+
+pub struct Correction {
+    pub page: usize,
+    pub line: usize,
+    pub from: String,
+    pub to: String,
+}
+
+impl Dictionary {
+    /// `max_distance` None picks one per token: 1 edit up to 4 characters, 2 beyond
+    pub fn new(words: &[String], max_distance: Option<usize>) -> Self {
+        let words = words
+            .iter()
+            .map(|w| w.trim())
+            .filter(|w| !w.is_empty())
+            .map(|w| (w.to_lowercase().chars().collect(), w.to_string()))
+            .collect();
+        Dictionary { words, max_distance }
+    }
+
+    // Closest word within the threshold, None when the token is already a word or nothing is close.
+    // Ties go to the earlier word in the list.
+    fn correct(&self, token: &str) -> Option<&str> {
+        let lower: Vec<char> = token.to_lowercase().chars().collect();
+        let limit = self.max_distance.unwrap_or(if lower.len() <= 4 { 1 } else { 2 });
+        let mut best: Option<(usize, &str)> = None;
+        for (word, original) in self.words.iter() {
+            if word.len().abs_diff(lower.len()) > limit {
+                continue;
+            }
+            let d = levenshtein(&lower, word);
+            if d == 0 {
+                return None;
+            }
+            if d <= limit && best.is_none_or(|(bd, _)| d < bd) {
+                best = Some((d, original));
+            }
+        }
+        best.map(|(_, w)| w)
+    }
+}
+
+/// Correct every line text of a Python-format `result` in place (both the single-page and the
+/// multi-page shape, as ocr2text reads them); boxes and scores are left untouched
+pub fn correct_result(result: &mut Value, dict: &Dictionary) -> Vec<Correction> {
+    let mut corrections = Vec::new();
+    let Some(pages) = result.as_array_mut() else { return corrections };
+    let multi_page = pages.first().is_some_and(|p| p.is_object() && p.get("page").is_some());
+    let page_lines: Vec<&mut Value> = if multi_page {
+        pages.iter_mut().filter_map(|p| p.get_mut("result")?.get_mut(0)).collect()
+    } else {
+        pages.first_mut().into_iter().collect()
+    };
+
+    for (page_idx, lines) in page_lines.into_iter().enumerate() {
+        let Some(lines) = lines.as_array_mut() else { continue };
+        for (line_idx, line) in lines.iter_mut().enumerate() {
+            let Some(text) = line.get_mut(1).and_then(|t| t.get_mut(0)) else { continue };
+            let Some(original) = text.as_str() else { continue };
+            let (corrected, changes) = correct_text(original, dict);
+            if changes.is_empty() {
+                continue;
+            }
+            *text = Value::String(corrected);
+            corrections.extend(changes.into_iter().map(|(from, to)| Correction { page: page_idx, line: line_idx, from, to }));
+        }
+    }
+    corrections
+}
+
+// Text with each correctable token replaced, plus the (from, to) pairs applied
+fn correct_text(text: &str, dict: &Dictionary) -> (String, Vec<(String, String)>) {
+    let mut out = String::with_capacity(text.len());
+    let mut changes = Vec::new();
+    let mut token = String::new();
+    let mut flush = |token: &mut String, out: &mut String| {
+        if token.is_empty() {
+            return;
+        }
+        match dict.correct(token) {
+            Some(word) => {
+                changes.push((token.clone(), word.to_string()));
+                out.push_str(word);
+            }
+            None => out.push_str(token),
+        }
+        token.clear();
+    };
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            token.push(c);
+        } else {
+            flush(&mut token, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut token, &mut out);
+    (out, changes)
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j + 1] + 1).min(cur[j] + 1).min(prev[j] + cost);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
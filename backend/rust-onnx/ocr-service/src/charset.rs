@@ -0,0 +1,82 @@
+// Post-recognition character whitelist for constrained fields (plates, serial numbers): the text
+// of each line is forced into a fixed alphabet without swapping the recognition dictionary.
+//
+// In `Map` mode a disallowed character is first replaced by a visually similar allowed one
+// (O→0, I→1, S→5, B→8, ... and the reverse, plus case folding), and dropped if none is allowed.
+// Scores are scaled by how much of the line survived as recognized: each mapped character
+// counts half, each dropped character nothing.
+
+use crate::region::Region;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Drop,
+    Map,
+}
+
+impl Mode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "drop" => Some(Mode::Drop),
+            "map" => Some(Mode::Map),
+            _ => None,
+        }
+    }
+}
+
+// Look-alike substitutions tried in order; both directions so letter-only and digit-only
+// alphabets are covered
+const CONFUSABLES: &[(char, &[char])] = &[
+    ('O', &['0', 'D', 'Q']),
+    ('0', &['O', 'D', 'Q']),
+    ('D', &['0', 'O']),
+    ('Q', &['0', 'O']),
+    ('I', &['1', 'L', 'T']),
+    ('L', &['1', 'I']),
+    ('1', &['I', 'L', 'T']),
+    ('T', &['1', 'I']),
+    ('Z', &['2']),
+    ('2', &['Z']),
+    ('S', &['5']),
+    ('5', &['S']),
+    ('G', &['6']),
+    ('6', &['G']),
+    ('B', &['8']),
+    ('8', &['B']),
+    ('A', &['4']),
+    ('4', &['A']),
+];
+
+/// Rewrite `region` so its text only uses characters from `whitelist`, rescoring as described above.
+pub fn apply(region: &mut Region, whitelist: &[char], mode: Mode) {
+    let total = region.text.chars().count();
+    if total == 0 {
+        return;
+    }
+
+    let mut text = String::with_capacity(region.text.len());
+    let mut kept = 0.0f32;
+    for c in region.text.chars() {
+        if whitelist.contains(&c) {
+            text.push(c);
+            kept += 1.0;
+        } else if mode == Mode::Map && let Some(m) = nearest_allowed(c, whitelist) {
+            text.push(m);
+            kept += 0.5;
+        }
+    }
+    region.score *= kept / total as f32;
+    region.text = text;
+}
+
+fn nearest_allowed(c: char, whitelist: &[char]) -> Option<char> {
+    let candidates = [c.to_ascii_uppercase(), c.to_ascii_lowercase()];
+    if let Some(&m) = candidates.iter().find(|m| whitelist.contains(m)) {
+        return Some(m);
+    }
+    let upper = c.to_ascii_uppercase();
+    CONFUSABLES
+        .iter()
+        .find(|(from, _)| *from == upper)
+        .and_then(|(_, to)| to.iter().flat_map(|t| [*t, t.to_ascii_lowercase()]).find(|t| whitelist.contains(t)))
+}
@@ -0,0 +1,69 @@
+// Coalescing of identical in-flight recognize requests: while one request for an image and option
+// set is running OCR, others with the same result cache key wait for its response instead of
+// running predict again. Covers the window before the response reaches the result cache.
+//
+// If the leading request fails, its waiters run OCR themselves.
+
+use crate::result_cache::CacheKey;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+pub struct Coalescer {
+    enabled: bool,
+    inflight: Mutex<HashMap<CacheKey, watch::Receiver<Option<Value>>>>,
+}
+
+pub enum Join {
+    /// First request for the key; it runs OCR and hands the response to the others
+    Leader(Leader),
+    /// Identical request already running; await its response
+    Follower(watch::Receiver<Option<Value>>),
+}
+
+/// Held by the request doing the work; dropping it without `finish` releases the waiters
+pub struct Leader {
+    coalescer: Arc<Coalescer>,
+    key: CacheKey,
+    sender: watch::Sender<Option<Value>>,
+}
+
+impl Coalescer {
+    /// On unless OCR_COALESCE is 0/false
+    pub fn from_env() -> Self {
+        let enabled = !matches!(std::env::var("OCR_COALESCE").as_deref().map(str::trim), Ok("0") | Ok("false"));
+        Coalescer { enabled, inflight: Mutex::new(HashMap::new()) }
+    }
+
+    /// None when coalescing is off
+    pub fn join(self: &Arc<Self>, key: &CacheKey) -> Option<Join> {
+        if !self.enabled {
+            return None;
+        }
+        let mut inflight = self.inflight.lock().unwrap();
+        if let Some(receiver) = inflight.get(key) {
+            return Some(Join::Follower(receiver.clone()));
+        }
+        let (sender, receiver) = watch::channel(None);
+        inflight.insert(key.clone(), receiver);
+        Some(Join::Leader(Leader { coalescer: self.clone(), key: key.clone(), sender }))
+    }
+}
+
+/// The leader's response, or None if it gave up without one
+pub async fn wait(mut receiver: watch::Receiver<Option<Value>>) -> Option<Value> {
+    receiver.wait_for(|v| v.is_some()).await.ok().and_then(|v| v.clone())
+}
+
+impl Leader {
+    pub fn finish(self, response: &Value) {
+        self.sender.send_replace(Some(response.clone()));
+    }
+}
+
+impl Drop for Leader {
+    fn drop(&mut self) {
+        self.coalescer.inflight.lock().unwrap().remove(&self.key);
+    }
+}
@@ -0,0 +1,111 @@
+// Highlight-color grouping for recognize: sample the background behind each line and cluster
+// the samples so lines marked with the same highlighter end up together.
+
+use crate::region::Region;
+use image::RgbImage;
+
+const KMEANS_ITERATIONS: usize = 20;
+pub const MAX_CLUSTERS: usize = 16;
+
+pub struct ColorGroup {
+    pub color: [u8; 3],
+    // Indices into the sampled colors, in input order
+    pub members: Vec<usize>,
+}
+
+/// Background color behind a line: the mean of the brighter half of the pixels inside its
+/// bounding box, which drops the (darker) glyph strokes and keeps the paper or highlight.
+pub fn background_color(img: &RgbImage, region: &Region) -> [u8; 3] {
+    let (x_min, y_min, x_max, y_max) = region.bounds();
+    let (w, h) = img.dimensions();
+    let x0 = (x_min.max(0.0) as u32).min(w.saturating_sub(1));
+    let y0 = (y_min.max(0.0) as u32).min(h.saturating_sub(1));
+    let x1 = (x_max.max(0.0) as u32).clamp(x0 + 1, w);
+    let y1 = (y_max.max(0.0) as u32).clamp(y0 + 1, h);
+
+    let mut pixels: Vec<[u8; 3]> = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
+    for y in y0..y1 {
+        for x in x0..x1 {
+            pixels.push(img.get_pixel(x, y).0);
+        }
+    }
+    if pixels.is_empty() {
+        return [255, 255, 255];
+    }
+
+    let luma = |p: &[u8; 3]| 299 * p[0] as u32 + 587 * p[1] as u32 + 114 * p[2] as u32;
+    pixels.sort_by_key(|p| std::cmp::Reverse(luma(p)));
+    let brighter = &pixels[..pixels.len().div_ceil(2)];
+    let mut sum = [0u64; 3];
+    for p in brighter.iter() {
+        for c in 0..3 {
+            sum[c] += p[c] as u64;
+        }
+    }
+    let n = brighter.len() as u64;
+    [(sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8]
+}
+
+/// k-means over RGB with farthest-point seeding, so the result is deterministic for a given
+/// input. Returns at most `k` non-empty groups, largest first.
+pub fn cluster(colors: &[[u8; 3]], k: usize) -> Vec<ColorGroup> {
+    if colors.is_empty() || k == 0 {
+        return Vec::new();
+    }
+    let points: Vec<[f32; 3]> = colors.iter().map(|c| [c[0] as f32, c[1] as f32, c[2] as f32]).collect();
+    let dist = |a: &[f32; 3], b: &[f32; 3]| (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f32>();
+
+    let mut centers = vec![points[0]];
+    while centers.len() < k.min(points.len()) {
+        let (idx, d) = points
+            .iter()
+            .map(|p| centers.iter().map(|c| dist(p, c)).fold(f32::MAX, f32::min))
+            .enumerate()
+            .fold((0, 0.0), |best, (i, d)| if d > best.1 { (i, d) } else { best });
+        // Fewer distinct colors than k
+        if d == 0.0 {
+            break;
+        }
+        centers.push(points[idx]);
+    }
+
+    let mut assignment = vec![0usize; points.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut changed = false;
+        for (i, p) in points.iter().enumerate() {
+            let nearest = (0..centers.len())
+                .min_by(|&a, &b| dist(p, &centers[a]).total_cmp(&dist(p, &centers[b])))
+                .unwrap();
+            if assignment[i] != nearest {
+                assignment[i] = nearest;
+                changed = true;
+            }
+        }
+        for (c, center) in centers.iter_mut().enumerate() {
+            let members: Vec<&[f32; 3]> = points.iter().zip(assignment.iter()).filter(|(_, a)| **a == c).map(|(p, _)| p).collect();
+            if !members.is_empty() {
+                let n = members.len() as f32;
+                *center = [0, 1, 2].map(|i| members.iter().map(|p| p[i]).sum::<f32>() / n);
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut groups: Vec<ColorGroup> = centers
+        .iter()
+        .enumerate()
+        .map(|(c, center)| ColorGroup {
+            color: center.map(|v| v.round().clamp(0.0, 255.0) as u8),
+            members: (0..points.len()).filter(|&i| assignment[i] == c).collect(),
+        })
+        .filter(|g| !g.members.is_empty())
+        .collect();
+    groups.sort_by_key(|g| std::cmp::Reverse(g.members.len()));
+    groups
+}
+
+pub fn hex(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
@@ -0,0 +1,122 @@
+// Startup configuration from `ocr-service.toml`: the file named by OCR_CONFIG, else one next to
+// the binary if present. Environment variables override the file, which overrides the built-in
+// defaults. Example:
+//
+//     host = "127.0.0.1"        # OCR_HOST
+//     port = 8081
+//     provider = "cpu"          # OCR_EP
+//     workers = 1               # OCR_WORKERS
+//
+//     [model]
+//     dir = "../models/ppocrv5" # OCR_MODEL_DIR
+//     det = "pp-ocrv5_mobile_det.onnx" # OCR_DET_MODEL
+//     rec = "pp-ocrv5_mobile_rec.onnx" # OCR_REC_MODEL
+//     dict = "ppocrv5_dict.txt"        # OCR_DICT
+//
+//     [defaults]
+//     det_db_thresh = 0.3
+//     cls_thresh = 0.9
+//     use_cls = true
+
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+const FILE_NAME: &str = "ocr-service.toml";
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    host: Option<String>,
+    port: Option<u16>,
+    provider: Option<String>,
+    workers: Option<usize>,
+    model: ModelSection,
+    defaults: DefaultsSection,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ModelSection {
+    dir: Option<String>,
+    det: Option<String>,
+    rec: Option<String>,
+    dict: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct DefaultsSection {
+    det_db_thresh: Option<f32>,
+    cls_thresh: Option<f32>,
+    use_cls: Option<bool>,
+}
+
+/// Resolved settings; `source` is the file they were read from, if any
+#[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+pub struct Config {
+    pub source: Option<PathBuf>,
+    /// Address to bind; 0.0.0.0 exposes the service beyond this machine
+    pub host: String,
+    pub port: u16,
+    pub provider: String,
+    pub workers: usize,
+    pub model_dir: String,
+    /// Model file names, relative to the model dir unless absolute
+    pub det: String,
+    pub rec: String,
+    pub dict: String,
+    pub det_db_thresh: f32,
+    pub cls_thresh: f32,
+    pub use_cls: bool,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Read the config once at startup; a named file that is missing or invalid is an error
+pub fn init() -> Result<&'static Config, String> {
+    let config = load()?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// The startup config (built-in defaults plus environment if `init` wasn't called)
+#[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(|| resolve(FileConfig::default(), None))
+}
+
+fn load() -> Result<Config, String> {
+    let path = match std::env::var("OCR_CONFIG") {
+        Ok(p) if !p.trim().is_empty() => Some(PathBuf::from(p.trim())),
+        _ => std::env::current_exe().ok().and_then(|exe| Some(exe.parent()?.join(FILE_NAME))).filter(|p| p.exists()),
+    };
+    let Some(path) = path else { return Ok(resolve(FileConfig::default(), None)) };
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse(&text).map(|config| Config { source: Some(path.clone()), ..config }).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+}
+
+/// The config from a file's contents, under the same environment overrides as at startup
+pub fn parse(text: &str) -> Result<Config, String> {
+    let file: FileConfig = toml::from_str(text).map_err(|e| e.to_string())?;
+    Ok(resolve(file, None))
+}
+
+fn resolve(file: FileConfig, source: Option<PathBuf>) -> Config {
+    let env = |name: &str| std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let defaults = file.defaults;
+    Config {
+        source,
+        host: env("OCR_HOST").or(file.host).unwrap_or_else(|| "127.0.0.1".to_string()),
+        port: env("OCR_PORT").and_then(|v| v.parse().ok()).or(file.port).unwrap_or(8081),
+        provider: env("OCR_EP").or(file.provider).unwrap_or_else(|| "cpu".to_string()),
+        workers: env("OCR_WORKERS").and_then(|v| v.parse().ok()).or(file.workers).filter(|n| *n > 0).unwrap_or(1),
+        // default relative path from repo: backend/rust-onnx/models/ppocrv5
+        model_dir: env("OCR_MODEL_DIR").or(file.model.dir).unwrap_or_else(|| "../models/ppocrv5".to_string()),
+        det: env("OCR_DET_MODEL").or(file.model.det).unwrap_or_else(|| "pp-ocrv5_mobile_det.onnx".to_string()),
+        rec: env("OCR_REC_MODEL").or(file.model.rec).unwrap_or_else(|| "pp-ocrv5_mobile_rec.onnx".to_string()),
+        dict: env("OCR_DICT").or(file.model.dict).unwrap_or_else(|| "ppocrv5_dict.txt".to_string()),
+        det_db_thresh: defaults.det_db_thresh.filter(|v| (0.0..=1.0).contains(v)).unwrap_or(0.3),
+        cls_thresh: defaults.cls_thresh.filter(|v| (0.0..=1.0).contains(v)).unwrap_or(0.9),
+        use_cls: defaults.use_cls.unwrap_or(true),
+    }
+}
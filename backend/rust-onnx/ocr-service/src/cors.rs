@@ -0,0 +1,42 @@
+// CORS for browser clients: the Tauri webview and dev frontends served from another origin.
+//
+// By default any port on http://localhost or http://127.0.0.1 is allowed, plus the Tauri webview
+// origins. OCR_CORS_ORIGINS (comma-separated exact origins, or `*`) replaces that list.
+
+use actix_cors::Cors;
+use actix_web::http::header::HeaderValue;
+
+const TAURI_ORIGINS: [&str; 2] = ["tauri://localhost", "https://tauri.localhost"];
+
+pub fn from_env() -> Cors {
+    let cors = Cors::default()
+        .allowed_methods(vec!["GET", "POST", "OPTIONS"])
+        .allow_any_header()
+        // Let browser clients read the non-safelisted headers our responses carry
+        .expose_headers(vec!["X-Deskew-Angle", "Retry-After", "X-Request-Id", "X-Score-Legend"])
+        .max_age(3600);
+
+    match std::env::var("OCR_CORS_ORIGINS") {
+        Ok(list) if list.trim() == "*" => cors.allow_any_origin(),
+        Ok(list) => list
+            .split(',')
+            .map(|o| o.trim())
+            .filter(|o| !o.is_empty())
+            .fold(cors, |cors, origin| cors.allowed_origin(origin)),
+        Err(_) => cors.allowed_origin_fn(|origin, _req| is_local_origin(origin)),
+    }
+}
+
+// http://localhost[:port], http://127.0.0.1[:port] or a Tauri webview origin
+fn is_local_origin(origin: &HeaderValue) -> bool {
+    let Ok(origin) = origin.to_str() else { return false };
+    if TAURI_ORIGINS.contains(&origin) {
+        return true;
+    }
+    let Some(host) = origin.strip_prefix("http://") else { return false };
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if port.parse::<u16>().is_ok() => host,
+        _ => host,
+    };
+    host == "localhost" || host == "127.0.0.1"
+}
@@ -0,0 +1,174 @@
+// Image upload decoding shared by every endpoint that takes a `file`, so they all accept the
+// same formats and reject bad input with the same errors.
+
+use crate::error::ApiError;
+use image::codecs::gif::GifDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageFormat, RgbImage, load_from_memory};
+use std::io::Cursor;
+
+// Frames beyond this in one multi-frame upload are refused rather than OCR'd one by one
+const MAX_FRAMES: usize = 100;
+
+/// Decode an uploaded image to RGB. A zero-byte upload is a 400 ("empty image") and bytes that
+/// aren't a recognizable image format are unsupported (415). A recognizable image that fails to
+/// decode, typically a truncated download, or has a zero-pixel side is a 400 naming the format.
+/// HEIC and AVIF decode through libheif (feature "heic"); without it they're a 415 saying so.
+pub fn rgb_image(bytes: &[u8]) -> Result<RgbImage, ApiError> {
+    if let Some(kind) = heif_kind(bytes) {
+        return heif_image(bytes, kind);
+    }
+    let format = guess_format(bytes)?;
+
+    match load_from_memory(bytes) {
+        // A crafted header can declare a 0-pixel side; detection panics on those
+        Ok(d) if d.width() == 0 || d.height() == 0 => Err(ApiError::DecodeFailed("image has zero dimensions".into())),
+        Ok(d) => Ok(d.to_rgb8()),
+        Err(e) => Err(corrupt(format, e)),
+    }
+}
+
+// HEIC (iPhone photos) and AVIF are ISO-BMFF files whose `ftyp` box names the brand. image can't
+// decode either, so they go to libheif when built with feature "heic".
+fn heif_kind(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.get(4..8) != Some(b"ftyp") {
+        return None;
+    }
+    match bytes.get(8..12)? {
+        b"heic" | b"heix" | b"heim" | b"heis" | b"hevc" | b"hevx" | b"mif1" | b"msf1" => Some("heic"),
+        b"avif" | b"avis" => Some("avif"),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "heic")]
+fn heif_image(bytes: &[u8], kind: &'static str) -> Result<RgbImage, ApiError> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let corrupt = |e: libheif_rs::HeifError| {
+        log::debug!("decoding {} upload failed: {}", kind, e);
+        ApiError::DecodeFailed(format!("truncated or corrupt {} image", kind))
+    };
+    let lib = LibHeif::new();
+    let context = HeifContext::read_from_bytes(bytes).map_err(corrupt)?;
+    let handle = context.primary_image_handle().map_err(corrupt)?;
+    // Applies the file's rotation/mirroring, so the text comes out upright as a viewer shows it
+    let decoded = lib.decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None).map_err(corrupt)?;
+    let plane = match decoded.planes().interleaved {
+        Some(plane) if plane.width > 0 && plane.height > 0 => plane,
+        Some(_) => return Err(ApiError::DecodeFailed("image has zero dimensions".into())),
+        None => return Err(ApiError::DecodeFailed(format!("truncated or corrupt {} image", kind))),
+    };
+
+    // Rows are padded out to `stride` bytes
+    let row_len = plane.width as usize * 3;
+    let mut pixels = Vec::with_capacity(row_len * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+    RgbImage::from_raw(plane.width, plane.height, pixels).ok_or_else(|| ApiError::DecodeFailed(format!("truncated or corrupt {} image", kind)))
+}
+
+#[cfg(not(feature = "heic"))]
+fn heif_image(_bytes: &[u8], kind: &'static str) -> Result<RgbImage, ApiError> {
+    Err(ApiError::UnsupportedFormat(format!("{} images need an ocr-service built with feature 'heic'", kind.to_uppercase())))
+}
+
+fn guess_format(bytes: &[u8]) -> Result<ImageFormat, ApiError> {
+    if bytes.is_empty() {
+        return Err(ApiError::DecodeFailed("empty image".into()));
+    }
+    image::guess_format(bytes).map_err(|_| ApiError::UnsupportedFormat("unsupported image format".into()))
+}
+
+// The decoder's own message goes to the log only; it rarely means anything to the uploader
+fn corrupt(format: ImageFormat, err: impl std::fmt::Display) -> ApiError {
+    let name = format!("{:?}", format).to_lowercase();
+    log::debug!("decoding {} upload failed: {}", name, err);
+    ApiError::DecodeFailed(format!("truncated or corrupt {} image", name))
+}
+
+/// Every frame of an upload: the pages of a multi-page TIFF or the frames of an animated GIF, in
+/// order. Other formats, and single-frame TIFF/GIF, give the one image `rgb_image` would.
+#[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+pub fn frames(bytes: &[u8]) -> Result<Vec<RgbImage>, ApiError> {
+    if heif_kind(bytes).is_some() {
+        return rgb_image(bytes).map(|img| vec![img]);
+    }
+    match guess_format(bytes)? {
+        ImageFormat::Tiff => tiff_pages(bytes),
+        ImageFormat::Gif => gif_frames(bytes),
+        _ => rgb_image(bytes).map(|img| vec![img]),
+    }
+}
+
+// image only reads a TIFF's first directory, so each page is decoded from a copy whose header
+// points at that page's directory instead
+fn tiff_pages(bytes: &[u8]) -> Result<Vec<RgbImage>, ApiError> {
+    let offsets = tiff_page_offsets(bytes).map_err(|e| corrupt(ImageFormat::Tiff, e))?;
+    if offsets.len() <= 1 {
+        return rgb_image(bytes).map(|img| vec![img]);
+    }
+    check_frame_count(offsets.len())?;
+
+    let big_endian = bytes.starts_with(b"MM");
+    let bigtiff = bytes.get(2..4) == Some(if big_endian { &[0, 43][..] } else { &[43, 0][..] });
+    let mut patched = bytes.to_vec();
+    let mut pages = Vec::with_capacity(offsets.len());
+    for offset in offsets {
+        if bigtiff {
+            let field = if big_endian { offset.to_be_bytes() } else { offset.to_le_bytes() };
+            patched[8..16].copy_from_slice(&field);
+        } else {
+            let offset = offset as u32;
+            let field = if big_endian { offset.to_be_bytes() } else { offset.to_le_bytes() };
+            patched[4..8].copy_from_slice(&field);
+        }
+        pages.push(rgb_image(&patched)?);
+    }
+    Ok(pages)
+}
+
+fn tiff_page_offsets(bytes: &[u8]) -> tiff::TiffResult<Vec<u64>> {
+    let mut decoder = tiff::decoder::Decoder::new(Cursor::new(bytes))?;
+    let mut offsets = Vec::new();
+    loop {
+        if let Some(pointer) = decoder.ifd_pointer() {
+            offsets.push(pointer.0);
+        }
+        if !decoder.more_images() || offsets.len() > MAX_FRAMES {
+            return Ok(offsets);
+        }
+        decoder.next_image()?;
+    }
+}
+
+// Frames come out composited onto the full canvas, as a viewer would show them
+fn gif_frames(bytes: &[u8]) -> Result<Vec<RgbImage>, ApiError> {
+    let decoder = GifDecoder::new(Cursor::new(bytes)).map_err(|e| corrupt(ImageFormat::Gif, e))?;
+    let frames: Vec<_> = decoder
+        .into_frames()
+        .take(MAX_FRAMES + 1)
+        .collect::<Result<_, _>>()
+        .map_err(|e| corrupt(ImageFormat::Gif, e))?;
+    if frames.len() <= 1 {
+        return rgb_image(bytes).map(|img| vec![img]);
+    }
+    check_frame_count(frames.len())?;
+
+    let mut pages = Vec::with_capacity(frames.len());
+    for frame in frames {
+        let buffer = frame.into_buffer();
+        if buffer.width() == 0 || buffer.height() == 0 {
+            return Err(ApiError::DecodeFailed("image has zero dimensions".into()));
+        }
+        pages.push(DynamicImage::ImageRgba8(buffer).to_rgb8());
+    }
+    Ok(pages)
+}
+
+fn check_frame_count(count: usize) -> Result<(), ApiError> {
+    if count > MAX_FRAMES {
+        return Err(ApiError::PayloadTooLarge(format!("image has more than {} frames", MAX_FRAMES)));
+    }
+    Ok(())
+}
@@ -0,0 +1,44 @@
+// Cross-page deduplication for multi-page recognize: lines that repeat verbatim on many pages
+// (running headers, footers, page furniture) are pulled out so the body text stands alone.
+
+use crate::region::Region;
+use std::collections::HashMap;
+
+pub struct RepeatedLine {
+    pub text: String,
+    // 0-based pages the line was removed from
+    pub pages: Vec<usize>,
+}
+
+/// Lines whose text appears on at least `min_fraction` of the pages (and on two pages at least).
+/// Text is compared after trimming; empty lines are never treated as repeated.
+pub fn find_repeated(pages: &[Vec<Region>], min_fraction: f32) -> Vec<RepeatedLine> {
+    let mut seen_on: HashMap<String, Vec<usize>> = HashMap::new();
+    for (page_idx, regions) in pages.iter().enumerate() {
+        for region in regions.iter() {
+            let text = region.text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            let on = seen_on.entry(text.to_string()).or_default();
+            if on.last() != Some(&page_idx) {
+                on.push(page_idx);
+            }
+        }
+    }
+
+    let min_pages = ((pages.len() as f32 * min_fraction).ceil() as usize).max(2);
+    let mut repeated: Vec<RepeatedLine> = seen_on
+        .into_iter()
+        .filter(|(_, on)| on.len() >= min_pages)
+        .map(|(text, pages)| RepeatedLine { text, pages })
+        .collect();
+    // Most widespread first, then by text so the output is stable
+    repeated.sort_by(|a, b| b.pages.len().cmp(&a.pages.len()).then_with(|| a.text.cmp(&b.text)));
+    repeated
+}
+
+pub fn is_repeated(region: &Region, repeated: &[RepeatedLine]) -> bool {
+    let text = region.text.trim();
+    repeated.iter().any(|rep| rep.text == text)
+}
@@ -0,0 +1,85 @@
+// Skew estimation and correction for scans: the dominant text angle is read off the detected
+// line boxes and the image is rotated so lines run horizontally. draw's `deskew` also moves the
+// boxes along with the image.
+
+use image::{Rgb, RgbImage, imageops};
+use imageproc::geometric_transformations::{Interpolation, rotate_about_center};
+
+// Below this the rotation isn't worth the resampling blur
+const MIN_CORRECTION_DEGREES: f32 = 0.1;
+
+/// Dominant text angle in degrees, clockwise positive (image y points down), in (-45, 45].
+/// Each box contributes the direction of its longest edge; the median over boxes is taken so a
+/// few vertical or misdetected lines don't pull it. None when no box has a usable edge.
+pub fn estimate_angle<'a>(boxes: impl IntoIterator<Item = &'a [[f32; 2]]>) -> Option<f32> {
+    let mut angles: Vec<f32> = boxes.into_iter().filter_map(box_angle).collect();
+    if angles.is_empty() {
+        return None;
+    }
+    angles.sort_by(|a, b| a.total_cmp(b));
+    Some(angles[angles.len() / 2])
+}
+
+/// `img` rotated counter-clockwise by `skew` degrees so text at that angle becomes horizontal.
+/// The canvas grows to keep the corners; uncovered area is filled white.
+pub fn straighten(img: &RgbImage, skew: f32) -> RgbImage {
+    if skew.abs() < MIN_CORRECTION_DEGREES {
+        return img.clone();
+    }
+    let theta = skew.to_radians();
+    let (canvas_w, canvas_h) = canvas_size(img.dimensions(), theta);
+
+    let white = Rgb([255, 255, 255]);
+    let mut canvas = RgbImage::from_pixel(canvas_w, canvas_h, white);
+    let x = (canvas.width() - img.width()) / 2;
+    let y = (canvas.height() - img.height()) / 2;
+    imageops::overlay(&mut canvas, img, x as i64, y as i64);
+    rotate_about_center(&canvas, -theta, Interpolation::Bilinear, white)
+}
+
+/// Where `point` of an image of `size` ends up in `straighten(img, skew)`
+pub fn straighten_point(point: [f32; 2], size: (u32, u32), skew: f32) -> [f32; 2] {
+    if skew.abs() < MIN_CORRECTION_DEGREES {
+        return point;
+    }
+    let theta = skew.to_radians();
+    let (canvas_w, canvas_h) = canvas_size(size, theta);
+    // Padded onto the canvas (integer offsets, as in straighten), then turned about its center
+    let (dx, dy) = (((canvas_w - size.0) / 2) as f32, ((canvas_h - size.1) / 2) as f32);
+    let (cx, cy) = (canvas_w as f32 / 2.0, canvas_h as f32 / 2.0);
+    let (x, y) = (point[0] + dx - cx, point[1] + dy - cy);
+    let (sin, cos) = theta.sin_cos();
+    [cx + x * cos + y * sin, cy - x * sin + y * cos]
+}
+
+// Canvas that keeps every corner of a (w, h) image turned by theta, never smaller than the image
+fn canvas_size((w, h): (u32, u32), theta: f32) -> (u32, u32) {
+    let (fw, fh) = (w as f32, h as f32);
+    let new_w = (fw * theta.cos().abs() + fh * theta.sin().abs()).ceil() as u32;
+    let new_h = (fw * theta.sin().abs() + fh * theta.cos().abs()).ceil() as u32;
+    (new_w.max(w), new_h.max(h))
+}
+
+// Angle of the longest box edge, folded into (-45, 45] so edge order and direction don't matter
+fn box_angle(points: &[[f32; 2]]) -> Option<f32> {
+    if points.len() < 2 {
+        return None;
+    }
+    let (dx, dy) = (0..points.len())
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            (b[0] - a[0], b[1] - a[1])
+        })
+        .max_by(|a, b| (a.0.hypot(a.1)).total_cmp(&b.0.hypot(b.1)))?;
+    if dx.hypot(dy) < 1.0 {
+        return None;
+    }
+    let mut angle = dy.atan2(dx).to_degrees();
+    while angle > 45.0 {
+        angle -= 90.0;
+    }
+    while angle <= -45.0 {
+        angle += 90.0;
+    }
+    Some(angle)
+}
@@ -0,0 +1,97 @@
+// Reading direction for recognize's `text_direction`. The recognizer reads left to right, so a
+// vertical column is turned 90 degrees counter-clockwise first: its top lands on the left and the
+// characters come out in reading order without reordering. The pipeline itself turns every crop
+// at least 1.5x taller than wide; `text_direction` overrides that choice per region.
+
+use crate::model;
+use image::{RgbImage, imageops};
+
+// Same ratio the pipeline's crop uses to decide a line is vertical
+const PIPELINE_RATIO: f32 = 1.5;
+
+#[derive(Clone, Copy)]
+pub enum Mode {
+    Horizontal,
+    Vertical,
+    // Per region: taller than wide reads as vertical
+    Auto,
+}
+
+impl Mode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "horizontal" => Some(Mode::Horizontal),
+            "vertical" => Some(Mode::Vertical),
+            "auto" => Some(Mode::Auto),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Horizontal => "horizontal",
+            Mode::Vertical => "vertical",
+            Mode::Auto => "auto",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    Horizontal,
+    Vertical,
+}
+
+impl Direction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Horizontal => "horizontal",
+            Direction::Vertical => "vertical",
+        }
+    }
+}
+
+/// Direction `mode` picks for the region with these box points
+pub fn choose(mode: Mode, polygon: &[[f32; 2]]) -> Direction {
+    match mode {
+        Mode::Horizontal => Direction::Horizontal,
+        Mode::Vertical => Direction::Vertical,
+        Mode::Auto => {
+            let (w, h) = crop_size(polygon);
+            if h > w { Direction::Vertical } else { Direction::Horizontal }
+        }
+    }
+}
+
+/// Direction the pipeline (and `model::crop_polygon`) read the region in
+pub fn pipeline_choice(polygon: &[[f32; 2]]) -> Direction {
+    let (w, h) = crop_size(polygon);
+    if polygon.len() == 4 && h >= w * PIPELINE_RATIO { Direction::Vertical } else { Direction::Horizontal }
+}
+
+/// The region's crop turned for reading in `direction`
+pub fn crop(img: &RgbImage, polygon: &[[f32; 2]], direction: Direction) -> Option<RgbImage> {
+    let crop = model::crop_polygon(img, polygon)?;
+    Some(match (pipeline_choice(polygon), direction) {
+        // crop_polygon already turned it counter-clockwise; undo that
+        (Direction::Vertical, Direction::Horizontal) => imageops::rotate90(&crop),
+        (Direction::Horizontal, Direction::Vertical) => imageops::rotate270(&crop),
+        _ => crop,
+    })
+}
+
+// Width and height of the rectified crop: mean lengths of opposite edges for a four-point box
+// (as the pipeline measures them), else the bounding rect
+fn crop_size(polygon: &[[f32; 2]]) -> (f32, f32) {
+    let distance = |a: [f32; 2], b: [f32; 2]| ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt();
+    if let [p0, p1, p2, p3] = *polygon {
+        let w = ((distance(p0, p1) + distance(p3, p2)) / 2.0).round();
+        let h = ((distance(p0, p3) + distance(p1, p2)) / 2.0).round();
+        return (w, h);
+    }
+    let span = |axis: usize| {
+        let values = polygon.iter().map(|p| p[axis]);
+        values.clone().fold(f32::MIN, f32::max) - values.fold(f32::MAX, f32::min)
+    };
+    (span(0).max(0.0), span(1).max(0.0))
+}
@@ -0,0 +1,118 @@
+// First-run download of missing model files. With OCR_MODEL_URL_BASE set, a det/rec/dict file
+// that isn't in the model dir is fetched from `<base>/<file name>` and checked against the
+// `<base>/SHA256SUMS` manifest (sha256sum format) before it's moved into place; nothing is
+// written under the final name unless the checksum matches.
+
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::time::Duration;
+use ureq::tls::{TlsConfig, TlsProvider};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// Whole-body limit per file; models are tens of MB, this only stops a runaway server
+const BODY_TIMEOUT: Duration = Duration::from_secs(600);
+const MAX_FILE_BYTES: u64 = 1024 * 1024 * 1024;
+// Progress is logged every this many bytes when the size is unknown, else every 10%
+const PROGRESS_STEP_BYTES: u64 = 8 * 1024 * 1024;
+
+/// Download whichever of `paths` don't exist. Without OCR_MODEL_URL_BASE a missing file is an
+/// error naming it. Blocking.
+pub fn ensure_files(paths: &[&str]) -> Result<(), String> {
+    let missing: Vec<&Path> = paths.iter().map(Path::new).filter(|p| !p.exists()).collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let list = missing.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", ");
+    let base = match std::env::var("OCR_MODEL_URL_BASE") {
+        Ok(base) if !base.trim().is_empty() => base.trim().trim_end_matches('/').to_string(),
+        _ => return Err(format!("model files not found: {} (set OCR_MODEL_URL_BASE to download them)", list)),
+    };
+
+    let agent = agent();
+    let manifest = agent
+        .get(format!("{}/SHA256SUMS", base))
+        .call()
+        .and_then(|r| r.into_body().read_to_string())
+        .map_err(|e| format!("Failed to fetch {}/SHA256SUMS: {}", base, e))?;
+    for path in missing {
+        let name = path.file_name().and_then(|n| n.to_str()).ok_or_else(|| format!("invalid model file path: {}", path.display()))?;
+        let expected = checksum_for(&manifest, name).ok_or_else(|| format!("{}/SHA256SUMS has no entry for {}", base, name))?;
+        download(&agent, &format!("{}/{}", base, name), path, &expected)?;
+    }
+    Ok(())
+}
+
+fn agent() -> ureq::Agent {
+    ureq::Agent::config_builder()
+        .timeout_connect(Some(CONNECT_TIMEOUT))
+        .timeout_recv_response(Some(CONNECT_TIMEOUT))
+        .timeout_recv_body(Some(BODY_TIMEOUT))
+        .tls_config(TlsConfig::builder().provider(TlsProvider::NativeTls).build())
+        .build()
+        .into()
+}
+
+// Lower-case hex digest listed for `name`; lines are "<hex>  <name>" or "<hex> *<name>"
+fn checksum_for(manifest: &str, name: &str) -> Option<String> {
+    manifest.lines().find_map(|line| {
+        let (hash, file) = line.trim().split_once(char::is_whitespace)?;
+        let file = file.trim_start().trim_start_matches('*');
+        (file == name && hash.len() == 64).then(|| hash.to_ascii_lowercase())
+    })
+}
+
+// Stream `url` into `<dest>.part`, hashing as it goes, and rename it to `dest` only on a match
+fn download(agent: &ureq::Agent, url: &str, dest: &Path, expected: &str) -> Result<(), String> {
+    if let Some(dir) = dest.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    let mut partial = dest.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = std::path::PathBuf::from(partial);
+    let result = fetch_to(agent, url, &partial, expected);
+    match result {
+        Ok(()) => std::fs::rename(&partial, dest).map_err(|e| format!("Failed to move {} into place: {}", dest.display(), e)),
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+fn fetch_to(agent: &ureq::Agent, url: &str, partial: &Path, expected: &str) -> Result<(), String> {
+    let response = agent.get(url).call().map_err(|e| format!("Failed to download {}: {}", url, e))?;
+    let total = response.body().content_length();
+    log::info!("Downloading {} ({})", url, total.map(|n| format!("{} bytes", n)).unwrap_or_else(|| "size unknown".into()));
+
+    let mut reader = response.into_body().into_with_config().limit(MAX_FILE_BYTES).reader();
+    let mut file = std::fs::File::create(partial).map_err(|e| format!("Failed to create {}: {}", partial.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    let (mut done, mut next_report) = (0u64, 0u64);
+    let step = total.map(|n| (n / 10).max(1)).unwrap_or(PROGRESS_STEP_BYTES);
+    loop {
+        let n = reader.read(&mut buf).map_err(|e| format!("Failed to download {}: {}", url, e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        file.write_all(&buf[..n]).map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+        done += n as u64;
+        if done >= next_report {
+            match total {
+                Some(total) => log::info!("  {}: {}% ({} / {} bytes)", url, done * 100 / total.max(1), done, total),
+                None => log::info!("  {}: {} bytes", url, done),
+            }
+            next_report = done + step;
+        }
+    }
+    file.sync_all().map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+
+    let actual: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    if actual != expected {
+        return Err(format!("Checksum mismatch for {}: expected {}, got {}", url, expected, actual));
+    }
+    log::info!("Downloaded {} ({} bytes, sha256 ok)", url, done);
+    Ok(())
+}
@@ -0,0 +1,145 @@
+// Error responses with a stable machine-readable code, so clients can tell failures apart without
+// matching on message text. The body is `{"error": {"code": "...", "message": "..."}}`.
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use std::fmt;
+
+// Seconds clients are told to wait before retrying a request refused as busy
+pub const BUSY_RETRY_AFTER_SECS: u32 = 2;
+
+#[derive(Debug)]
+#[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+pub enum ApiError {
+    /// A required form or JSON field is absent (the field name)
+    MissingField(&'static str),
+    /// A field is present but unusable
+    InvalidField(String),
+    /// The upload isn't an image (or PDF) we can read
+    UnsupportedFormat(String),
+    /// The upload looked like an image but couldn't be decoded
+    DecodeFailed(String),
+    ModelNotLoaded,
+    /// The `model_id` asked for isn't loaded
+    UnknownModel(String),
+    PayloadTooLarge(String),
+    /// Missing or wrong OCR_API_KEY bearer token
+    Unauthorized,
+    /// Missing or wrong admin token
+    Forbidden(String),
+    /// An Idempotency-Key reused for a different request
+    Conflict(String),
+    /// Admission refused the request; retry after BUSY_RETRY_AFTER_SECS
+    Busy,
+    /// The client is over OCR_RATE_LIMIT; retry after this many seconds
+    RateLimited(u64),
+    /// OCR didn't finish in time
+    Timeout(String),
+    /// Downloading a `url` image timed out
+    FetchTimeout(String),
+    /// Downloading a `url` image failed upstream
+    FetchFailed(String),
+    /// A known model that couldn't be (re)built, e.g. its files were removed
+    #[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+    ModelUnavailable(String),
+    InferenceFailed(String),
+    Internal(String),
+    /// The endpoint needs the `with-ocr` feature this build lacks
+    #[cfg_attr(feature = "with-ocr", allow(dead_code))]
+    FeatureDisabled,
+}
+
+impl ApiError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::MissingField(_) => "missing_field",
+            ApiError::InvalidField(_) => "invalid_field",
+            ApiError::UnsupportedFormat(_) => "unsupported_format",
+            ApiError::DecodeFailed(_) => "decode_failed",
+            ApiError::ModelNotLoaded => "model_not_loaded",
+            ApiError::UnknownModel(_) => "unknown_model",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Busy => "busy",
+            ApiError::RateLimited(_) => "rate_limited",
+            ApiError::Timeout(_) => "timeout",
+            ApiError::FetchTimeout(_) => "fetch_timeout",
+            ApiError::FetchFailed(_) => "fetch_failed",
+            ApiError::ModelUnavailable(_) => "model_unavailable",
+            ApiError::InferenceFailed(_) => "inference_failed",
+            ApiError::Internal(_) => "internal",
+            ApiError::FeatureDisabled => "feature_disabled",
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::MissingField(name) => write!(f, "missing {}", name),
+            ApiError::ModelNotLoaded => write!(f, "Model not loaded"),
+            ApiError::UnknownModel(id) => write!(f, "Unknown model_id '{}'", id),
+            ApiError::Busy => write!(f, "server busy"),
+            ApiError::RateLimited(secs) => write!(f, "rate limit exceeded; retry after {}s", secs),
+            ApiError::Unauthorized => write!(f, "missing or invalid API key; send 'Authorization: Bearer <key>'"),
+            ApiError::FeatureDisabled => write!(f, "ocr-service built without feature 'with-ocr'; enable it to use native OCR"),
+            ApiError::InvalidField(msg)
+            | ApiError::UnsupportedFormat(msg)
+            | ApiError::DecodeFailed(msg)
+            | ApiError::PayloadTooLarge(msg)
+            | ApiError::Forbidden(msg)
+            | ApiError::Conflict(msg)
+            | ApiError::Timeout(msg)
+            | ApiError::FetchTimeout(msg)
+            | ApiError::FetchFailed(msg)
+            | ApiError::ModelUnavailable(msg)
+            | ApiError::InferenceFailed(msg)
+            | ApiError::Internal(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::MissingField(_) | ApiError::InvalidField(_) | ApiError::DecodeFailed(_) | ApiError::ModelNotLoaded => StatusCode::BAD_REQUEST,
+            ApiError::UnsupportedFormat(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::UnknownModel(_) => StatusCode::NOT_FOUND,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Busy | ApiError::ModelUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Timeout(_) => StatusCode::REQUEST_TIMEOUT,
+            ApiError::FetchTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::FetchFailed(_) => StatusCode::BAD_GATEWAY,
+            ApiError::InferenceFailed(_) | ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::FeatureDisabled => StatusCode::NOT_IMPLEMENTED,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut resp = HttpResponse::build(self.status_code());
+        match self {
+            ApiError::Busy => {
+                resp.insert_header(("Retry-After", BUSY_RETRY_AFTER_SECS.to_string()));
+            }
+            ApiError::RateLimited(secs) => {
+                resp.insert_header(("Retry-After", secs.to_string()));
+            }
+            _ => {}
+        }
+        let mut res = resp.json(serde_json::json!({"error": {"code": self.code(), "message": self.to_string()}}));
+        res.extensions_mut().insert(ErrorDetail { code: self.code(), message: self.to_string() });
+        res
+    }
+}
+
+/// What an error response said, attached to it for the request log
+pub struct ErrorDetail {
+    pub code: &'static str,
+    pub message: String,
+}
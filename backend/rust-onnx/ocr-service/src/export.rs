@@ -0,0 +1,93 @@
+// Alternative output formats rendered from recognized regions
+
+use crate::region::Region;
+
+/// HTML fragment for inline review: one absolutely positioned `<div>` per line, placed at the
+/// line's bounding box, holding one `<span>` per word. The span background goes from red (low
+/// confidence) to green (high). OAROCR only scores whole lines, so every word in a line shares
+/// the line's color.
+pub fn confidence_html(regions: &[Region], width: u32, height: u32) -> String {
+    let mut html = format!(
+        "<div class=\"ocr-review\" style=\"position:relative;width:{}px;height:{}px;\">\n",
+        width, height
+    );
+    for region in regions.iter() {
+        let (x_min, y_min, x_max, y_max) = region.bounds();
+        let line_height = (y_max - y_min).max(1.0);
+        let color = confidence_color(region.score);
+        let words: Vec<String> = region
+            .text
+            .split_whitespace()
+            .map(|w| format!("<span style=\"background-color:{}\">{}</span>", color, escape_html(w)))
+            .collect();
+        html.push_str(&format!(
+            "  <div class=\"ocr-line\" data-score=\"{:.4}\" style=\"position:absolute;left:{:.0}px;top:{:.0}px;width:{:.0}px;height:{:.0}px;font-size:{:.0}px;line-height:{:.0}px;white-space:nowrap;\">{}</div>\n",
+            region.score,
+            x_min,
+            y_min,
+            (x_max - x_min).max(1.0),
+            line_height,
+            line_height * 0.8,
+            line_height,
+            words.join(" ")
+        ));
+    }
+    html.push_str("</div>\n");
+    html
+}
+
+/// Flat, edit-friendly transcript: `{"lines": [{text, x, y, height}], "full_text"}` in reading
+/// order. `x`/`y` are the top-left of the line's bounding box. Lines whose vertical centers fall
+/// within half a line height of each other count as one row, read left to right; `full_text`
+/// joins a row's lines with spaces and rows with newlines.
+pub fn transcript(regions: &[Region]) -> serde_json::Value {
+    let mut lines: Vec<(f32, f32, f32, &str)> = regions
+        .iter()
+        .map(|r| {
+            let (x_min, y_min, _, y_max) = r.bounds();
+            (x_min, y_min, y_max - y_min, r.text.as_str())
+        })
+        .collect();
+    lines.sort_by(|a, b| (a.1 + a.2 / 2.0).total_cmp(&(b.1 + b.2 / 2.0)));
+
+    let mut rows: Vec<Vec<(f32, f32, f32, &str)>> = Vec::new();
+    for line in lines {
+        let center = line.1 + line.2 / 2.0;
+        match rows.last_mut() {
+            Some(row) if (center - (row[0].1 + row[0].2 / 2.0)).abs() <= row[0].2.min(line.2) / 2.0 => row.push(line),
+            _ => rows.push(vec![line]),
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut full_text: Vec<String> = Vec::with_capacity(rows.len());
+    for row in rows.iter_mut() {
+        row.sort_by(|a, b| a.0.total_cmp(&b.0));
+        full_text.push(row.iter().map(|l| l.3).collect::<Vec<_>>().join(" "));
+        out.extend(row.iter().map(|&(x, y, height, text)| serde_json::json!({"text": text, "x": x, "y": y, "height": height})));
+    }
+    serde_json::json!({"lines": out, "full_text": full_text.join("\n")})
+}
+
+// Linear red -> green ramp over [0, 1], semi-transparent so the text stays readable
+fn confidence_color(score: f32) -> String {
+    let s = if score.is_finite() { score.clamp(0.0, 1.0) } else { 0.0 };
+    let r = ((1.0 - s) * 255.0).round() as u8;
+    let g = (s * 255.0).round() as u8;
+    format!("rgba({},{},0,0.35)", r, g)
+}
+
+fn escape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,94 @@
+// Image download for recognize's `url` field.
+//
+// The fetch is bounded on every axis a remote server controls: connect and per-phase read
+// timeouts, redirect count, and body size (checked against Content-Length before reading and
+// enforced again while reading, since the header can be absent or wrong).
+
+use std::time::Duration;
+use ureq::tls::{TlsConfig, TlsProvider};
+
+pub enum FetchError {
+    // Not an http(s) URL (client error)
+    InvalidUrl(String),
+    Timeout,
+    TooLarge { limit: u64 },
+    // Body arrived but isn't an image or PDF
+    NotAnImage(String),
+    // DNS, connection, TLS, HTTP status or redirect failures
+    Failed(String),
+}
+
+impl std::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::InvalidUrl(url) => write!(f, "Invalid url '{}': only http and https are supported", url),
+            FetchError::Timeout => write!(f, "Timed out fetching url"),
+            FetchError::TooLarge { limit } => write!(f, "Remote file exceeds the {} byte limit", limit),
+            FetchError::NotAnImage(content_type) => write!(f, "URL did not return an image (content-type: {})", content_type),
+            FetchError::Failed(e) => write!(f, "Failed to fetch url: {}", e),
+        }
+    }
+}
+
+pub struct FetchConfig {
+    timeout: Duration,
+    max_redirects: u32,
+    max_bytes: u64,
+}
+
+impl FetchConfig {
+    /// OCR_FETCH_TIMEOUT_MS (default 10000, applied to connect and to each read phase),
+    /// OCR_FETCH_MAX_REDIRECTS (default 3) and OCR_FETCH_MAX_BYTES (default 20 MiB)
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.trim().parse::<u64>().ok());
+        FetchConfig {
+            timeout: Duration::from_millis(var("OCR_FETCH_TIMEOUT_MS").unwrap_or(10_000)),
+            max_redirects: var("OCR_FETCH_MAX_REDIRECTS").unwrap_or(3) as u32,
+            max_bytes: var("OCR_FETCH_MAX_BYTES").unwrap_or(20 * 1024 * 1024),
+        }
+    }
+}
+
+/// Download `url` and return its bytes if they look like an image or a PDF. Blocking.
+pub fn fetch(url: &str, config: &FetchConfig) -> Result<Vec<u8>, FetchError> {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err(FetchError::InvalidUrl(url.to_string()));
+    }
+
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_connect(Some(config.timeout))
+        .timeout_recv_response(Some(config.timeout))
+        .timeout_recv_body(Some(config.timeout))
+        .max_redirects(config.max_redirects)
+        .max_redirects_will_error(true)
+        .tls_config(TlsConfig::builder().provider(TlsProvider::NativeTls).build())
+        .build()
+        .into();
+
+    let response = agent.get(url).call().map_err(map_error)?;
+    if let Some(len) = response.body().content_length()
+        && len > config.max_bytes
+    {
+        return Err(FetchError::TooLarge { limit: config.max_bytes });
+    }
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("unknown")
+        .to_string();
+    let bytes = response.into_body().with_config().limit(config.max_bytes).read_to_vec().map_err(map_error)?;
+
+    if image::guess_format(&bytes).is_err() && !crate::pdf::is_pdf(&bytes) {
+        return Err(FetchError::NotAnImage(content_type));
+    }
+    Ok(bytes)
+}
+
+fn map_error(e: ureq::Error) -> FetchError {
+    match e {
+        ureq::Error::Timeout(_) => FetchError::Timeout,
+        ureq::Error::BodyExceedsLimit(limit) => FetchError::TooLarge { limit },
+        e => FetchError::Failed(e.to_string()),
+    }
+}
@@ -0,0 +1,121 @@
+// Canonical form of a hand-edited result, as accepted by draw, searchable_pdf, hocr and export.
+//
+// Editing UIs tend to send integer or string-typed coordinates, boxes whose corners were dragged
+// out of order, or lines without a score. Everything is checked strictly (the other endpoints
+// skip what they can't read) and rewritten to the shape recognize itself returns.
+
+use serde_json::Value;
+
+// Coordinates are kept to this many decimal places
+const COORD_DECIMALS: f64 = 100.0;
+
+pub struct Line {
+    pub points: Vec<[f64; 2]>,
+    pub text: String,
+    pub score: f64,
+    // Trailing `{"id", "raw_text", ...}` object, passed through
+    pub extra: Option<Value>,
+}
+
+impl Line {
+    pub fn to_value(&self) -> Value {
+        let points: Vec<Vec<f64>> = self.points.iter().map(|p| vec![p[0], p[1]]).collect();
+        match &self.extra {
+            Some(extra) => serde_json::json!([points, [self.text, self.score], extra]),
+            None => serde_json::json!([points, [self.text, self.score]]),
+        }
+    }
+}
+
+/// Canonical `result` for a posted one: `[lines]` for a single page, `[{"page", "result": [lines]}]`
+/// for several. `size` (page width, height) clamps coordinates to the page. Errors name the
+/// offending page and line (1-based).
+pub fn finalize(result: &Value, size: Option<(f64, f64)>) -> Result<Value, String> {
+    let entries = result.as_array().ok_or("'result' should be an array")?;
+    let multi_page = entries.first().is_some_and(|p| p.is_object());
+    if !multi_page {
+        let lines = match entries.first() {
+            None => return Ok(Value::Array(Vec::new())),
+            Some(lines) => lines.as_array().ok_or("expected a list of lines or pages")?,
+        };
+        return Ok(serde_json::json!([page_lines(1, lines, size)?]));
+    }
+
+    let mut pages = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        let number = match entry.get("page") {
+            None => i as u64 + 1,
+            Some(n) => n.as_u64().filter(|&n| n > 0).ok_or(format!("page entry {}: 'page' must be a positive integer", i + 1))?,
+        };
+        let lines = entry
+            .get("result")
+            .and_then(|r| r.get(0))
+            .and_then(|l| l.as_array())
+            .ok_or(format!("page {}: expected \"result\": [[lines]]", number))?;
+        pages.push(serde_json::json!({"page": number, "result": [page_lines(number, lines, size)?]}));
+    }
+    Ok(Value::Array(pages))
+}
+
+fn page_lines(page: u64, lines: &[Value], size: Option<(f64, f64)>) -> Result<Vec<Value>, String> {
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, item)| parse_line(item, size).map(|l| l.to_value()).map_err(|e| format!("page {} line {}: {}", page, i + 1, e)))
+        .collect()
+}
+
+fn parse_line(item: &Value, size: Option<(f64, f64)>) -> Result<Line, String> {
+    let parts = item.as_array().ok_or("expected [box_points, [text, score]]")?;
+    let raw_points = parts.first().and_then(|p| p.as_array()).ok_or("box points should be a list of [x, y]")?;
+    let mut points = raw_points
+        .iter()
+        .map(|p| {
+            let x = p.get(0).and_then(number).ok_or("each point should be [x, y] numbers")?;
+            let y = p.get(1).and_then(number).ok_or("each point should be [x, y] numbers")?;
+            Ok(normalize_point([x, y], size))
+        })
+        .collect::<Result<Vec<[f64; 2]>, String>>()?;
+    if points.len() < 3 {
+        return Err(format!("a box needs at least 3 points, got {}", points.len()));
+    }
+    if points.len() == 4 {
+        points = clockwise_from_top_left(points);
+    }
+
+    let content = parts.get(1).and_then(|c| c.as_array()).ok_or("expected [text, score] after the box")?;
+    let text = content.first().and_then(|t| t.as_str()).ok_or("text should be a string")?.to_string();
+    // Hand-typed lines often have no score; they're taken as certain
+    let score = match content.get(1) {
+        None | Some(Value::Null) => 1.0,
+        Some(s) => number(s).filter(|s| (0.0..=1.0).contains(s)).ok_or("score should be a number between 0 and 1")?,
+    };
+    let extra = match parts.get(2) {
+        None => None,
+        Some(e) if e.is_object() => Some(e.clone()),
+        Some(_) => return Err("a third element, if present, should be an object".to_string()),
+    };
+    Ok(Line { points, text, score, extra })
+}
+
+// Finite number, also from a numeric string
+fn number(v: &Value) -> Option<f64> {
+    v.as_f64().or_else(|| v.as_str()?.trim().parse::<f64>().ok()).filter(|n| n.is_finite())
+}
+
+fn normalize_point(p: [f64; 2], size: Option<(f64, f64)>) -> [f64; 2] {
+    let (max_x, max_y) = size.unwrap_or((f64::MAX, f64::MAX));
+    let round = |v: f64| (v * COORD_DECIMALS).round() / COORD_DECIMALS;
+    [round(p[0].clamp(0.0, max_x)), round(p[1].clamp(0.0, max_y))]
+}
+
+// Quadrilateral corners in the order recognize emits: top-left, top-right, bottom-right, bottom-left
+fn clockwise_from_top_left(mut points: Vec<[f64; 2]>) -> Vec<[f64; 2]> {
+    let cx = points.iter().map(|p| p[0]).sum::<f64>() / 4.0;
+    let cy = points.iter().map(|p| p[1]).sum::<f64>() / 4.0;
+    // Image y points down, so increasing atan2 angle is clockwise on screen
+    points.sort_by(|a, b| (a[1] - cy).atan2(a[0] - cx).total_cmp(&(b[1] - cy).atan2(b[0] - cx)));
+    let start = (0..4).min_by(|&i, &j| (points[i][0] + points[i][1]).total_cmp(&(points[j][0] + points[j][1]))).unwrap_or(0);
+    points.rotate_left(start);
+    points
+}
@@ -0,0 +1,23 @@
+// Font for the text labels drawn by /api/ocr/draw.
+//
+// Recognized text is mostly CJK, so the font must cover it. FONT_PATH wins; otherwise the
+// PaddleOCR default `fonts/simfang.ttf` is looked up relative to the working directory, where the
+// desktop bundle ships it. Loaded once and kept for the life of the process.
+
+use ab_glyph::FontVec;
+use std::sync::OnceLock;
+
+const DEFAULT_FONT_PATH: &str = "fonts/simfang.ttf";
+
+static LABEL_FONT: OnceLock<Option<FontVec>> = OnceLock::new();
+
+/// The label font, or None when no usable font file was found
+pub fn label_font() -> Option<&'static FontVec> {
+    LABEL_FONT
+        .get_or_init(|| {
+            let path = std::env::var("FONT_PATH").unwrap_or_else(|_| DEFAULT_FONT_PATH.to_string());
+            let data = std::fs::read(path).ok()?;
+            FontVec::try_from_vec(data).ok()
+        })
+        .as_ref()
+}
@@ -0,0 +1,114 @@
+// Printed vs handwritten guess for each line, from how evenly its strokes are drawn. Type has a
+// near-constant stroke width; pen strokes thicken, thin and trail off. The line crop is binarized
+// (Otsu), each ink pixel's local stroke width is taken as the shorter of its horizontal and
+// vertical ink runs, and the spread of those widths (coefficient of variation) is the signal.
+//
+// This is a heuristic, not a trained classifier: bold/italic mixes and very noisy scans can read
+// as handwritten, and neat block capitals as printed.
+
+use image::RgbImage;
+
+// Widths spread below this read as printed, above as handwritten
+const PIVOT: f32 = 0.45;
+// How quickly confidence grows away from the pivot
+const STEEPNESS: f32 = 8.0;
+// Crops shorter than this don't have enough stroke to judge
+const MIN_HEIGHT: u32 = 8;
+
+#[derive(Clone, Copy)]
+pub enum Style {
+    Printed,
+    Handwritten,
+}
+
+impl Style {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Style::Printed => "printed",
+            Style::Handwritten => "handwritten",
+        }
+    }
+}
+
+/// Style of the text in `crop` with a confidence in 0.5..=1. Crops too small or too blank to
+/// judge come back as printed at 0.5.
+pub fn classify(crop: &RgbImage) -> (Style, f32) {
+    let unsure = (Style::Printed, 0.5);
+    let (w, h) = crop.dimensions();
+    if h < MIN_HEIGHT || w < MIN_HEIGHT {
+        return unsure;
+    }
+    let gray: Vec<u8> = crop.pixels().map(|p| ((299 * p[0] as u32 + 587 * p[1] as u32 + 114 * p[2] as u32) / 1000) as u8).collect();
+    let threshold = otsu(&gray);
+    // Ink is the darker side
+    let ink: Vec<bool> = gray.iter().map(|&v| v <= threshold).collect();
+    let ink_count = ink.iter().filter(|&&i| i).count();
+    if ink_count < (w * h / 50) as usize || ink_count > (w * h / 2) as usize {
+        return unsure;
+    }
+
+    let (w, h) = (w as usize, h as usize);
+    let horizontal = run_lengths(&ink, w, h, true);
+    let vertical = run_lengths(&ink, w, h, false);
+    let widths: Vec<f32> = (0..w * h).filter(|&i| ink[i]).map(|i| horizontal[i].min(vertical[i]) as f32).collect();
+    let mean = widths.iter().sum::<f32>() / widths.len() as f32;
+    let variance = widths.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / widths.len() as f32;
+    let spread = variance.sqrt() / mean;
+
+    let p = 1.0 / (1.0 + (-(spread - PIVOT) * STEEPNESS).exp());
+    if p >= 0.5 { (Style::Handwritten, p) } else { (Style::Printed, 1.0 - p) }
+}
+
+// Length of the ink run through each pixel, along rows or columns (0 off ink)
+fn run_lengths(ink: &[bool], w: usize, h: usize, along_rows: bool) -> Vec<u32> {
+    let (outer, inner) = if along_rows { (h, w) } else { (w, h) };
+    let index = |o: usize, i: usize| if along_rows { o * w + i } else { i * w + o };
+    let mut lengths = vec![0u32; w * h];
+    for o in 0..outer {
+        let mut start = 0;
+        while start < inner {
+            if !ink[index(o, start)] {
+                start += 1;
+                continue;
+            }
+            let mut end = start;
+            while end < inner && ink[index(o, end)] {
+                end += 1;
+            }
+            for i in start..end {
+                lengths[index(o, i)] = (end - start) as u32;
+            }
+            start = end;
+        }
+    }
+    lengths
+}
+
+// Gray level that best splits `gray` into two classes (Otsu's method)
+fn otsu(gray: &[u8]) -> u8 {
+    let mut histogram = [0u64; 256];
+    for &v in gray.iter() {
+        histogram[v as usize] += 1;
+    }
+    let total = gray.len() as f64;
+    let sum: f64 = histogram.iter().enumerate().map(|(v, &n)| v as f64 * n as f64).sum();
+
+    let (mut best, mut best_between) = (0u8, 0.0f64);
+    let (mut weight_below, mut sum_below) = (0.0f64, 0.0f64);
+    for (v, &n) in histogram.iter().enumerate() {
+        weight_below += n as f64;
+        sum_below += v as f64 * n as f64;
+        let weight_above = total - weight_below;
+        if weight_below == 0.0 || weight_above == 0.0 {
+            continue;
+        }
+        let mean_below = sum_below / weight_below;
+        let mean_above = (sum - sum_below) / weight_above;
+        let between = weight_below * weight_above * (mean_below - mean_above).powi(2);
+        if between > best_between {
+            best_between = between;
+            best = v as u8;
+        }
+    }
+    best
+}
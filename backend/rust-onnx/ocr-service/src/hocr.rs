@@ -0,0 +1,127 @@
+// hOCR output for archival and ebook tools: one `ocr_page` per page, one `ocr_line` per
+// recognized line, each holding its `ocrx_word`s with `bbox` and `x_wconf`.
+//
+// Results only carry whole-line text and boxes, so `line_words` yields a single word spanning
+// the line; word-level boxes can replace it without touching the rest of the layout.
+
+use serde_json::Value;
+
+struct Word {
+    bbox: [i64; 4],
+    text: String,
+    // Confidence 0..=100
+    conf: i64,
+}
+
+struct Line {
+    bbox: [i64; 4],
+    words: Vec<Word>,
+}
+
+/// Complete XHTML hOCR document for `pages` of Python-format lines. `size` is the page size in
+/// pixels when known; otherwise each page's bbox is the extent of its lines.
+pub fn document(pages: &[(u64, &[Value])], size: Option<(u32, u32)>) -> String {
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\" \"http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd\">\n",
+        "<html xmlns=\"http://www.w3.org/1999/xhtml\" xml:lang=\"en\" lang=\"en\">\n",
+        "<head>\n",
+        "<title></title>\n",
+        "<meta http-equiv=\"Content-Type\" content=\"text/html; charset=utf-8\" />\n",
+        "<meta name=\"ocr-system\" content=\"PaddleOCR-Desktop ocr-service\" />\n",
+        "<meta name=\"ocr-capabilities\" content=\"ocr_page ocr_line ocrx_word\" />\n",
+        "</head>\n",
+        "<body>\n",
+    ));
+    for (page_idx, (page_no, items)) in pages.iter().enumerate() {
+        let lines: Vec<Line> = items.iter().filter_map(parse_line).collect();
+        let page_bbox = match size {
+            Some((w, h)) => [0, 0, w as i64, h as i64],
+            None => [0, 0, lines.iter().map(|l| l.bbox[2]).max().unwrap_or(0), lines.iter().map(|l| l.bbox[3]).max().unwrap_or(0)],
+        };
+        out.push_str(&format!(
+            "<div class=\"ocr_page\" id=\"page_{}\" title=\"bbox {}; ppageno {}\">\n",
+            page_no,
+            bbox(page_bbox),
+            page_idx
+        ));
+        for (line_idx, line) in lines.iter().enumerate() {
+            out.push_str(&format!(
+                "<span class=\"ocr_line\" id=\"line_{}_{}\" title=\"bbox {}\">",
+                page_no,
+                line_idx + 1,
+                bbox(line.bbox)
+            ));
+            for (word_idx, word) in line.words.iter().enumerate() {
+                if word_idx > 0 {
+                    out.push(' ');
+                }
+                out.push_str(&format!(
+                    "<span class=\"ocrx_word\" id=\"word_{}_{}_{}\" title=\"bbox {}; x_wconf {}\">{}</span>",
+                    page_no,
+                    line_idx + 1,
+                    word_idx + 1,
+                    bbox(word.bbox),
+                    word.conf,
+                    escape_xml(&word.text)
+                ));
+            }
+            out.push_str("</span>\n");
+        }
+        out.push_str("</div>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+// `[box_points, [text, score]]` as a line; None without usable points or text
+fn parse_line(item: &Value) -> Option<Line> {
+    let points: Vec<(f64, f64)> = item
+        .get(0)?
+        .as_array()?
+        .iter()
+        .filter_map(|p| Some((p.get(0)?.as_f64()?, p.get(1)?.as_f64()?)))
+        .collect();
+    if points.is_empty() {
+        return None;
+    }
+    let text = item.get(1)?.get(0)?.as_str()?.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let score = item.get(1).and_then(|t| t.get(1)).and_then(|s| s.as_f64()).unwrap_or(0.0);
+    let bbox = [
+        points.iter().map(|p| p.0).fold(f64::MAX, f64::min).floor().max(0.0) as i64,
+        points.iter().map(|p| p.1).fold(f64::MAX, f64::min).floor().max(0.0) as i64,
+        points.iter().map(|p| p.0).fold(f64::MIN, f64::max).ceil().max(0.0) as i64,
+        points.iter().map(|p| p.1).fold(f64::MIN, f64::max).ceil().max(0.0) as i64,
+    ];
+    Some(Line { bbox, words: line_words(bbox, text, score) })
+}
+
+// Words of one line; for now the whole line as a single word
+fn line_words(bbox: [i64; 4], text: &str, score: f64) -> Vec<Word> {
+    let conf = (score.clamp(0.0, 1.0) * 100.0).round() as i64;
+    vec![Word { bbox, text: text.to_string(), conf }]
+}
+
+fn bbox(b: [i64; 4]) -> String {
+    format!("{} {} {} {}", b[0], b[1], b[2], b[3])
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            // Control characters other than tab/newline aren't allowed in XML 1.0
+            c if c.is_control() && c != '\t' && c != '\n' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
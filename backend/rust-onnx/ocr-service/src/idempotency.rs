@@ -0,0 +1,59 @@
+// Idempotency-Key support for recognize: a retried request carrying the same key gets the
+// stored response instead of re-running OCR. A key is tied to the image and option fields it was
+// first used with, the same pair the result cache keys on.
+
+use crate::result_cache::CacheKey;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Entry {
+    request: CacheKey,
+    response: serde_json::Value,
+    expires: Instant,
+}
+
+pub enum Lookup {
+    Miss,
+    Hit(serde_json::Value),
+    // Key already used for a different image or options
+    Conflict,
+}
+
+pub struct IdempotencyCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl: Duration) -> Self {
+        IdempotencyCache { ttl, entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// TTL from OCR_IDEMPOTENCY_TTL_SECS, 10 minutes by default
+    pub fn from_env() -> Self {
+        let secs = std::env::var("OCR_IDEMPOTENCY_TTL_SECS").ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(600);
+        IdempotencyCache::new(Duration::from_secs(secs))
+    }
+
+    pub fn lookup(&self, key: &str, request: &CacheKey) -> Lookup {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(e) if e.expires <= Instant::now() => {
+                entries.remove(key);
+                Lookup::Miss
+            }
+            Some(e) if e.request != *request => Lookup::Conflict,
+            Some(e) => Lookup::Hit(e.response.clone()),
+            None => Lookup::Miss,
+        }
+    }
+
+    pub fn store(&self, key: String, request: CacheKey, response: serde_json::Value) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        // Expired entries are only dropped here, so the map can't grow past one TTL's worth of keys
+        entries.retain(|_, e| e.expires > now);
+        entries.insert(key, Entry { request, response, expires: now + self.ttl });
+    }
+}
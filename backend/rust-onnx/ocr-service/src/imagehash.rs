@@ -0,0 +1,55 @@
+// Duplicate detection for batch uploads, so a folder of near-identical screenshots is recognized
+// once per distinct image. Byte-identical files are always matched; with perceptual matching,
+// images whose difference hash (dHash) is within a small Hamming distance are matched too.
+
+use crate::decode;
+use crate::pdf;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, RgbImage};
+use std::collections::HashMap;
+
+/// Default Hamming distance (out of 64 bits) under which two images count as the same
+pub const DEFAULT_DISTANCE: u32 = 5;
+pub const MAX_DISTANCE: u32 = 32;
+
+/// 64-bit difference hash: the image shrunk to 9x8 grey pixels, one bit per horizontal neighbour
+/// pair set when the left pixel is brighter. Robust to rescaling and recompression, not to crops.
+pub fn dhash(img: &RgbImage) -> u64 {
+    let grey = DynamicImage::ImageRgb8(img.clone()).to_luma8();
+    let small = imageops::resize(&grey, 9, 8, FilterType::Triangle);
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash = (hash << 1) | (small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0]) as u64;
+        }
+    }
+    hash
+}
+
+/// For each file, the index of an earlier file it duplicates, if any. Always matches identical
+/// bytes; with `distance` set, also images whose dHash differs from an earlier original's by at
+/// most that many bits. PDFs and files that don't decode are only matched byte for byte.
+pub fn find_duplicates<'a>(files: impl IntoIterator<Item = &'a [u8]>, distance: Option<u32>) -> Vec<Option<usize>> {
+    let mut by_bytes: HashMap<blake3::Hash, usize> = HashMap::new();
+    let mut hashes: Vec<(usize, u64)> = Vec::new();
+    let mut duplicate_of = Vec::new();
+    for (index, bytes) in files.into_iter().enumerate() {
+        let digest = blake3::hash(bytes);
+        if let Some(&original) = by_bytes.get(&digest) {
+            duplicate_of.push(Some(original));
+            continue;
+        }
+        by_bytes.insert(digest, index);
+        let Some(max_distance) = distance.filter(|_| !pdf::is_pdf(bytes)) else {
+            duplicate_of.push(None);
+            continue;
+        };
+        let hash = decode::rgb_image(bytes).ok().map(|img| dhash(&img));
+        let similar = hash.and_then(|h| hashes.iter().find(|(_, other)| (h ^ other).count_ones() <= max_distance).map(|&(i, _)| i));
+        if similar.is_none() && let Some(h) = hash {
+            hashes.push((index, h));
+        }
+        duplicate_of.push(similar);
+    }
+    duplicate_of
+}
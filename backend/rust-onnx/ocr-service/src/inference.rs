@@ -0,0 +1,115 @@
+// Every blocking model call goes through `Inference::run`, so all of them, whichever endpoint
+// makes them, share one limit on how many run at once (OCR_MAX_CONCURRENCY) and one bound on how
+// long a request waits for each (OCR_TIMEOUT_SECS). Calls past the limit queue for a permit rather
+// than all landing on the blocking thread pool together.
+
+use crate::error::ApiError;
+use crate::model::OcrModel;
+use crate::pool::OcrPool;
+use actix_web::web;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tracing::{Instrument, info_span};
+
+pub struct Inference {
+    permits: Arc<Semaphore>,
+    max_concurrency: usize,
+    timeout: Duration,
+    calls: AtomicU64,
+}
+
+impl Inference {
+    pub fn new(max_concurrency: usize, timeout: Duration) -> Self {
+        let max_concurrency = max_concurrency.max(1);
+        Inference { permits: Arc::new(Semaphore::new(max_concurrency)), max_concurrency, timeout, calls: AtomicU64::new(0) }
+    }
+
+    /// OCR_TIMEOUT_SECS defaults to 30
+    pub fn from_env() -> Self {
+        let secs = std::env::var("OCR_TIMEOUT_SECS").ok().and_then(|v| v.trim().parse::<u64>().ok()).filter(|&s| s > 0).unwrap_or(30);
+        Inference::new(crate::admission::max_concurrency(), Duration::from_secs(secs))
+    }
+
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+
+    /// Calls holding a permit, including any that already gave up waiting on their result
+    pub fn running(&self) -> usize {
+        self.max_concurrency - self.permits.available_permits()
+    }
+
+    /// Model calls started since startup
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// Run `work` on the blocking thread pool once a permit is free; returns its output and how
+    /// long it queued for the permit. Past the timeout (counted from getting the permit) the caller
+    /// gets a Timeout, but the task isn't cancelled: it finishes in the background, and the permit
+    /// moved into it is released only then. A failed call is an InferenceFailed whose message
+    /// starts with `what` ("OCR", "Detection", ...).
+    pub async fn run<T, E, F>(&self, what: &str, work: F) -> Result<(T, Duration), ApiError>
+    where
+        T: Send + 'static,
+        E: std::fmt::Display,
+        F: FnOnce() -> Result<T, E> + Send + 'static,
+    {
+        let queued = Instant::now();
+        // The semaphore is never closed, so acquiring can't fail
+        let permit = self.permits.clone().acquire_owned().instrument(info_span!("queue")).await.unwrap();
+        let waited = queued.elapsed();
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let task = web::block(move || {
+            let _permit = permit;
+            work().map_err(|e| e.to_string())
+        });
+        let res = match actix_rt::time::timeout(self.timeout, task).instrument(info_span!("predict")).await {
+            Ok(res) => res,
+            Err(_) => return Err(ApiError::Timeout(format!("OCR timed out after {:?}", self.timeout))),
+        };
+        match res {
+            Ok(Ok(output)) => Ok((output, waited)),
+            Ok(Err(e)) => Err(ApiError::InferenceFailed(format!("{} error: {}", what, e))),
+            Err(e) => Err(ApiError::Internal(format!("Task error: {}", e))),
+        }
+    }
+}
+
+/// One request's model pool and the limits its calls run under. `queue_wait` adds up the time
+/// those calls spent waiting for a permit, for recognize's meta.queue_wait_ms.
+pub struct Ocr<'a> {
+    pool: &'a Arc<OcrPool>,
+    inference: &'a Inference,
+    queue_wait: Mutex<Duration>,
+}
+
+impl<'a> Ocr<'a> {
+    pub fn new(pool: &'a Arc<OcrPool>, inference: &'a Inference) -> Self {
+        Ocr { pool, inference, queue_wait: Mutex::new(Duration::ZERO) }
+    }
+
+    pub fn pool(&self) -> &Arc<OcrPool> {
+        self.pool
+    }
+
+    pub fn queue_wait(&self) -> Duration {
+        *self.queue_wait.lock().unwrap()
+    }
+
+    /// Check out a worker and run `work` on its model through `Inference::run`. The checkout moves
+    /// into the blocking task too, so no other request gets this model while `work` still runs.
+    pub async fn run<T, E, F>(&self, what: &str, work: F) -> Result<T, ApiError>
+    where
+        T: Send + 'static,
+        E: std::fmt::Display,
+        F: FnOnce(&OcrModel) -> Result<T, E> + Send + 'static,
+    {
+        let pooled = self.pool.acquire().instrument(info_span!("acquire_worker")).await;
+        let (output, waited) = self.inference.run(what, move || work(&pooled.model())).await?;
+        *self.queue_wait.lock().unwrap() += waited;
+        Ok(output)
+    }
+}
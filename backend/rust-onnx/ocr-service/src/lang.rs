@@ -0,0 +1,108 @@
+// Language guesses from the scripts of recognized letters: per line for recognize's
+// `detect_language`, and document-level for routing, where lines vote weighted by letter count.
+//
+// Codes are PaddleOCR's recognition-model language names ("ch", "japan", "korean", "latin",
+// "cyrillic", ...), so the answer can pick the model to re-run with. Only the script is seen, so
+// Latin-script languages all come out as "latin".
+
+use std::collections::HashMap;
+
+// A document is reported as mixed when the runner-up holds at least this share of the letters
+const MIXED_SHARE: f32 = 0.25;
+
+pub struct DocLang {
+    pub code: &'static str,
+    // Share of the document's letters in lines of this language
+    pub confidence: f32,
+    // Top two languages with their shares, for mixed documents
+    pub mixed: Option<[(&'static str, f32); 2]>,
+}
+
+impl DocLang {
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut value = serde_json::json!({"code": self.code, "confidence": round(self.confidence)});
+        if let Some(mixed) = self.mixed {
+            value["mixed"] = mixed
+                .iter()
+                .map(|&(code, share)| serde_json::json!({"code": code, "proportion": round(share)}))
+                .collect();
+        }
+        value
+    }
+}
+
+/// Dominant language of `lines`, or None when they have no letters of a known script
+pub fn detect_document<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<DocLang> {
+    let mut weights: HashMap<&'static str, usize> = HashMap::new();
+    for text in lines {
+        if let Some((code, letters)) = detect_line(text) {
+            *weights.entry(code).or_default() += letters;
+        }
+    }
+    let total: usize = weights.values().sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut ranked: Vec<(&'static str, f32)> = weights.into_iter().map(|(code, n)| (code, n as f32 / total as f32)).collect();
+    // Ties broken by code so the answer doesn't depend on hash order
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+    let mixed = match ranked.get(1) {
+        Some(&second) if second.1 >= MIXED_SHARE => Some([ranked[0], second]),
+        _ => None,
+    };
+    Some(DocLang { code: ranked[0].0, confidence: ranked[0].1, mixed })
+}
+
+/// Language of one line's text, or None when it has no letters of a known script (empty,
+/// digits or punctuation only)
+pub fn detect_region(text: &str) -> Option<&'static str> {
+    detect_line(text).map(|(code, _)| code)
+}
+
+// Language of one line and its number of letters in known scripts. Kana makes a line Japanese
+// and Hangul Korean even among Han characters; otherwise the most common script wins.
+fn detect_line(text: &str) -> Option<(&'static str, usize)> {
+    let mut counts: HashMap<&'static str, usize> = HashMap::new();
+    for c in text.chars() {
+        if let Some(script) = script(c) {
+            *counts.entry(script).or_default() += 1;
+        }
+    }
+    let letters: usize = counts.values().sum();
+    if letters == 0 {
+        return None;
+    }
+    let code = if counts.contains_key("kana") {
+        "japan"
+    } else if counts.contains_key("korean") {
+        "korean"
+    } else {
+        counts.into_iter().max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(a.0))).map(|(s, _)| if s == "han" { "ch" } else { s })?
+    };
+    Some((code, letters))
+}
+
+fn script(c: char) -> Option<&'static str> {
+    let script = match c as u32 {
+        0x41..=0x5A | 0x61..=0x7A | 0xC0..=0x24F | 0x1E00..=0x1EFF | 0xFF21..=0xFF3A | 0xFF41..=0xFF5A => "latin",
+        0x370..=0x3FF => "greek",
+        0x400..=0x4FF => "cyrillic",
+        0x600..=0x6FF | 0x750..=0x77F => "arabic",
+        0x900..=0x97F => "devanagari",
+        0xE00..=0xE7F => "thai",
+        0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9D => "kana",
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => "korean",
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FA1F => "han",
+        _ => return None,
+    };
+    // The katakana middle dot is shared CJK punctuation; × and ÷ sit inside Latin-1
+    if c == '\u{30FB}' || (script == "latin" && !c.is_alphabetic()) {
+        return None;
+    }
+    Some(script)
+}
+
+fn round(share: f32) -> f64 {
+    (share as f64 * 1000.0).round() / 1000.0
+}
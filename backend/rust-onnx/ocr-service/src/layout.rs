@@ -0,0 +1,169 @@
+// Line layout for ocr2text: reading order and paragraph breaks recovered from the box geometry
+// of Python-format `[box_points, [text, score]]` lines. layout_text's monospace grid reuses the
+// same rows.
+
+use serde_json::Value;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    // As given (detection order)
+    Detection,
+    // Top to bottom, then left to right within a row
+    ReadingOrder,
+    // Reading order, with side-by-side lines joined and blank lines between paragraphs
+    Paragraphs,
+}
+
+// A gap wider than this many median line heights starts a new paragraph
+const PARAGRAPH_GAP: f32 = 1.5;
+// Lines on one row further apart than this many line heights are separate lines, not one
+const WORD_GAP: f32 = 2.0;
+
+struct Line<'a> {
+    left: f32,
+    top: f32,
+    right: f32,
+    bottom: f32,
+    value: &'a Value,
+}
+
+impl<'a> Line<'a> {
+    // Bounds of the box in the line's first element; lines without points sit at the origin
+    fn new(value: &'a Value) -> Self {
+        let points: Vec<(f32, f32)> = value
+            .get(0)
+            .and_then(|b| b.as_array())
+            .map(|pts| pts.iter().filter_map(|p| Some((p.get(0)?.as_f64()? as f32, p.get(1)?.as_f64()? as f32))).collect())
+            .unwrap_or_default();
+        if points.is_empty() {
+            return Line { left: 0.0, top: 0.0, right: 0.0, bottom: 0.0, value };
+        }
+        Line {
+            left: points.iter().map(|p| p.0).fold(f32::MAX, f32::min),
+            top: points.iter().map(|p| p.1).fold(f32::MAX, f32::min),
+            right: points.iter().map(|p| p.0).fold(f32::MIN, f32::max),
+            bottom: points.iter().map(|p| p.1).fold(f32::MIN, f32::max),
+            value,
+        }
+    }
+
+    fn height(&self) -> f32 {
+        self.bottom - self.top
+    }
+
+    fn text(&self) -> Option<&'a str> {
+        self.value.get(1).and_then(|t| t.get(0)).and_then(|s| s.as_str()).filter(|t| !t.trim().is_empty())
+    }
+}
+
+/// Non-blank line texts of one page laid out per `layout`. Paragraph layout yields one entry per
+/// visual line (side-by-side boxes joined with a space) and an empty entry between paragraphs.
+pub fn page_text(lines: &[Value], layout: Layout) -> Vec<String> {
+    let lines: Vec<Line> = lines.iter().map(Line::new).collect();
+    match layout {
+        Layout::Detection => lines.iter().filter_map(|l| l.text()).map(str::to_string).collect(),
+        Layout::ReadingOrder => rows(lines).iter().flatten().filter_map(|l| l.text()).map(str::to_string).collect(),
+        Layout::Paragraphs => paragraphs(rows(lines)),
+    }
+}
+
+// Lines ordered by the top of their box; lines whose tops are within half a line height of the
+// row's first line share a row, sorted left to right
+fn rows(mut lines: Vec<Line>) -> Vec<Vec<Line>> {
+    lines.sort_by(|a, b| a.top.total_cmp(&b.top));
+    let mut rows: Vec<Vec<Line>> = Vec::new();
+    for line in lines {
+        match rows.last_mut() {
+            Some(row) if (line.top - row[0].top).abs() <= row[0].height().min(line.height()) / 2.0 => row.push(line),
+            _ => rows.push(vec![line]),
+        }
+    }
+    for row in rows.iter_mut() {
+        row.sort_by(|a, b| a.left.total_cmp(&b.left));
+    }
+    rows
+}
+
+fn paragraphs(rows: Vec<Vec<Line>>) -> Vec<String> {
+    let mut heights: Vec<f32> = rows.iter().flatten().filter(|l| l.text().is_some()).map(Line::height).collect();
+    heights.sort_by(|a, b| a.total_cmp(b));
+    let median_height = heights.get(heights.len() / 2).copied().unwrap_or(0.0);
+
+    let mut out: Vec<String> = Vec::new();
+    let mut prev_bottom: Option<f32> = None;
+    for row in rows.iter() {
+        let lines: Vec<&Line> = row.iter().filter(|l| l.text().is_some()).collect();
+        let Some(first) = lines.first() else { continue };
+        let top = lines.iter().map(|l| l.top).fold(f32::MAX, f32::min);
+        if let Some(bottom) = prev_bottom
+            && top - bottom > PARAGRAPH_GAP * median_height
+        {
+            out.push(String::new());
+        }
+
+        let mut current = first.text().unwrap_or_default().to_string();
+        for pair in lines.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            if b.left - a.right <= WORD_GAP * a.height().max(b.height()) {
+                current.push(' ');
+            } else {
+                out.push(std::mem::take(&mut current));
+            }
+            current.push_str(b.text().unwrap_or_default());
+        }
+        out.push(current);
+        prev_bottom = Some(lines.iter().map(|l| l.bottom).fold(f32::MIN, f32::max));
+    }
+    out
+}
+
+/// layout_text's rough monospace rendering of one page `width` pixels wide: one text row per row
+/// of boxes, each line starting at the column its left edge falls in and blank rows for vertical
+/// gaps, so tables and forms keep their shape. A column is the median character width of the
+/// lines (CJK characters count as two columns). Lines that overlap are kept left to right with a
+/// space between.
+pub fn page_grid(lines: &[Value], width: f32) -> Vec<String> {
+    let rows = rows(lines.iter().map(Line::new).filter(|l| l.text().is_some()).collect());
+    let mut char_widths: Vec<f32> = rows.iter().flatten().map(|l| (l.right - l.left) / display_width(l.text().unwrap_or_default()).max(1) as f32).filter(|w| *w > 0.0).collect();
+    char_widths.sort_by(|a, b| a.total_cmp(b));
+    let Some(&char_width) = char_widths.get(char_widths.len() / 2) else { return Vec::new() };
+    let mut heights: Vec<f32> = rows.iter().flatten().map(Line::height).collect();
+    heights.sort_by(|a, b| a.total_cmp(b));
+    let line_height = heights.get(heights.len() / 2).copied().unwrap_or(0.0).max(1.0);
+    let last_column = (width / char_width).ceil().max(0.0) as usize;
+
+    let mut out: Vec<String> = Vec::new();
+    let mut prev_bottom: Option<f32> = None;
+    for row in rows.iter() {
+        let top = row.iter().map(|l| l.top).fold(f32::MAX, f32::min);
+        if let Some(bottom) = prev_bottom {
+            // Ordinary leading between consecutive rows isn't a blank row
+            let blank = ((top - bottom) / line_height).max(0.0) as usize;
+            out.extend(std::iter::repeat_n(String::new(), blank));
+        }
+        let mut text = String::new();
+        let mut column = 0;
+        for line in row.iter() {
+            let start = ((line.left / char_width).round().max(0.0) as usize).min(last_column);
+            // Overlapping or touching boxes still get a space between them
+            let start = if column == 0 { start } else { start.max(column + 1) };
+            text.extend(std::iter::repeat_n(' ', start - column));
+            let line_text = line.text().unwrap_or_default().trim();
+            text.push_str(line_text);
+            column = start + display_width(line_text);
+        }
+        out.push(text);
+        prev_bottom = Some(row.iter().map(|l| l.bottom).fold(f32::MIN, f32::max));
+    }
+    out
+}
+
+// Monospace columns `text` takes up: East Asian wide and fullwidth characters count two
+fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x1100..=0x115F | 0x2E80..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFE30..=0xFE4F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6 | 0x20000..=0x3FFFD => 2,
+            _ => 1,
+        })
+        .sum()
+}
@@ -0,0 +1,87 @@
+// Merging of detection boxes that split one line, for recognize's merge_boxes: the detector
+// sometimes breaks a line at wide word gaps or changes of font, and reading the pieces separately
+// gives choppy text. Pieces that sit on the same baseline at the same height, no more than a few
+// line heights apart, are joined into one box before recognition.
+
+use crate::direction::{self, Direction};
+use crate::region::Region;
+
+/// Widest horizontal gap that's still joined, in line heights
+pub const DEFAULT_GAP: f32 = 1.0;
+pub const MAX_GAP: f32 = 10.0;
+
+// Smallest ratio of the shorter piece's height to the taller's
+const MIN_HEIGHT_RATIO: f32 = 0.6;
+// Largest distance between vertical centers, as a share of the shorter piece's height
+const MAX_CENTER_OFFSET: f32 = 0.5;
+
+struct Line {
+    // Input indices of the pieces, left to right
+    members: Vec<usize>,
+    bounds: (f32, f32, f32, f32),
+    // Height of the rightmost piece, which the next one is compared with
+    last_height: f32,
+    vertical: bool,
+}
+
+/// Join horizontally adjacent boxes of the same line, at most `max_gap` line heights apart. A
+/// joined line's box is the axis-aligned rectangle around its pieces with their mean score and
+/// empty text, to be read again; boxes left alone are returned unchanged. Lines keep the order of
+/// their first piece in `regions`.
+pub fn merge_adjacent(regions: Vec<Region>, max_gap: f32) -> Vec<Region> {
+    let mut order: Vec<usize> = (0..regions.len()).collect();
+    order.sort_by(|&a, &b| regions[a].bounds().0.total_cmp(&regions[b].bounds().0));
+
+    let mut lines: Vec<Line> = Vec::new();
+    for i in order {
+        let b = regions[i].bounds();
+        let height = b.3 - b.1;
+        // Columns the pipeline would read turned are vertical text, never joined
+        let vertical = direction::pipeline_choice(&regions[i].points) == Direction::Vertical;
+        let joined = if vertical || height <= 0.0 {
+            None
+        } else {
+            lines
+                .iter_mut()
+                .filter(|line| !line.vertical && continues(line, b, max_gap))
+                .min_by(|x, y| (b.0 - x.bounds.2).abs().total_cmp(&(b.0 - y.bounds.2).abs()))
+        };
+        match joined {
+            Some(line) => {
+                line.members.push(i);
+                line.bounds = (line.bounds.0.min(b.0), line.bounds.1.min(b.1), line.bounds.2.max(b.2), line.bounds.3.max(b.3));
+                line.last_height = height;
+            }
+            None => lines.push(Line { members: vec![i], bounds: b, last_height: height, vertical }),
+        }
+    }
+
+    lines.sort_by_key(|line| line.members.iter().copied().min());
+    lines
+        .into_iter()
+        .map(|line| {
+            if let [only] = line.members[..] {
+                return regions[only].clone();
+            }
+            let (x0, y0, x1, y1) = line.bounds;
+            let score = line.members.iter().map(|&i| regions[i].score).sum::<f32>() / line.members.len() as f32;
+            Region { points: vec![[x0, y0], [x1, y0], [x1, y1], [x0, y1]], text: String::new(), score }
+        })
+        .collect()
+}
+
+// Whether a piece with bounds `b` carries on `line` to its right
+fn continues(line: &Line, b: (f32, f32, f32, f32), max_gap: f32) -> bool {
+    let height = b.3 - b.1;
+    let (shorter, taller) = (height.min(line.last_height), height.max(line.last_height));
+    if shorter < taller * MIN_HEIGHT_RATIO {
+        return false;
+    }
+    let line_center = (line.bounds.1 + line.bounds.3) / 2.0;
+    if ((b.1 + b.3) / 2.0 - line_center).abs() > shorter * MAX_CENTER_OFFSET {
+        return false;
+    }
+    // Slight overlaps happen where the detector's boxes bleed into each other
+    let gap = b.0 - line.bounds.2;
+    gap >= -shorter * MAX_CENTER_OFFSET && gap <= taller * max_gap
+}
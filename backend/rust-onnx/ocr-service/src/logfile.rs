@@ -0,0 +1,157 @@
+// Logging setup. Output always goes to stderr as before; with OCR_LOG_DIR set it's also written to
+// `<dir>/ocr-service-YYYY-MM-DD.log` (local date), so a sidecar whose stderr nobody reads still
+// leaves a trail. A day's file that passes OCR_LOG_MAX_BYTES (default 10 MiB) continues in
+// `ocr-service-YYYY-MM-DD.1.log`, `.2.log`, ...; only the newest OCR_LOG_MAX_FILES (default 10)
+// files are kept.
+//
+// The level comes from RUST_LOG, else OCR_LOG_LEVEL (e.g. "info", "ocr_service=debug"), else
+// info when logging to a file and env_logger's default (errors only) otherwise.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+const PREFIX: &str = "ocr-service-";
+const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+const DEFAULT_MAX_FILES: usize = 10;
+
+pub fn init() {
+    let dir = std::env::var("OCR_LOG_DIR").ok().filter(|d| !d.trim().is_empty()).map(PathBuf::from);
+    let level = std::env::var("RUST_LOG")
+        .or_else(|_| std::env::var("OCR_LOG_LEVEL"))
+        .ok()
+        .or_else(|| dir.as_ref().map(|_| "info".to_string()));
+
+    let mut builder = env_logger::Builder::new();
+    if let Some(level) = &level {
+        builder.parse_filters(level);
+    }
+    let Some(dir) = dir else {
+        builder.init();
+        return;
+    };
+    match RotatingFile::open(dir.clone()) {
+        Ok(file) => {
+            builder.target(env_logger::Target::Pipe(Box::new(Tee { file }))).init();
+            log::info!("Logging to {}", dir.display());
+        }
+        Err(e) => {
+            builder.init();
+            log::warn!("OCR_LOG_DIR: could not open a log file in {}: {}; logging to stderr only", dir.display(), e);
+        }
+    }
+}
+
+// Every log line goes to stderr and the file; a failing file doesn't stop stderr output
+struct Tee {
+    file: RotatingFile,
+}
+
+impl Write for Tee {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = io::stderr().write_all(buf);
+        let _ = self.file.write_all(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let _ = io::stderr().flush();
+        self.file.flush()
+    }
+}
+
+struct RotatingFile {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    date: String,
+    index: u32,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    fn open(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.trim().parse::<u64>().ok());
+        let date = today();
+        // Continue today's newest file after a restart rather than starting another
+        let mut index = 0;
+        while path(&dir, &date, index + 1).exists() {
+            index += 1;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path(&dir, &date, index))?;
+        let written = file.metadata().map(|m| m.len()).unwrap_or(0);
+        let rotating = RotatingFile {
+            dir,
+            max_bytes: var("OCR_LOG_MAX_BYTES").unwrap_or(DEFAULT_MAX_BYTES).max(1),
+            max_files: var("OCR_LOG_MAX_FILES").map(|n| n as usize).unwrap_or(DEFAULT_MAX_FILES).max(1),
+            date,
+            index,
+            file,
+            written,
+        };
+        rotating.prune();
+        Ok(rotating)
+    }
+
+    // Move on to a new file when the day changes or this one is full
+    fn rotate_if_needed(&mut self) -> io::Result<()> {
+        let date = today();
+        if date == self.date && self.written < self.max_bytes {
+            return Ok(());
+        }
+        let index = if date == self.date { self.index + 1 } else { 0 };
+        self.file = OpenOptions::new().create(true).append(true).open(path(&self.dir, &date, index))?;
+        self.written = 0;
+        (self.date, self.index) = (date, index);
+        self.prune();
+        Ok(())
+    }
+
+    // Delete the oldest log files beyond max_files. Names sort by date; within a day by index.
+    fn prune(&self) {
+        let Ok(entries) = fs::read_dir(&self.dir) else { return };
+        let mut logs: Vec<(String, u32, PathBuf)> = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let stem = name.strip_prefix(PREFIX)?.strip_suffix(".log")?;
+                let (date, index) = match stem.split_once('.') {
+                    Some((date, index)) => (date.to_string(), index.parse().ok()?),
+                    None => (stem.to_string(), 0),
+                };
+                Some((date, index, entry.path()))
+            })
+            .collect();
+        logs.sort();
+        let excess = logs.len().saturating_sub(self.max_files);
+        for (_, _, path) in logs.into_iter().take(excess) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.rotate_if_needed()?;
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+fn path(dir: &std::path::Path, date: &str, index: u32) -> PathBuf {
+    match index {
+        0 => dir.join(format!("{}{}.log", PREFIX, date)),
+        n => dir.join(format!("{}{}.{}.log", PREFIX, date, n)),
+    }
+}
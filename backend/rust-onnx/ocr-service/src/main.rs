@@ -1,260 +1,2327 @@
 use actix_multipart::Multipart;
-use actix_web::{web, App, HttpResponse, HttpServer, Responder, get, post, middleware::Logger};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, Responder, ResponseError, get, post, put, route, middleware::Logger};
 use futures::{StreamExt, TryStreamExt};
+#[cfg(feature = "with-ocr")]
 use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 use std::env;
+#[cfg(feature = "with-ocr")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "with-ocr")]
+use tracing::{Instrument, info_span};
 
 #[cfg(feature = "with-ocr")]
-use oar_ocr::prelude::*;
-use image::{load_from_memory, ImageEncoder};
+use base64::Engine;
+#[cfg(feature = "with-ocr")]
+use image::RgbImage;
+use image::ImageEncoder;
 use image::codecs::png::PngEncoder;
 use image::ColorType;
-use env_logger;
 
+mod admission;
+mod auth;
+mod autocorrect;
+#[cfg(feature = "with-ocr")]
+mod charset;
+#[cfg(feature = "with-ocr")]
+mod coalesce;
+#[cfg(feature = "with-ocr")]
+mod color;
+mod config;
+mod cors;
+mod decode;
+#[cfg(feature = "with-ocr")]
+mod dedupe;
+mod deskew;
+mod error;
+#[cfg(feature = "with-ocr")]
+mod direction;
+#[cfg(feature = "with-ocr")]
+mod download;
+#[cfg(feature = "with-ocr")]
+mod export;
+mod finalize;
+mod font;
+#[cfg(feature = "with-ocr")]
+mod fetch;
+#[cfg(feature = "with-ocr")]
+mod handwriting;
+mod hocr;
+#[cfg(feature = "with-ocr")]
+mod idempotency;
+#[cfg(feature = "with-ocr")]
+mod imagehash;
+#[cfg(feature = "with-ocr")]
+mod inference;
+#[cfg(feature = "with-ocr")]
+mod lang;
+mod layout;
+mod logfile;
+#[cfg(feature = "with-ocr")]
+mod linemerge;
+#[cfg(feature = "with-ocr")]
+mod memory;
+mod metrics;
+#[cfg(feature = "with-ocr")]
+mod model;
+#[cfg(feature = "with-ocr")]
+mod multiscale;
+#[cfg(feature = "with-ocr")]
+mod orientation;
+#[cfg(feature = "with-ocr")]
+mod pdf;
+#[cfg(feature = "with-ocr")]
+mod pool;
+#[cfg(feature = "with-ocr")]
+mod preprocess;
+mod pretty;
+#[cfg(feature = "with-ocr")]
+mod preview;
+mod ratelimit;
+#[cfg(feature = "with-ocr")]
+mod region;
+mod request_id;
+#[cfg(feature = "with-ocr")]
+mod result_cache;
+mod schema;
+mod searchable_pdf;
+mod settings;
+mod shutdown;
+mod table;
+mod trace;
+
+use admission::{JobGate, JobSlot};
+use error::ApiError;
+#[cfg(feature = "with-ocr")]
+use fetch::{FetchConfig, FetchError};
+#[cfg(feature = "with-ocr")]
+use idempotency::{IdempotencyCache, Lookup};
+#[cfg(feature = "with-ocr")]
+use inference::{Inference, Ocr};
+use layout::Layout;
+#[cfg(feature = "with-ocr")]
+use memory::MemoryGuard;
+use metrics::Metrics;
+#[cfg(feature = "with-ocr")]
+use model::{ExecutionProvider, OcrModel, PredictParams};
+#[cfg(feature = "with-ocr")]
+use pdf::PdfError;
+#[cfg(feature = "with-ocr")]
+use pool::{LoadInfo, ModelRegistry, ModelSource, OcrPool, Resolved};
+#[cfg(feature = "with-ocr")]
+use region::Region;
+#[cfg(feature = "with-ocr")]
+use coalesce::{Coalescer, Join};
+#[cfg(feature = "with-ocr")]
+use result_cache::{CacheKey, ResultCache};
+
+#[cfg(feature = "with-ocr")]
+type OcrInner = ModelRegistry;
+
+#[cfg(not(feature = "with-ocr"))]
+type OcrInner = ();
+
+#[derive(Clone)]
+struct AppState {
+    // Loaded model pools by model id; handlers clone the Arc<OcrPool> they need out of the lock
+    #[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+    ocr: Arc<Mutex<OcrInner>>,
+    // Held by load, unload and lazy reloads for their whole run, so they apply one at a time and
+    // model_status only ever sees the registry before or after one of them
+    #[cfg(feature = "with-ocr")]
+    model_changes: Arc<tokio::sync::Mutex<()>>,
+    // Responses remembered per Idempotency-Key so client retries don't re-run OCR
+    #[cfg(feature = "with-ocr")]
+    idempotency: Arc<IdempotencyCache>,
+    // Queued + running OCR requests, capped so overload is a fast 503 rather than an endless wait
+    #[cfg(feature = "with-ocr")]
+    jobs: Arc<JobGate>,
+    // Every model call waits here so at most OCR_MAX_CONCURRENCY run at once, across all requests
+    #[cfg(feature = "with-ocr")]
+    inference: Arc<Inference>,
+    // In-flight draws (OCR_DRAW_CONCURRENCY), limited apart from OCR since each holds a full canvas
+    draws: Arc<JobGate>,
+    // OCR_MEMORY_LIMIT_MB auto-unload and the lazy reload that follows it
+    #[cfg(feature = "with-ocr")]
+    memory: Arc<MemoryGuard>,
+    // Recognize responses by image hash and options (OCR_CACHE_SIZE entries)
+    #[cfg(feature = "with-ocr")]
+    results: Arc<ResultCache>,
+    // Identical recognize requests in flight, so only the first runs OCR (OCR_COALESCE)
+    #[cfg(feature = "with-ocr")]
+    coalescer: Arc<Coalescer>,
+    metrics: Arc<Metrics>,
+    // Set once the server is running, for the shutdown endpoint
+    server: Arc<std::sync::OnceLock<actix_web::dev::ServerHandle>>,
+}
+
+impl AppState {
+    // Limits and caches as configured by the environment, with no model loaded
+    fn from_env() -> Self {
+        AppState {
+            ocr: Arc::default(),
+            #[cfg(feature = "with-ocr")]
+            model_changes: Arc::default(),
+            #[cfg(feature = "with-ocr")]
+            idempotency: Arc::new(IdempotencyCache::from_env()),
+            #[cfg(feature = "with-ocr")]
+            jobs: Arc::new(JobGate::from_env()),
+            #[cfg(feature = "with-ocr")]
+            inference: Arc::new(Inference::from_env()),
+            draws: Arc::new(JobGate::from_env_var("OCR_DRAW_CONCURRENCY", 2)),
+            #[cfg(feature = "with-ocr")]
+            memory: Arc::new(MemoryGuard::from_env()),
+            #[cfg(feature = "with-ocr")]
+            results: Arc::new(ResultCache::from_env()),
+            #[cfg(feature = "with-ocr")]
+            coalescer: Arc::new(Coalescer::from_env()),
+            metrics: Arc::new(Metrics::new()),
+            server: Arc::default(),
+        }
+    }
+}
+
+#[cfg(feature = "with-ocr")]
+#[derive(Serialize)]
+struct Message { message: String }
+
+#[cfg(feature = "with-ocr")]
+#[derive(Serialize)]
+struct ModelStatus {
+    loaded: bool,
+    // Model used when a request doesn't name one (the most recently loaded)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_model: Option<String>,
+    models: Vec<LoadedModel>,
+    // Execution provider and pool size of the default model; absent when nothing is loaded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provider: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workers: Option<usize>,
+    // Models unloaded under memory pressure, reloaded on next use
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unloaded_models: Vec<String>,
+    // OCR_MEMORY_LIMIT_MB, current RSS and the last auto-unload/reload
+    memory: serde_json::Value,
+    // Where the default model was loaded from and when; null when nothing is loaded
+    model: Option<serde_json::Value>,
+    // A load, unload or reload is under way; everything above is from before it
+    changing: bool,
+}
+
+#[cfg(feature = "with-ocr")]
+#[derive(Serialize)]
+struct LoadedModel {
+    model_id: String,
+    provider: &'static str,
+    workers: usize,
+}
+
+// Model id used by load/unload when the form doesn't give one
+#[cfg(feature = "with-ocr")]
+const DEFAULT_MODEL_ID: &str = "default";
+
+// HEAD too, for load balancer probes; actix sends the headers and drops the body
+#[route("/api/health/", method = "GET", method = "HEAD")]
+async fn health() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({"status": "ok"}))
+}
+
+/// Prometheus scrape endpoint (text exposition format).
+#[get("/metrics")]
+async fn prometheus_metrics(state: web::Data<AppState>) -> impl Responder {
+    #[cfg(feature = "with-ocr")]
+    let loaded = state.ocr.lock().unwrap().default_model().is_some();
+    #[cfg(not(feature = "with-ocr"))]
+    let loaded = false;
+    state.metrics.model_loaded.set(loaded as i64);
+
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(state.metrics.render())
+}
+
+/// OCR load: `queue_depth` requests currently queued or running, out of `max_queue_depth`
+/// before new ones are refused with 503 (OCR_MAX_QUEUE); `inference_running` model calls hold one
+/// of the `max_concurrency` inference permits (OCR_MAX_CONCURRENCY); `inference_calls` counts the
+/// model calls made since startup.
+#[cfg(feature = "with-ocr")]
+#[get("/api/ocr/stats")]
+async fn stats(state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({
+        "queue_depth": state.jobs.depth(),
+        "max_queue_depth": state.jobs.high_water(),
+        "inference_running": state.inference.running(),
+        "max_concurrency": state.inference.max_concurrency(),
+        "inference_calls": state.inference.calls(),
+    }))
+}
+
+#[cfg(not(feature = "with-ocr"))]
+#[get("/api/ocr/stats")]
+async fn stats(_state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
+}
+
+/// Load a model under `model_id` (form field, default "default") from `model_dir` (form field,
+/// else OCR_MODEL_DIR, else the config file, else the bundled path). Several ids can stay loaded at once; the most
+/// recently loaded one serves requests that don't pick a `model_id`.
+/// Each worker runs one throwaway predict on a blank image before the load returns, so the first
+/// request isn't slowed by ONNX Runtime's lazy setup; `warmup_ms` is its cost (null with
+/// OCR_SKIP_WARMUP=1).
+/// The det/rec/dict files are the configured names inside the model directory unless the
+/// `det_path`, `rec_path` or `dict_path` fields name others (absolute, or relative to the model
+/// directory), for custom or fine-tuned models; a given path that doesn't exist is a 400 naming it.
+/// Missing default files are downloaded from OCR_MODEL_URL_BASE (`<base>/<file name>`) when
+/// that's set, each checked against the sha256 listed in `<base>/SHA256SUMS`; a failed download
+/// or checksum mismatch leaves the model unloaded and is reported in the error.
+/// Loads, unloads and reloads of memory-parked models run one at a time, and a model appears in
+/// model_status only once fully loaded; `changing` there is true while one is under way.
+#[cfg(feature = "with-ocr")]
+#[post("/api/ocr/load")]
+async fn load_model(payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    let fields = read_text_fields(payload).await;
+    let model_id = fields.get("model_id").filter(|v| !v.is_empty()).cloned().unwrap_or_else(|| DEFAULT_MODEL_ID.to_string());
+    let source = match model_source(&fields, config::get()) { Ok(s) => s, Err(e) => return e.error_response() };
+
+    let _changing = state.model_changes.lock().await;
+    let load_source = source.clone();
+    let (pool, requested) = match web::block(move || load_pool(&load_source)).await {
+        Ok(Ok(loaded)) => loaded,
+        Ok(Err(e)) => return ApiError::Internal(e).error_response(),
+        Err(e) => return ApiError::Internal(format!("Task error: {}", e)).error_response(),
+    };
+    let (provider, workers) = (pool.provider(), pool.size());
+    let warmup_ms = pool.info().warmup_time.map(|t| t.as_millis());
+
+    state.ocr.lock().unwrap().insert(model_id.clone(), source, Arc::new(pool));
+    state.results.clear();
+    HttpResponse::Ok().json(serde_json::json!({
+        "message": "OCR model loaded successfully",
+        "model_id": model_id,
+        "provider": provider.name(),
+        "requested_provider": requested.name(),
+        "workers": workers,
+        "warmup_ms": warmup_ms,
+    }))
+}
+
+// Model directory and files for a load request: the model_dir, det_path, rec_path and dict_path
+// fields, each defaulting to `config`. Files named explicitly must exist (nothing is downloaded
+// for them), so a typo is a 400 naming the path rather than a failed build.
+#[cfg(feature = "with-ocr")]
+fn model_source(fields: &std::collections::HashMap<String, String>, config: &config::Config) -> Result<ModelSource, ApiError> {
+    let field = |name: &str| fields.get(name).map(|v| v.trim()).filter(|v| !v.is_empty()).map(str::to_string);
+    let source = ModelSource {
+        dir: field("model_dir").unwrap_or_else(|| config.model_dir.clone()),
+        det: field("det_path").unwrap_or_else(|| config.det.clone()),
+        rec: field("rec_path").unwrap_or_else(|| config.rec.clone()),
+        dict: field("dict_path").unwrap_or_else(|| config.dict.clone()),
+    };
+    for (name, file) in [("det_path", &source.det), ("rec_path", &source.rec), ("dict_path", &source.dict)] {
+        let path = source.path(file);
+        if field(name).is_some() && !std::path::Path::new(&path).is_file() {
+            return Err(ApiError::InvalidField(format!("Invalid '{}': file not found: {}", name, path)));
+        }
+    }
+    Ok(source)
+}
+
+// OCR_WORKERS instances of the model from `source` on the OCR_EP provider, with the provider
+// that was asked for. Blocking; the first instance settles the provider for the rest.
+// Missing det/rec/dict files are downloaded first when OCR_MODEL_URL_BASE is set.
+#[cfg(feature = "with-ocr")]
+fn load_pool(source: &ModelSource) -> Result<(OcrPool, ExecutionProvider), String> {
+    let model_dir = source.dir.as_str();
+    let (det, rec, dict) = (source.path(&source.det), source.path(&source.rec), source.path(&source.dict));
+    // Fetched from OCR_MODEL_URL_BASE if missing; the pool isn't built unless they verify
+    download::ensure_files(&[&det, &rec, &dict])?;
+    // optional text line orientation classifier, used when requests ask for use_cls
+    let cls = format!("{}/pp-lcnet_x0_25_textline_ori.onnx", model_dir);
+    let cls = if std::path::Path::new(&cls).exists() { Some(cls) } else { None };
+    // optional document orientation classifier for auto_rotate
+    let doc_ori = format!("{}/pp-lcnet_x1_0_doc_ori.onnx", model_dir);
+    let doc_ori = if std::path::Path::new(&doc_ori).exists() { Some(doc_ori) } else { None };
+    // execution provider from OCR_EP; falls back to CPU if it can't be initialized
+    let requested = ExecutionProvider::from_config()?;
+
+    let started = Instant::now();
+    let workers = OcrPool::configured_workers();
+    let mut models: Vec<OcrModel> = Vec::with_capacity(workers);
+    let mut provider = requested;
+    for _ in 0..workers {
+        let model = OcrModel::load(det.clone(), rec.clone(), dict.clone(), cls.clone(), doc_ori.clone(), provider)
+            .map_err(|e| format!("Failed to build model: {}", e))?;
+        provider = model.provider();
+        models.push(model);
+    }
+    let load_time = started.elapsed();
+
+    // Run each instance once so the first real request doesn't pay for ONNX Runtime's lazy setup
+    let warmup_time = if skip_warmup() {
+        None
+    } else {
+        let started = Instant::now();
+        for model in models.iter() {
+            model.warm_up().map_err(|e| format!("Model warmup failed: {}", e))?;
+        }
+        Some(started.elapsed())
+    };
+    let info = LoadInfo {
+        model_dir: model_dir.to_string(),
+        det: source.det.clone(),
+        rec: source.rec.clone(),
+        dict: source.dict.clone(),
+        loaded_at: std::time::SystemTime::now(),
+        load_time,
+        warmup_time,
+        orientation_classifier: doc_ori.is_some(),
+    };
+    Ok((OcrPool::new(models, info), requested))
+}
+
+// OCR_SKIP_WARMUP=1/true loads models without the warmup pass, for fast startup in tests
+#[cfg(feature = "with-ocr")]
+fn skip_warmup() -> bool {
+    matches!(env::var("OCR_SKIP_WARMUP").as_deref().map(str::trim), Ok("1") | Ok("true"))
+}
+
+#[cfg(not(feature = "with-ocr"))]
+#[post("/api/ocr/load")]
+async fn load_model(_payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
+}
+
+/// Unload the model named by the `model_id` form field, or every model when it's absent
+#[cfg(feature = "with-ocr")]
+#[post("/api/ocr/unload")]
+async fn unload_model(payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    let fields = read_text_fields(payload).await;
+    // Waits for a load or reload in progress, so it can't put back a model unloaded meanwhile
+    let _changing = state.model_changes.lock().await;
+    // Requests already holding a model finish on it; the pool is freed once the last one returns
+    let mut guard = state.ocr.lock().unwrap();
+    state.results.clear();
+    match fields.get("model_id").filter(|v| !v.is_empty()) {
+        Some(id) => {
+            if !guard.remove(id) {
+                return ApiError::UnknownModel(id.to_string()).error_response();
+            }
+            HttpResponse::Ok().json(Message{ message: format!("OCR model '{}' unloaded", id) })
+        }
+        None => {
+            guard.clear();
+            HttpResponse::Ok().json(Message{ message: "OCR model unloaded".into() })
+        }
+    }
+}
+
+#[cfg(not(feature = "with-ocr"))]
+#[post("/api/ocr/unload")]
+async fn unload_model(_payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
+}
+
+#[cfg(feature = "with-ocr")]
+#[route("/api/ocr/model_status", method = "GET", method = "HEAD")]
+async fn model_status(state: web::Data<AppState>) -> impl Responder {
+    let changing = state.model_changes.try_lock().is_err();
+    let guard = state.ocr.lock().unwrap();
+    let default_model = guard.default_model();
+    HttpResponse::Ok().json(ModelStatus{
+        changing,
+        loaded: default_model.is_some(),
+        default_model: default_model.as_ref().map(|(id, _)| id.to_string()),
+        models: guard
+            .iter()
+            .map(|(id, pool)| LoadedModel { model_id: id.to_string(), provider: pool.provider().name(), workers: pool.size() })
+            .collect(),
+        provider: default_model.as_ref().map(|(_, p)| p.provider().name()),
+        workers: default_model.as_ref().map(|(_, p)| p.size()),
+        unloaded_models: guard.parked_ids().map(str::to_string).collect(),
+        memory: state.memory.status(),
+        model: default_model.as_ref().map(|(_, p)| {
+            let info = p.info();
+            serde_json::json!({
+                "model_dir": info.model_dir,
+                "det": info.det,
+                "rec": info.rec,
+                "dict": info.dict,
+                "provider": p.provider().name(),
+                "loaded_at": info.loaded_at.duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+                "load_ms": info.load_time.as_millis(),
+                "warmup_ms": info.warmup_time.map(|t| t.as_millis()),
+            })
+        }),
+    })
+}
+
+#[cfg(not(feature = "with-ocr"))]
+#[route("/api/ocr/model_status", method = "GET", method = "HEAD")]
+async fn model_status(_state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({"loaded": false, "model": null, "note": "ocr-service built without feature 'with-ocr'"}))
+}
+
+/// Model directories on disk, for picking one to `/api/ocr/load`: each subdirectory of
+/// OCR_MODELS_ROOT (default ../models) as `{"name", "model_dir", "complete", "missing"}`, where
+/// `missing` lists whichever of the configured det/rec/dict files it lacks. A root that doesn't
+/// exist gives an empty list.
+#[get("/api/ocr/available_models")]
+async fn available_models() -> impl Responder {
+    let root = std::env::var("OCR_MODELS_ROOT").ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).unwrap_or_else(|| "../models".to_string());
+    let config = config::get();
+    let required = [&config.det, &config.rec, &config.dict];
+
+    let mut dirs: Vec<std::path::PathBuf> = match std::fs::read_dir(&root) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir()).collect(),
+        Err(_) => Vec::new(),
+    };
+    dirs.sort();
+    let models: Vec<serde_json::Value> = dirs
+        .iter()
+        .map(|dir| {
+            let missing: Vec<&String> = required.iter().copied().filter(|file| !dir.join(file).is_file()).collect();
+            serde_json::json!({
+                "name": dir.file_name().map(|n| n.to_string_lossy().into_owned()),
+                "model_dir": dir.to_string_lossy(),
+                "complete": missing.is_empty(),
+                "missing": missing,
+            })
+        })
+        .collect();
+    HttpResponse::Ok().json(serde_json::json!({"root": root, "models": models}))
+}
+
+/// Saved recognition defaults: `det_db_thresh`, `cls_thresh`, `use_cls` and `drop_score` as
+/// recognize (and the other OCR endpoints, for the thresholds) apply them when the form field is
+/// absent, plus `saved`, the stored values (null where the startup config's default applies).
+#[get("/api/ocr/settings")]
+async fn get_settings() -> impl Responder {
+    HttpResponse::Ok().json(settings_json(&settings::get()))
+}
+
+/// Replace the saved defaults with a JSON object of any of `det_db_thresh`, `cls_thresh`,
+/// `drop_score` (0..=1) and `use_cls`; fields left out (or null) go back to the config's defaults.
+/// Written to OCR_SETTINGS_PATH (default ocr-settings.json next to the binary) and reloaded at
+/// startup. Answers like GET.
+#[put("/api/ocr/settings")]
+async fn put_settings(body: web::Json<serde_json::Value>) -> impl Responder {
+    let new: settings::Settings = match serde_json::from_value(body.into_inner()) {
+        Ok(s) => s,
+        Err(e) => return ApiError::InvalidField(format!("Invalid settings: {}", e)).error_response(),
+    };
+    if let Err(e) = settings::put(new.clone()) {
+        return e.error_response();
+    }
+    HttpResponse::Ok().json(settings_json(&new))
+}
+
+fn settings_json(saved: &settings::Settings) -> serde_json::Value {
+    let config = config::get();
+    // The config's f32 thresholds by their shortest decimal form, like the saved f64 ones
+    let short = |v: f32| v.to_string().parse::<f64>().unwrap_or(v as f64);
+    serde_json::json!({
+        "det_db_thresh": saved.det_db_thresh.unwrap_or(short(config.det_db_thresh)),
+        "cls_thresh": saved.cls_thresh.unwrap_or(short(config.cls_thresh)),
+        "use_cls": saved.use_cls.unwrap_or(config.use_cls),
+        "drop_score": saved.drop_score.unwrap_or(0.0),
+        "saved": saved,
+    })
+}
+
+/// Accepts multipart form with `file` (or `url` to download the image from) and optional form fields:
+/// det_db_thresh (f32), cls_thresh (f32), use_cls (bool)
+/// use_cls/cls_thresh only take effect when the model dir has a text line orientation model.
+/// `det_limit_side_len` (32..=4000, default 960) caps the longest side the detector sees: lower is
+/// faster, higher finds smaller text. Boxes are always in the uploaded image's coordinates.
+/// PDF uploads are rasterized at `dpi` (default 300) and return one inner line array per page;
+/// multi-page TIFFs and animated GIFs likewise return one per frame (at most 100 frames).
+/// `return_prob_map=true` adds `prob_map`: one base64 grayscale PNG per page of the detector's
+/// pre-threshold probability output, for diagnosing why boxes were or weren't formed.
+/// `group_by_color=true` adds `color_groups`: lines clustered by the background (highlight) color
+/// sampled behind each box, `color_clusters` groups at most (default 3), across all pages.
+/// `dedupe_across_batch=true` (multi-page uploads) drops lines that repeat on at least
+/// `repeat_threshold` of the pages (fraction, default 0.5) and lists them in `repeated_lines`.
+/// `multi_scale=true` runs the pipeline at several scales (`scales`, comma-separated, default
+/// 0.75,1.0,1.5, at most 4) and merges the boxes with NMS; each scale is a full extra pass.
+/// `model_id` picks one of the loaded models (default: the most recently loaded).
+/// `normalize_text=true` folds full-width characters and collapses whitespace in each line's text;
+/// with `with_raw=true` a line whose text changed gets a third element `{"raw_text": ...}`.
+/// `with_text=true` adds `text`: the lines joined with newlines, as ocr2text returns them.
+/// `with_ids=true` adds `{"id": ...}` as a line's third element (merged with `raw_text`): a hash
+/// of the page index, the box center snapped to a 16px grid and the normalized text, so a line
+/// keeps its id across re-runs as long as its text is unchanged and it hasn't moved to another
+/// grid cell. Any text correction yields a new id.
+/// Box points are clamped to the page (`clip_boxes=false` keeps the detector's raw coordinates,
+/// which can fall slightly outside the image).
+/// `beam_width` is accepted for forward compatibility but only 1 (greedy CTC decoding, the
+/// recognizer's only mode) is valid; wider beams would trade latency for accuracy.
+/// `charset_whitelist` (e.g. "0123456789ABCDEFGHJKLMNPRSTUVWXYZ") restricts each line's text to
+/// those characters: `charset_mode=drop` (default) removes the others, `charset_mode=map` first
+/// swaps them for a look-alike allowed one (O/0, I/1/L, S/5, B/8, Z/2, G/6, A/4, letter case).
+/// Scores shrink with the share of changed characters (a mapped one counts half).
+/// Preprocessing, applied in this order after any auto_rotate turn: `max_side` shrinks pages whose
+/// longest side is larger (boxes are scaled back to the uploaded size), `grayscale=true`, then
+/// `contrast` (factor around mid-gray, 0..=10). `prob_map` shows the preprocessed page.
+/// `auto_rotate=true` turns each page upright (90/180/270 degrees) before OCR, using the model
+/// dir's pp-lcnet_x1_0_doc_ori.onnx if present, else a heuristic that OCRs candidate turns; the
+/// turn is `meta.rotation_deg` (plus `meta.page_rotations` for multi-page uploads) and boxes are in
+/// the turned image's coordinates, so `draw` needs the image turned the same way.
+/// `detect_doc_lang=true` adds `doc_lang: {"code", "confidence"}`: the document's dominant
+/// language from the scripts of each line's letters, lines weighted by length; codes follow the
+/// recognition model names (ch, japan, korean, latin, cyrillic, ...). When the runner-up covers at
+/// least a quarter of the text, `doc_lang.mixed` lists the top two with their proportions.
+/// `Accept: application/vnd.ocr.v2+json` or `?schema=v2` replaces `result` with `regions`:
+/// `[{"box": [[x, y], ...], "text", "score"}]`, any third-element fields (`id`, `raw_text`,
+/// `style`, ...) merged into each region and `page` (1-based) added for multi-page uploads;
+/// `color_groups` lines follow suit. draw, ocr2text, export, hocr and searchable_pdf accept it.
+/// `text_direction` (horizontal, vertical or auto) sets how each line is read: vertical turns the
+/// crop 90 degrees counter-clockwise so a top-to-bottom column reads in order, auto picks vertical
+/// for boxes taller than wide. Without it the pipeline turns boxes at least 1.5x taller than wide.
+/// Each line's third element gets `direction` (a field of the region in v2).
+/// `detect_language=true` gives each line's third element a `lang` (a field of the region in v2):
+/// the language of its own text by the same script rules as `doc_lang`, or null for lines without
+/// letters (empty, digits or punctuation only). Latin-script languages are all "latin".
+/// `with_chars=true` adds `chars` to each line's third element (a field of the region in v2),
+/// meant as `[{"char", "score"}]` per recognized character. The oar_ocr recognizer only reports a
+/// line-level score, so `chars` is null for every line and a `warnings` entry says why.
+/// `detect_only=true` runs the detector alone: each line is `[box, [null, box_score]]` (v2:
+/// `text: null`, `score` the detector's confidence) and `meta.recognition_skipped` is true. The
+/// recognizer, line orientation classifier, `multi_scale`, `text_direction` re-reads,
+/// `charset_whitelist` and `dedupe_across_batch` are skipped; `drop_score` applies to the box
+/// score. `auto_rotate` still reads the page when the model has no orientation classifier.
+/// `merge_boxes=true` joins detection boxes that split one line before reading them: pieces of
+/// similar height on the same baseline, at most `merge_gap` line heights apart (0..=10, default
+/// 1), become one axis-aligned box read as a whole. Boxes are read without the line orientation
+/// classifier, so `use_cls` has no effect. Ignored with `multi_scale`; with `detect_only` the
+/// joined boxes are returned unread, scored with their pieces' mean.
+/// `coords=normalized` (default `pixel`) divides each box point's x by its page's width and y by
+/// its height, so boxes are in 0..=1 (clipped boxes) for overlaying on a resized canvas.
+/// `meta.coords` says so; `meta.width`/`height` give the first page's size to convert back, and
+/// multi-page uploads add `meta.page_sizes: [[width, height], ...]`. draw takes either kind.
+/// `drop_score` (0..=1, default 0) leaves out lines scoring below it, counted in
+/// `meta.filtered_regions`; boxes left unread by `time_budget_ms` have no score and are only kept
+/// at 0. Scores are compared after any `charset_whitelist` penalty.
+/// `meta` reports decode/inference/total milliseconds, the first page's size and the line count.
+/// At most OCR_MAX_CONCURRENCY (default: CPU cores) model calls run at once across all requests;
+/// the rest wait their turn, and `meta.queue_wait_ms` is how long this request's calls waited in all.
+/// The same image with the same fields is answered from an in-memory cache (OCR_CACHE_SIZE
+/// entries, 0 disables); `meta.cached` tells which.
+/// An identical request arriving while one is still running waits for that one's response
+/// (`meta.coalesced`) instead of running OCR again; OCR_COALESCE=0 turns this off.
+/// `classify_handwriting=true` adds `{"style": "printed"|"handwritten", "style_confidence"}` to
+/// each line's third element (merged like `id`). No handwriting classifier model is supported
+/// yet, so the style comes from a stroke-width heuristic on the line crop and the response carries
+/// a `warnings` entry saying so.
+/// `echo_params=true` adds `params`: the settings the request actually ran with after defaults and
+/// validation (thresholds, detector size, dpi, preprocessing, rotation, multi-scale, text
+/// post-processing and the model id), so a stored result says how it was produced.
+/// `time_budget_ms` caps how long recognition goes on: once a page finishes past the budget
+/// (counted from the request's arrival), the remaining pages are only run through detection, so
+/// every box is still returned but those lines come back as ["", 0.0]. The response then has
+/// `"partial": true` and `unrecognized_boxes`, and isn't cached. A page already being read is
+/// never interrupted, so a single-page upload always completes in full.
+/// An `Idempotency-Key` header makes retries with the same key, image and fields return the first
+/// response without re-running OCR; reusing a key for a different image or options is a 409.
+#[cfg(feature = "with-ocr")]
+#[post("/api/ocr/")]
+async fn recognize(req: HttpRequest, payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    state.metrics.requests.inc();
+    let response = match admit(&state) {
+        Ok(_slot) => recognize_request(req, payload, &state).instrument(info_span!("recognize")).await,
+        Err(e) => e.error_response(),
+    };
+    if !response.status().is_success() {
+        state.metrics.failures.inc();
+    }
+    response
+}
+
+#[cfg(feature = "with-ocr")]
+async fn recognize_request(req: HttpRequest, mut payload: Multipart, state: &AppState) -> HttpResponse {
+    let started = Instant::now();
+    let saved = settings::get();
+    let mut form = match read_recognize(&mut payload, &saved).await { Ok(f) => f, Err(e) => return e.error_response() };
+
+    let v2 = match schema::wants_v2(&req) { Ok(v) => v, Err(e) => return e.error_response() };
+    if v2 {
+        form.fields.insert("schema".into(), "v2".into());
+    }
+    // Saved defaults stood in for absent fields, so results cached under other ones don't apply
+    if !saved.is_empty() {
+        form.fields.insert("settings".into(), serde_json::to_string(&saved).unwrap_or_default());
+    }
+
+    let bytes = match (form.file.take(), form.url.take()) {
+        (Some(_), Some(_)) => return ApiError::InvalidField("send either file or url, not both".into()).error_response(),
+        (Some(b), None) => b,
+        (None, Some(url)) => match fetch_url(url).instrument(info_span!("fetch")).await { Ok(b) => b, Err(e) => return e.error_response() },
+        (None, None) => return ApiError::MissingField("file").error_response(),
+    };
+    // Both caches answer before any model is selected, which may mean reloading a parked one
+    let cache_key = CacheKey::new(&bytes, &form.fields);
+    let idempotency_key = req.headers().get("Idempotency-Key").and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    if let Some(key) = &idempotency_key {
+        match state.idempotency.lookup(key, &cache_key) {
+            Lookup::Hit(response) => return HttpResponse::Ok().json(response),
+            Lookup::Conflict => return ApiError::Conflict("Idempotency-Key was already used with a different image or options".into()).error_response(),
+            Lookup::Miss => {}
+        }
+    }
+
+    if let Some(mut response) = state.results.get(&cache_key) {
+        response["meta"]["cached"] = serde_json::json!(true);
+        response["meta"]["total_ms"] = serde_json::json!(started.elapsed().as_millis());
+        log_recognized(&response["meta"], "cached");
+        if let Some(key) = idempotency_key {
+            state.idempotency.store(key, cache_key, response.clone());
+        }
+        return HttpResponse::Ok().json(response);
+    }
+    let leader = match state.coalescer.join(&cache_key) {
+        Some(Join::Leader(leader)) => Some(leader),
+        Some(Join::Follower(receiver)) => match coalesce::wait(receiver).instrument(info_span!("coalesced")).await {
+            Some(mut response) => {
+                response["meta"]["coalesced"] = serde_json::json!(true);
+                response["meta"]["total_ms"] = serde_json::json!(started.elapsed().as_millis());
+                log_recognized(&response["meta"], "coalesced");
+                if let Some(key) = idempotency_key {
+                    state.idempotency.store(key, cache_key, response.clone());
+                }
+                return HttpResponse::Ok().json(response);
+            }
+            // The first request failed; try again here
+            None => None,
+        },
+        None => None,
+    };
+    let pool = match select_model(state, form.model_id.as_deref()).await { Ok(p) => p, Err(e) => return e.error_response() };
+    // The default model as of now; a load during OCR would change it
+    let echo = form.echo_params.then(|| {
+        let model_id = form.model_id.clone().or_else(|| state.ocr.lock().unwrap().default_model().map(|(id, _)| id.to_string()));
+        form.echo(model_id)
+    });
+
+    let decode_started = Instant::now();
+    let pages = if pdf::is_pdf(&bytes) {
+        render_pdf(bytes, form.dpi).instrument(info_span!("decode", kind = "pdf")).await
+    } else {
+        let _decode = info_span!("decode", kind = "image").entered();
+        decode::frames(&bytes)
+    };
+    let pages = match pages { Ok(p) => p, Err(e) => return e.error_response() };
+    let decode_ms = decode_started.elapsed().as_millis();
+    let page_count = pages.len();
+    // First page size as OCR saw it, i.e. after any auto_rotate turn
+    let (mut width, mut height) = (0, 0);
+    let mut rotations: Vec<u32> = Vec::new();
+
+    // Time spent in the OCR pipeline, including any wait for a free worker
+    let mut inference = Duration::ZERO;
+    let mut prob_maps: Vec<String> = Vec::new();
+    let mut page_regions: Vec<Vec<Region>> = Vec::with_capacity(pages.len());
+    // Background color sampled behind each region, kept alongside it when grouping by color
+    let mut page_colors: Vec<Vec<[u8; 3]>> = Vec::new();
+    // Printed/handwritten guess per region, kept alongside it like the colors
+    let mut page_styles: Vec<Vec<(handwriting::Style, f32)>> = Vec::new();
+    // Reading direction per region, with text_direction
+    let mut page_directions: Vec<Vec<direction::Direction>> = Vec::new();
+    // Each page's size as OCR saw it, the divisor for coords=normalized
+    let mut page_sizes: Vec<(u32, u32)> = Vec::with_capacity(pages.len());
+    // Boxes on pages past the time budget, detected but not read
+    let mut unrecognized: Option<usize> = None;
+    // Regions dropped for scoring under drop_score
+    let mut filtered: usize = 0;
+    let params = form.params;
+    let ocr = Ocr::new(&pool, &state.inference);
+    for (page_idx, mut page) in pages.into_iter().enumerate() {
+        if form.auto_rotate {
+            let started = Instant::now();
+            let rotation = match page_rotation(&ocr, &page, params).instrument(info_span!("orientation", page = page_idx)).await { Ok(r) => r, Err(e) => return e.error_response() };
+            inference += started.elapsed();
+            page = orientation::rotate(page, rotation);
+            rotations.push(rotation);
+        }
+        if page_idx == 0 {
+            (width, height) = page.dimensions();
+        }
+        let sample_from = if form.group_by_color || form.classify_handwriting || form.text_direction.is_some() { Some(page.clone()) } else { None };
+        let (page_width, page_height) = page.dimensions();
+        page_sizes.push((page_width, page_height));
+        let (page, factor) = if form.preprocess.is_noop() {
+            (page, 1.0)
+        } else {
+            let _preprocess = info_span!("preprocess", page = page_idx).entered();
+            form.preprocess.apply(page)
+        };
+        if form.return_prob_map {
+            match prob_map_png(&ocr, page.clone(), params.det_limit_side_len).instrument(info_span!("prob_map", page = page_idx)).await { Ok(png) => prob_maps.push(png), Err(e) => return e.error_response() }
+        }
+        let inference_started = Instant::now();
+        // The first page is always read, however long the upload took to arrive and decode
+        let over_budget = page_idx > 0 && form.time_budget.is_some_and(|budget| started.elapsed() >= budget);
+        let ocr_span = info_span!("ocr", page = page_idx, multi_scale = form.multi_scale, over_budget);
+        let regions = if form.detect_only {
+            let detected = run_detection(&ocr, page, params).instrument(ocr_span).await;
+            detected.map(|regions| if form.merge_boxes { linemerge::merge_adjacent(regions, form.merge_gap) } else { regions })
+        } else if over_budget {
+            // Unread, so no score; the detector's would read as a recognition score
+            run_detection(&ocr, page, params)
+                .instrument(ocr_span)
+                .await
+                .map(|regions| regions.into_iter().map(|r| Region { score: 0.0, ..r }).collect())
+        } else if form.multi_scale {
+            run_ocr_multi_scale(&ocr, page, params, &form.scales).instrument(ocr_span).await
+        } else if form.merge_boxes {
+            run_ocr_merged(&ocr, page, params, form.merge_gap).instrument(ocr_span).await
+        } else {
+            run_ocr(&ocr, page, params).instrument(ocr_span).await
+        };
+        inference += inference_started.elapsed();
+        let mut regions = match regions { Ok(r) => r, Err(e) => return e.error_response() };
+        if factor != 1.0 {
+            regions = regions.into_iter().map(|r| r.scaled(1.0 / factor)).collect();
+        }
+        if form.clip_boxes {
+            regions.iter_mut().for_each(|r| r.clip_to(page_width, page_height));
+        }
+        let mut directions = match (form.text_direction, &sample_from) {
+            (Some(mode), Some(img)) => {
+                let chosen: Vec<direction::Direction> = regions.iter().map(|r| direction::choose(mode, &r.points)).collect();
+                // Unread boxes past the time budget stay unread
+                if !over_budget && !form.detect_only && let Err(e) = reread_directed(&ocr, img, &mut regions, &chosen).instrument(info_span!("text_direction", page = page_idx)).await {
+                    return e.error_response();
+                }
+                Some(chosen)
+            }
+            _ => None,
+        };
+        if let Some(whitelist) = form.charset_whitelist.as_ref().filter(|_| !form.detect_only) {
+            regions.iter_mut().for_each(|r| charset::apply(r, whitelist, form.charset_mode));
+        }
+        // After the charset so its score penalty counts; unread boxes score 0 and only survive a 0 threshold
+        let keep: Vec<bool> = regions.iter().map(|r| r.score >= form.drop_score).collect();
+        if let Some(directions) = directions.as_mut() {
+            let mut k = keep.iter();
+            directions.retain(|_| *k.next().unwrap());
+        }
+        let detected = regions.len();
+        let mut k = keep.iter();
+        regions.retain(|_| *k.next().unwrap());
+        filtered += detected - regions.len();
+        if let Some(directions) = directions {
+            page_directions.push(directions);
+        }
+        if over_budget {
+            *unrecognized.get_or_insert(0) += regions.len();
+        }
+        if let Some(img) = sample_from {
+            if form.group_by_color {
+                page_colors.push(regions.iter().map(|r| color::background_color(&img, r)).collect());
+            }
+            if form.classify_handwriting {
+                let _styles = info_span!("handwriting", page = page_idx).entered();
+                let style = |r: &Region| model::crop_polygon(&img, &r.points).map(|c| handwriting::classify(&c)).unwrap_or((handwriting::Style::Printed, 0.5));
+                page_styles.push(regions.iter().map(style).collect());
+            }
+        }
+        page_regions.push(regions);
+    }
+
+    let postprocess = info_span!("postprocess").entered();
+    // Without text every line would look repeated
+    let repeated = if form.dedupe_across_batch && !form.detect_only {
+        let repeated = dedupe::find_repeated(&page_regions, form.repeat_threshold);
+        for (i, regions) in page_regions.iter_mut().enumerate() {
+            let keep: Vec<bool> = regions.iter().map(|r| !dedupe::is_repeated(r, &repeated)).collect();
+            if let Some(colors) = page_colors.get_mut(i) {
+                let mut k = keep.iter();
+                colors.retain(|_| *k.next().unwrap());
+            }
+            if let Some(styles) = page_styles.get_mut(i) {
+                let mut k = keep.iter();
+                styles.retain(|_| *k.next().unwrap());
+            }
+            if let Some(directions) = page_directions.get_mut(i) {
+                let mut k = keep.iter();
+                directions.retain(|_| *k.next().unwrap());
+            }
+            let mut k = keep.iter();
+            regions.retain(|_| *k.next().unwrap());
+        }
+        Some(repeated)
+    } else {
+        None
+    };
+
+    drop(postprocess);
+
+    let _serialize = info_span!("serialize").entered();
+    // Convert result into Python-compatible structure
+    // Python format: {"result": [ [box_points, [text,score]], ... ] }, one inner array per page
+    let result: Vec<Vec<serde_json::Value>> = page_regions
+        .iter()
+        .enumerate()
+        .map(|(p, regions)| {
+            let ids = if form.with_ids { region::stable_ids(p, regions) } else { Vec::new() };
+            regions
+                .iter()
+                .enumerate()
+                .map(|(i, region)| {
+                    let info = LineInfo {
+                        id: ids.get(i).cloned(),
+                        style: page_styles.get(p).map(|styles| styles[i]),
+                        direction: page_directions.get(p).map(|directions| directions[i]),
+                        page_size: page_sizes[p],
+                    };
+                    form.line(region, info)
+                })
+                .collect()
+        })
+        .collect();
+    // Same text as ocr2text gives for this result, so callers that want both need one request
+    let text = form.with_text.then(|| result.iter().flat_map(|lines| layout::page_text(lines, Layout::Detection)).collect::<Vec<_>>().join("\n"));
+    let color_groups = form.group_by_color.then(|| {
+        // Clustered across all pages; member indices follow page order
+        let lines: Vec<&serde_json::Value> = result.iter().flatten().collect();
+        let colors: Vec<[u8; 3]> = page_colors.into_iter().flatten().collect();
+        color::cluster(&colors, form.color_clusters)
+            .into_iter()
+            .map(|g| serde_json::json!({
+                "color": color::hex(g.color),
+                "lines": g.members.iter().map(|&i| lines[i].clone()).collect::<Vec<_>>(),
+            }))
+            .collect::<Vec<_>>()
+    });
+    let mut response = serde_json::json!({"result": result});
+    let warnings = form.warnings();
+    if !warnings.is_empty() {
+        response["warnings"] = serde_json::json!(warnings);
+    }
+    if form.return_prob_map {
+        response["prob_map"] = serde_json::json!(prob_maps);
+    }
+    if let Some(repeated) = repeated {
+        response["repeated_lines"] = repeated
+            .iter()
+            .map(|rep| serde_json::json!({"text": rep.text, "pages": rep.pages}))
+            .collect();
+    }
+    if let Some(groups) = color_groups {
+        response["color_groups"] = serde_json::json!(groups);
+    }
+    if v2 {
+        // Same lines, one object each; `page` tells multi-page uploads apart
+        let page_of = |i: usize| (page_count > 1).then_some(i + 1);
+        let result = response.as_object_mut().unwrap().remove("result").unwrap_or_default();
+        let regions: Vec<serde_json::Value> = result
+            .as_array()
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .flat_map(|(i, lines)| lines.as_array().into_iter().flatten().map(move |line| schema::region(line, page_of(i))))
+            .collect();
+        response["regions"] = serde_json::json!(regions);
+        if let Some(groups) = response.get_mut("color_groups").and_then(|g| g.as_array_mut()) {
+            for group in groups.iter_mut() {
+                let lines: Vec<serde_json::Value> = group["lines"].as_array().into_iter().flatten().map(|line| schema::region(line, None)).collect();
+                group["lines"] = serde_json::json!(lines);
+            }
+        }
+    }
+    if let Some(echo) = echo {
+        response["params"] = echo;
+    }
+    if let Some(count) = unrecognized {
+        response["partial"] = serde_json::json!(true);
+        response["unrecognized_boxes"] = serde_json::json!(count);
+    }
+    if form.detect_doc_lang {
+        let texts = page_regions.iter().flatten().map(|r| r.text.as_str());
+        response["doc_lang"] = lang::detect_document(texts).map(|d| d.to_json()).unwrap_or(serde_json::Value::Null);
+    }
+    if let Some(text) = text {
+        response["text"] = serde_json::json!(text);
+    }
+    state.metrics.inference_seconds.observe(inference.as_secs_f64());
+    // Timing and input size for performance debugging; width/height are those of the first page
+    response["meta"] = serde_json::json!({
+        "decode_ms": decode_ms,
+        "queue_wait_ms": ocr.queue_wait().as_millis(),
+        "inference_ms": inference.as_millis(),
+        "total_ms": started.elapsed().as_millis(),
+        "width": width,
+        "height": height,
+        "pages": page_count,
+        "regions": page_regions.iter().map(Vec::len).sum::<usize>(),
+        "filtered_regions": filtered,
+        "cached": false,
+    });
+    if form.detect_only {
+        response["meta"]["recognition_skipped"] = serde_json::json!(true);
+    }
+    if form.normalized_coords {
+        response["meta"]["coords"] = serde_json::json!("normalized");
+        if page_count > 1 {
+            response["meta"]["page_sizes"] = page_sizes.iter().map(|&(w, h)| serde_json::json!([w, h])).collect();
+        }
+    }
+    // Clockwise turn applied before OCR; boxes are in the turned image's coordinates
+    if let Some(&first) = rotations.first() {
+        response["meta"]["rotation_deg"] = serde_json::json!(first);
+        if page_count > 1 {
+            response["meta"]["page_rotations"] = serde_json::json!(rotations);
+        }
+    }
+    // A partial result depends on timing, so a retry should get the chance to finish
+    if unrecognized.is_none() {
+        state.results.put(cache_key.clone(), response.clone());
+    }
+    if let Some(leader) = leader {
+        leader.finish(&response);
+    }
+
+    if let Some(key) = idempotency_key {
+        state.idempotency.store(key, cache_key, response.clone());
+    }
+    log_recognized(&response["meta"], "");
+    HttpResponse::Ok().json(response)
+}
+
+// recognize's form: the image (or the url to fetch it from) and every option, validated
+#[cfg(feature = "with-ocr")]
+struct RecognizeForm {
+    file: Option<Vec<u8>>,
+    url: Option<String>,
+    model_id: Option<String>,
+    params: PredictParams,
+    dpi: f32,
+    return_prob_map: bool,
+    group_by_color: bool,
+    color_clusters: usize,
+    dedupe_across_batch: bool,
+    repeat_threshold: f32,
+    multi_scale: bool,
+    scales: Vec<f32>,
+    normalize_text: bool,
+    with_raw: bool,
+    with_text: bool,
+    charset_whitelist: Option<Vec<char>>,
+    charset_mode: charset::Mode,
+    clip_boxes: bool,
+    auto_rotate: bool,
+    with_ids: bool,
+    detect_doc_lang: bool,
+    detect_language: bool,
+    with_chars: bool,
+    detect_only: bool,
+    merge_boxes: bool,
+    merge_gap: f32,
+    normalized_coords: bool,
+    classify_handwriting: bool,
+    echo_params: bool,
+    time_budget: Option<Duration>,
+    drop_score: f32,
+    text_direction: Option<direction::Mode>,
+    preprocess: preprocess::Preprocess,
+    // Raw option fields, part of the result cache key
+    fields: std::collections::BTreeMap<String, String>,
+}
+
+// What recognize found out about one region besides its box and text
+#[cfg(feature = "with-ocr")]
+struct LineInfo {
+    id: Option<String>,
+    style: Option<(handwriting::Style, f32)>,
+    direction: Option<direction::Direction>,
+    // Its page's size as OCR saw it, the divisor for coords=normalized
+    page_size: (u32, u32),
+}
+
+#[cfg(feature = "with-ocr")]
+async fn read_recognize(payload: &mut Multipart, saved: &settings::Settings) -> Result<RecognizeForm, ApiError> {
+    let mut form = RecognizeForm::new(saved);
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let name = field.content_disposition().get_name().unwrap_or("").to_string();
+        let data = read_field(&mut field, &name).await?;
+        if name == "file" {
+            form.file = Some(data);
+            continue;
+        }
+        let value = String::from_utf8_lossy(&data);
+        let value = value.trim();
+        if name != "url" {
+            form.fields.insert(name.clone(), value.to_string());
+        }
+        if predict_field(&mut form.params, &name, value)? {
+            continue;
+        }
+        match name.as_str() {
+            "dpi" => form.dpi = number_field(&name, value, 1.0, MAX_DPI)?,
+            "return_prob_map" => if let Ok(v) = value.parse::<bool>() { form.return_prob_map = v; },
+            "group_by_color" => if let Ok(v) = value.parse::<bool>() { form.group_by_color = v; },
+            "color_clusters" => form.color_clusters = match value.parse::<usize>() {
+                Ok(v) if (1..=color::MAX_CLUSTERS).contains(&v) => v,
+                _ => return Err(ApiError::InvalidField(format!("Invalid 'color_clusters': expected an integer between 1 and {}", color::MAX_CLUSTERS))),
+            },
+            "dedupe_across_batch" => if let Ok(v) = value.parse::<bool>() { form.dedupe_across_batch = v; },
+            "repeat_threshold" => form.repeat_threshold = number_field(&name, value, 0.0, 1.0)?,
+            "multi_scale" => if let Ok(v) = value.parse::<bool>() { form.multi_scale = v; },
+            "normalize_text" => if let Ok(v) = value.parse::<bool>() { form.normalize_text = v; },
+            "with_raw" => if let Ok(v) = value.parse::<bool>() { form.with_raw = v; },
+            "with_text" => if let Ok(v) = value.parse::<bool>() { form.with_text = v; },
+            "clip_boxes" => if let Ok(v) = value.parse::<bool>() { form.clip_boxes = v; },
+            "auto_rotate" => if let Ok(v) = value.parse::<bool>() { form.auto_rotate = v; },
+            "with_ids" => if let Ok(v) = value.parse::<bool>() { form.with_ids = v; },
+            "detect_doc_lang" => if let Ok(v) = value.parse::<bool>() { form.detect_doc_lang = v; },
+            "detect_language" => if let Ok(v) = value.parse::<bool>() { form.detect_language = v; },
+            "with_chars" => if let Ok(v) = value.parse::<bool>() { form.with_chars = v; },
+            "detect_only" => if let Ok(v) = value.parse::<bool>() { form.detect_only = v; },
+            "merge_boxes" => if let Ok(v) = value.parse::<bool>() { form.merge_boxes = v; },
+            "merge_gap" => form.merge_gap = number_field(&name, value, 0.0, linemerge::MAX_GAP)?,
+            "coords" => form.normalized_coords = coords_field(value)?,
+            "classify_handwriting" => if let Ok(v) = value.parse::<bool>() { form.classify_handwriting = v; },
+            "echo_params" => if let Ok(v) = value.parse::<bool>() { form.echo_params = v; },
+            "text_direction" if !value.is_empty() => form.text_direction = match direction::Mode::parse(value) {
+                Some(m) => Some(m),
+                None => return Err(ApiError::InvalidField(format!("Invalid 'text_direction': expected 'horizontal', 'vertical' or 'auto', got '{}'", value))),
+            },
+            "drop_score" if !value.is_empty() => form.drop_score = number_field(&name, value, 0.0, 1.0)?,
+            "time_budget_ms" if !value.is_empty() => form.time_budget = match value.parse::<u64>() {
+                Ok(v) if v > 0 => Some(Duration::from_millis(v)),
+                _ => return Err(ApiError::InvalidField(format!("Invalid 'time_budget_ms': expected a positive integer, got '{}'", value))),
+            },
+            "max_side" if !value.is_empty() => form.preprocess.max_side = match value.parse::<u32>() {
+                Ok(v) if v >= preprocess::MIN_SIDE => Some(v),
+                _ => return Err(ApiError::InvalidField(format!("Invalid 'max_side': expected an integer of at least {}, got '{}'", preprocess::MIN_SIDE, value))),
+            },
+            "grayscale" => if let Ok(v) = value.parse::<bool>() { form.preprocess.grayscale = v; },
+            "contrast" if !value.is_empty() => form.preprocess.contrast = Some(number_field(&name, value, 0.0, preprocess::MAX_CONTRAST)?),
+            // The recognizer decodes CTC greedily, which is beam width 1; wider beams aren't available
+            "beam_width" => match value.parse::<u32>() {
+                Ok(1) => {}
+                Ok(w) if w > 1 => return Err(ApiError::InvalidField(format!("beam_width {} is not supported: the recognizer only decodes greedily (beam_width=1)", w))),
+                _ => return Err(ApiError::InvalidField(format!("Invalid 'beam_width': expected a positive integer, got '{}'", value))),
+            },
+            "charset_whitelist" if !value.is_empty() => form.charset_whitelist = Some(value.chars().collect()),
+            "charset_mode" => form.charset_mode = match charset::Mode::parse(value) {
+                Some(m) => m,
+                None => return Err(ApiError::InvalidField(format!("Invalid 'charset_mode': expected 'drop' or 'map', got '{}'", value))),
+            },
+            "model_id" if !value.is_empty() => form.model_id = Some(value.to_string()),
+            "url" if !value.is_empty() => form.url = Some(value.to_string()),
+            "scales" => form.scales = multiscale::parse_scales(value).map_err(|e| ApiError::InvalidField(format!("Invalid 'scales': {}", e)))?,
+            _ => {}
+        }
+    }
+    Ok(form)
+}
+
+#[cfg(feature = "with-ocr")]
+impl RecognizeForm {
+    /// Every option at its default, with drop_score from the saved settings
+    fn new(saved: &settings::Settings) -> Self {
+        RecognizeForm {
+            file: None,
+            url: None,
+            model_id: None,
+            params: PredictParams::default(),
+            dpi: 300.0,
+            return_prob_map: false,
+            group_by_color: false,
+            color_clusters: 3,
+            dedupe_across_batch: false,
+            repeat_threshold: 0.5,
+            multi_scale: false,
+            scales: multiscale::DEFAULT_SCALES.to_vec(),
+            normalize_text: false,
+            with_raw: false,
+            with_text: false,
+            charset_whitelist: None,
+            charset_mode: charset::Mode::Drop,
+            clip_boxes: true,
+            auto_rotate: false,
+            with_ids: false,
+            detect_doc_lang: false,
+            detect_language: false,
+            with_chars: false,
+            detect_only: false,
+            merge_boxes: false,
+            merge_gap: linemerge::DEFAULT_GAP,
+            normalized_coords: false,
+            classify_handwriting: false,
+            echo_params: false,
+            time_budget: None,
+            drop_score: saved.drop_score.unwrap_or(0.0) as f32,
+            text_direction: None,
+            preprocess: preprocess::Preprocess::default(),
+            fields: std::collections::BTreeMap::new(),
+        }
+    }
+
+    /// One region as a result line, `[box, [text, score]]`, plus a third element holding whatever
+    /// the options add to it (raw_text, id, style, direction, lang, chars)
+    fn line(&self, region: &Region, info: LineInfo) -> serde_json::Value {
+        // normalize_text cleans up the text; with_raw then also keeps the original where it changed
+        let mut line = if self.normalize_text { region.to_legacy_normalized(self.with_raw) } else { region.to_legacy() };
+        let mut extra = serde_json::Map::new();
+        if let Some(id) = info.id {
+            extra.insert("id".into(), serde_json::json!(id));
+        }
+        if let Some((style, confidence)) = info.style {
+            extra.insert("style".into(), serde_json::json!(style.name()));
+            extra.insert("style_confidence".into(), serde_json::json!((confidence as f64 * 1000.0).round() / 1000.0));
+        }
+        if let Some(direction) = info.direction {
+            extra.insert("direction".into(), serde_json::json!(direction.name()));
+        }
+        if self.detect_language {
+            extra.insert("lang".into(), serde_json::json!(lang::detect_region(&region.text)));
+        }
+        // The recognizer averages its per-step probabilities into the line score and doesn't
+        // return them, so there are no character scores to give
+        if self.with_chars {
+            extra.insert("chars".into(), serde_json::Value::Null);
+        }
+        if !extra.is_empty() {
+            match line.get_mut(2).and_then(|e| e.as_object_mut()) {
+                Some(existing) => existing.extend(extra),
+                None => line.as_array_mut().unwrap().push(serde_json::Value::Object(extra)),
+            }
+        }
+        if self.detect_only {
+            line[1][0] = serde_json::Value::Null;
+        }
+        if self.normalized_coords {
+            normalize_box(&mut line, info.page_size);
+        }
+        line
+    }
+
+    fn warnings(&self) -> Vec<&'static str> {
+        let mut warnings = Vec::new();
+        if self.classify_handwriting {
+            warnings.push("no handwriting classifier is configured; style comes from a stroke-width heuristic");
+        }
+        if self.with_chars {
+            warnings.push("the recognizer only reports line-level scores; chars is null");
+        }
+        warnings
+    }
+
+    /// The settings the request runs with after defaults and validation, for echo_params
+    fn echo(&self, model_id: Option<String>) -> serde_json::Value {
+        serde_json::json!({
+            "model_id": model_id,
+            "det_db_thresh": self.params.det_db_thresh,
+            "cls_thresh": self.params.cls_thresh,
+            "use_cls": self.params.use_cls,
+            "det_limit_side_len": self.params.det_limit_side_len,
+            "dpi": self.dpi,
+            "auto_rotate": self.auto_rotate,
+            "max_side": self.preprocess.max_side,
+            "grayscale": self.preprocess.grayscale,
+            "contrast": self.preprocess.contrast,
+            "multi_scale": self.multi_scale,
+            "scales": if self.multi_scale { Some(&self.scales) } else { None },
+            "normalize_text": self.normalize_text,
+            "charset_whitelist": self.charset_whitelist.as_ref().map(|c| c.iter().collect::<String>()),
+            "charset_mode": if self.charset_mode == charset::Mode::Map { "map" } else { "drop" },
+            "clip_boxes": self.clip_boxes,
+            "drop_score": self.drop_score,
+            "text_direction": self.text_direction.map(|m| m.name()),
+            "detect_only": self.detect_only,
+            "merge_boxes": self.merge_boxes,
+            "merge_gap": if self.merge_boxes { Some(self.merge_gap) } else { None },
+            "coords": if self.normalized_coords { "normalized" } else { "pixel" },
+            "time_budget_ms": self.time_budget.map(|t| t.as_millis()),
+        })
+    }
+}
+
+// The PredictParams fields recognize and batch share; false when `name` isn't one of them
+#[cfg(feature = "with-ocr")]
+fn predict_field(params: &mut PredictParams, name: &str, value: &str) -> Result<bool, ApiError> {
+    match name {
+        "det_db_thresh" => params.det_db_thresh = number_field(name, value, 0.0, 1.0)?,
+        "cls_thresh" => params.cls_thresh = number_field(name, value, 0.0, 1.0)?,
+        "use_cls" if !value.is_empty() => params.use_cls = match value.parse::<bool>() {
+            Ok(v) => v,
+            Err(_) => return Err(ApiError::InvalidField(format!("Invalid 'use_cls': expected true or false, got '{}'", value))),
+        },
+        "det_limit_side_len" if !value.is_empty() => params.det_limit_side_len = match value.parse::<u32>() {
+            Ok(v) if (model::MIN_DET_LIMIT_SIDE_LEN..=model::MAX_DET_LIMIT_SIDE_LEN).contains(&v) => v,
+            _ => return Err(ApiError::InvalidField(format!("Invalid 'det_limit_side_len': expected an integer in {}..={}, got '{}'", model::MIN_DET_LIMIT_SIDE_LEN, model::MAX_DET_LIMIT_SIDE_LEN, value))),
+        },
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+// recognize's request log line, from its response `meta`
+#[cfg(feature = "with-ocr")]
+fn log_recognized(meta: &serde_json::Value, note: &str) {
+    let field = |key: &str| meta[key].as_u64().unwrap_or(0);
+    let size = (field("width") as u32, field("height") as u32);
+    log_ocr("POST /api/ocr/", size, field("pages") as usize, field("regions") as usize, Duration::from_millis(field("total_ms")), note);
+}
+
+// One info line per OCR request: endpoint, first page size, pages, regions and duration, so
+// problems in the field can be traced from the log file (OCR_LOG_DIR)
+#[cfg(feature = "with-ocr")]
+fn log_ocr(endpoint: &str, (width, height): (u32, u32), pages: usize, regions: usize, elapsed: Duration, note: &str) {
+    let note = if note.is_empty() { String::new() } else { format!(" ({})", note) };
+    log::info!("{} {}x{} pages={} regions={} {}ms{}", endpoint, width, height, pages, regions, elapsed.as_millis(), note);
+}
+
+// Download the image for a `url` request off the async runtime
+#[cfg(feature = "with-ocr")]
+async fn fetch_url(url: String) -> Result<Vec<u8>, ApiError> {
+    let res = web::block(move || fetch::fetch(&url, &FetchConfig::from_env())).await;
+    match res {
+        Ok(Ok(bytes)) => Ok(bytes),
+        Ok(Err(e)) => Err(match e {
+            FetchError::InvalidUrl(_) => ApiError::InvalidField(e.to_string()),
+            FetchError::Timeout => ApiError::FetchTimeout(e.to_string()),
+            FetchError::TooLarge { .. } => ApiError::PayloadTooLarge(e.to_string()),
+            FetchError::NotAnImage(_) => ApiError::UnsupportedFormat(e.to_string()),
+            FetchError::Failed(_) => ApiError::FetchFailed(e.to_string()),
+        }),
+        Err(e) => Err(ApiError::Internal(format!("Task error: {}", e))),
+    }
+}
+
+// Rasterize a PDF upload off the async runtime
+#[cfg(feature = "with-ocr")]
+async fn render_pdf(bytes: Vec<u8>, dpi: f32) -> Result<Vec<RgbImage>, ApiError> {
+    match web::block(move || pdf::render_pages(&bytes, dpi)).await {
+        Ok(Ok(pages)) => Ok(pages),
+        Ok(Err(PdfError::Invalid(msg))) => Err(ApiError::DecodeFailed(msg)),
+        Ok(Err(PdfError::Backend(msg))) => Err(ApiError::Internal(msg)),
+        Err(e) => Err(ApiError::Internal(format!("Task error: {}", e))),
+    }
+}
+
+// Detector probability map for one page as a base64 PNG
+#[cfg(feature = "with-ocr")]
+async fn prob_map_png(ocr: &Ocr<'_>, img: RgbImage, limit_side_len: u32) -> Result<String, ApiError> {
+    let map = ocr.run("OCR", move |model| model.det_prob_map(&img, limit_side_len)).await?;
+
+    let mut buf = Vec::new();
+    if let Err(e) = PngEncoder::new(&mut buf).write_image(map.as_raw(), map.width(), map.height(), ColorType::L8.into()) {
+        return Err(ApiError::Internal(format!("Failed to encode PNG: {}", e)));
+    }
+    Ok(base64::engine::general_purpose::STANDARD.encode(buf))
+}
+
+// Loaded model for a request: `model_id` if given (404 when unknown), else the default one.
+// A model unloaded under memory pressure is reloaded here first, unless it was unloaded for good
+// while this request waited its turn, which is the same 404 or "model not loaded" as before.
+#[cfg(feature = "with-ocr")]
+async fn select_model(state: &AppState, model_id: Option<&str>) -> Result<Arc<OcrPool>, ApiError> {
+    let resolved = state.ocr.lock().unwrap().resolve(model_id);
+    let id = match resolved {
+        Resolved::Loaded(pool) => return Ok(pool),
+        Resolved::Parked(id, _) => id,
+        Resolved::Unknown => return Err(ApiError::UnknownModel(model_id.unwrap_or_default().to_string())),
+        Resolved::Empty => return Err(ApiError::ModelNotLoaded),
+    };
+
+    let _changing = state.model_changes.lock().await;
+    // Another request may have reloaded it while this one waited, or an unload dropped it
+    let resolved = state.ocr.lock().unwrap().resolve(Some(&id));
+    let source = match resolved {
+        Resolved::Loaded(pool) => return Ok(pool),
+        Resolved::Parked(_, source) => source,
+        Resolved::Unknown | Resolved::Empty if model_id.is_some() => return Err(ApiError::UnknownModel(id)),
+        Resolved::Unknown | Resolved::Empty => return Err(ApiError::ModelNotLoaded),
+    };
+    let pool = match web::block(move || load_pool(&source)).await {
+        Ok(Ok((pool, _))) => Arc::new(pool),
+        Ok(Err(e)) => return Err(ApiError::ModelUnavailable(format!("model unavailable: failed to reload '{}': {}", id, e))),
+        Err(e) => return Err(ApiError::Internal(format!("Task error: {}", e))),
+    };
+    // Back in its old place, so the default stays whichever model was loaded last
+    state.ocr.lock().unwrap().restore(&id, pool.clone());
+    state.results.clear();
+    state.memory.record("reloaded", &[id]);
+    Ok(pool)
+}
+
+// Take a job slot for an OCR request, or a 503 (with Retry-After) when the server is saturated
+#[cfg(feature = "with-ocr")]
+fn admit(state: &AppState) -> Result<JobSlot, ApiError> {
+    enter(&state.jobs)
+}
+
+fn enter(gate: &JobGate) -> Result<JobSlot, ApiError> {
+    gate.try_enter().ok_or(ApiError::Busy)
+}
+
+// Upload cap for an image `file` field, from OCR_MAX_IMAGE_BYTES (default 20 MiB)
+fn max_image_bytes() -> usize {
+    static LIMIT: std::sync::OnceLock<usize> = std::sync::OnceLock::new();
+    *LIMIT.get_or_init(|| env::var("OCR_MAX_IMAGE_BYTES").ok().and_then(|v| v.trim().parse::<usize>().ok()).filter(|&n| n > 0).unwrap_or(20 * 1024 * 1024))
+}
+
+// Whole multipart field, refused with 413 as soon as it passes its cap (max_image_bytes for
+// `file`, JSON_BODY_LIMIT for the rest) instead of buffering an unbounded upload
+async fn read_field(field: &mut actix_multipart::Field, name: &str) -> Result<Vec<u8>, ApiError> {
+    let limit = if name == "file" { max_image_bytes() } else { JSON_BODY_LIMIT };
+    let mut data = Vec::new();
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk.map_err(|e| ApiError::InvalidField(format!("Failed to read upload: {}", e)))?;
+        if data.len() + chunk.len() > limit {
+            let error = if name == "file" { format!("image exceeds {} bytes", limit) } else { format!("field '{}' exceeds {} bytes", name, limit) };
+            return Err(ApiError::PayloadTooLarge(error));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+// Text form fields of a small multipart body (load/unload options); empty when there is no body
+#[cfg(feature = "with-ocr")]
+async fn read_text_fields(mut payload: Multipart) -> std::collections::HashMap<String, String> {
+    let mut fields = std::collections::HashMap::new();
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let name = field.content_disposition().get_name().unwrap_or("").to_string();
+        let mut data = Vec::new();
+        while let Some(Ok(chunk)) = field.next().await { data.extend_from_slice(&chunk); }
+        fields.insert(name, String::from_utf8_lossy(&data).trim().to_string());
+    }
+    fields
+}
+
+// Run the loaded pipeline on one image, mapping failures to the response the handler should return
+#[cfg(feature = "with-ocr")]
+async fn run_ocr(ocr: &Ocr<'_>, img: RgbImage, params: PredictParams) -> Result<Vec<Region>, ApiError> {
+    // Predict (and building a pipeline for new params) is CPU-heavy; detection and recognition
+    // both happen inside this one call
+    let mut vec_res = ocr.run("OCR", move |model| model.pipeline(&params)?.predict(&[img])).await?;
+    Ok(vec_res.remove(0).text_regions.iter().map(Region::from).collect())
+}
+
+// Re-read the regions whose chosen direction isn't the one the pipeline read them in
+#[cfg(feature = "with-ocr")]
+async fn reread_directed(ocr: &Ocr<'_>, page: &RgbImage, regions: &mut [Region], chosen: &[direction::Direction]) -> Result<(), ApiError> {
+    let (indices, turned): (Vec<usize>, Vec<RgbImage>) = regions
+        .iter()
+        .zip(chosen.iter())
+        .enumerate()
+        .filter(|(_, (r, d))| direction::pipeline_choice(&r.points) != **d)
+        .filter_map(|(i, (r, d))| direction::crop(page, &r.points, *d).map(|crop| (i, crop)))
+        .unzip();
+    if turned.is_empty() {
+        return Ok(());
+    }
+    let read = ocr.run("OCR", move |model| model.recognize_crops(turned)).await?;
+    for (i, (text, score)) in indices.into_iter().zip(read) {
+        regions[i].text = text;
+        regions[i].score = score;
+    }
+    Ok(())
+}
+
+// Boxes only, without reading them: recognize's detect_only and pages past time_budget_ms, and
+// layout_preview. Each region has empty text and the detector's box score.
+#[cfg(feature = "with-ocr")]
+async fn run_detection(ocr: &Ocr<'_>, img: RgbImage, params: PredictParams) -> Result<Vec<Region>, ApiError> {
+    let boxes = ocr.run("Detection", move |model| model.detect(&img, &params)).await?;
+    Ok(boxes.into_iter().map(|(points, score)| Region { points, text: String::new(), score }).collect())
+}
+
+// recognize's merge_boxes: detection, then split lines joined, then every box read on its own
+#[cfg(feature = "with-ocr")]
+async fn run_ocr_merged(ocr: &Ocr<'_>, img: RgbImage, params: PredictParams, max_gap: f32) -> Result<Vec<Region>, ApiError> {
+    let detected = run_detection(ocr, img.clone(), params).await?;
+    let mut regions = linemerge::merge_adjacent(detected, max_gap);
+    let polygons: Vec<Vec<[f32; 2]>> = regions.iter().map(|r| r.points.clone()).collect();
+    let read = ocr.run("OCR", move |model| model.recognize_polygons(&img, &polygons)).await?;
+    for (region, (text, score)) in regions.iter_mut().zip(read) {
+        region.text = text;
+        region.score = score;
+    }
+    Ok(regions)
+}
+
+// Clockwise turn (0, 90, 180 or 270) that makes `page` upright for auto_rotate: the model's
+// document orientation classifier when it has one, else the orientation heuristic, which reads
+// the page and its candidate turns
+#[cfg(feature = "with-ocr")]
+async fn page_rotation(ocr: &Ocr<'_>, page: &RgbImage, params: PredictParams) -> Result<u32, ApiError> {
+    // Known from the load, so no worker is checked out just to ask
+    if ocr.pool().info().orientation_classifier {
+        let img = page.clone();
+        return Ok(ocr.run("Orientation", move |model| model.page_orientation(&img)).await?.unwrap_or(0));
+    }
+
+    let upright = run_ocr(ocr, page.clone(), params).await?;
+    let candidates: [u32; 2] = if orientation::looks_sideways(&upright) { [90, 270] } else { [0, 180] };
+    let mut best = (0, f32::MIN);
+    for rotation in candidates {
+        let score = if rotation == 0 {
+            orientation::reading_score(&upright)
+        } else {
+            orientation::reading_score(&run_ocr(ocr, orientation::rotate(page.clone(), rotation), params).await?)
+        };
+        if score > best.1 {
+            best = (rotation, score);
+        }
+    }
+    Ok(best.0)
+}
+
+// Run the pipeline once per scale and merge the boxes, in original image coordinates
+#[cfg(feature = "with-ocr")]
+async fn run_ocr_multi_scale(ocr: &Ocr<'_>, img: RgbImage, params: PredictParams, scales: &[f32]) -> Result<Vec<Region>, ApiError> {
+    let mut regions = Vec::new();
+    for &scale in scales.iter() {
+        let scaled = if scale == 1.0 { img.clone() } else { multiscale::rescale(&img, scale) };
+        let found = run_ocr(ocr, scaled, params).await?;
+        regions.extend(found.into_iter().map(|r| r.scaled(1.0 / scale)));
+    }
+    Ok(multiscale::merge(regions))
+}
+
+#[cfg(not(feature = "with-ocr"))]
+#[post("/api/ocr/")]
+async fn recognize(_req: HttpRequest, _payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    state.metrics.requests.inc();
+    state.metrics.failures.inc();
+    ApiError::FeatureDisabled.error_response()
+}
+
+/// Several images in one request: repeated `file` parts plus the shared fields det_db_thresh,
+/// cls_thresh, use_cls, det_limit_side_len, dpi (for PDFs) and model_id. Returns
+/// `{"results": [{"filename", "result"}, ...]}` in upload order, each `result` shaped as in
+/// recognize; a file that can't be decoded or read gets `"error": {"code", "message"}` in place of
+/// `result` without failing the others. Files run side by side, their model calls sharing the
+/// OCR_MAX_CONCURRENCY limit with every other request. More than OCR_MAX_BATCH files (default 50)
+/// is a 413.
+///
+/// A file byte-identical to an earlier one isn't recognized again: its entry is
+/// `{"filename", "duplicate_of": index}` pointing at the first copy. With `dedupe=perceptual`,
+/// images that merely look the same (difference hashes within `dedupe_distance` bits of 64,
+/// default 5) are skipped the same way; `dedupe=exact` (the default) matches bytes only.
+#[cfg(feature = "with-ocr")]
+#[post("/api/ocr/batch")]
+async fn batch(mut payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    let _slot = match admit(&state) { Ok(slot) => slot, Err(e) => return e.error_response() };
+    let request = match read_batch(&mut payload).await { Ok(r) => r, Err(e) => return e.error_response() };
+    let pool = match select_model(&state, request.model_id.as_deref()).await { Ok(p) => p, Err(e) => return e.error_response() };
+
+    let (params, dpi) = (request.params, request.dpi);
+    let (files, duplicates) = match batch_duplicates(request.files, request.dedupe_distance).await { Ok(d) => d, Err(e) => return e.error_response() };
+    let items = files.into_iter().zip(duplicates).map(|((filename, bytes), duplicate_of)| {
+        let (state, pool) = (state.clone(), pool.clone());
+        async move { batch_entry(&state, &pool, filename, bytes, duplicate_of, params, dpi).await }
+    });
+    let results = futures::future::join_all(items).instrument(info_span!("batch")).await;
+    HttpResponse::Ok().json(serde_json::json!({"results": results}))
+}
+
+/// Same request as batch, answered as Server-Sent Events (`text/event-stream`): a `progress`
+/// event `{"index", "filename", "done", "total"}` as each file finishes (in finishing order;
+/// `index` is its upload position), then one `complete` event with the `{"results": [...]}` batch
+/// would return. Each event is sent as soon as it's ready; if the client disconnects, files not
+/// yet finished are dropped. Errors found before any file runs are ordinary JSON error responses.
+#[cfg(feature = "with-ocr")]
+#[post("/api/ocr/batch_stream")]
+async fn batch_stream(mut payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    use futures::stream::FuturesUnordered;
+
+    let slot = match admit(&state) { Ok(slot) => slot, Err(e) => return e.error_response() };
+    let request = match read_batch(&mut payload).await { Ok(r) => r, Err(e) => return e.error_response() };
+    let pool = match select_model(&state, request.model_id.as_deref()).await { Ok(p) => p, Err(e) => return e.error_response() };
+
+    let (params, dpi) = (request.params, request.dpi);
+    let (files, duplicates) = match batch_duplicates(request.files, request.dedupe_distance).await { Ok(d) => d, Err(e) => return e.error_response() };
+    let total = files.len();
+    let items: FuturesUnordered<_> = files
+        .into_iter()
+        .zip(duplicates)
+        .enumerate()
+        .map(|(index, ((filename, bytes), duplicate_of))| {
+            let (state, pool) = (state.clone(), pool.clone());
+            async move { (index, batch_entry(&state, &pool, filename, bytes, duplicate_of, params, dpi).await) }
+        })
+        .collect();
+
+    // The job slot rides along with the stream, so it's held until the last event or a disconnect
+    let events = futures::stream::unfold(Some((items, vec![serde_json::Value::Null; total], slot)), move |progress| async move {
+        let (mut items, mut results, slot) = progress?;
+        let event = match items.next().await {
+            Some((index, entry)) => {
+                let done = total - items.len();
+                let event = sse_event("progress", &serde_json::json!({"index": index, "filename": entry["filename"], "done": done, "total": total}));
+                results[index] = entry;
+                return Some((Ok::<_, std::convert::Infallible>(event), Some((items, results, slot))));
+            }
+            None => sse_event("complete", &serde_json::json!({"results": results})),
+        };
+        Some((Ok(event), None))
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events)
+}
+
+// One Server-Sent Events message
+#[cfg(feature = "with-ocr")]
+fn sse_event(name: &str, data: &serde_json::Value) -> web::Bytes {
+    web::Bytes::from(format!("event: {}\ndata: {}\n\n", name, data))
+}
+
+// Files and shared fields of a batch upload
+#[cfg(feature = "with-ocr")]
+struct BatchRequest {
+    files: Vec<(String, Vec<u8>)>,
+    params: PredictParams,
+    dpi: f32,
+    model_id: Option<String>,
+    // Hamming distance for perceptual dedupe; None matches identical bytes only
+    dedupe_distance: Option<u32>,
+}
+
+#[cfg(feature = "with-ocr")]
+async fn read_batch(payload: &mut Multipart) -> Result<BatchRequest, ApiError> {
+    let max_files = max_batch();
+    let mut request = BatchRequest { files: Vec::new(), params: PredictParams::default(), dpi: 300.0, model_id: None, dedupe_distance: None };
+    let (mut perceptual, mut distance) = (false, imagehash::DEFAULT_DISTANCE);
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let name = field.content_disposition().get_name().unwrap_or("").to_string();
+        if name == "file" && request.files.len() == max_files {
+            return Err(ApiError::PayloadTooLarge(format!("batch exceeds {} files (OCR_MAX_BATCH)", max_files)));
+        }
+        let filename = field.content_disposition().get_filename().map(str::to_string);
+        let data = read_field(&mut field, &name).await?;
+        if name == "file" {
+            let filename = filename.unwrap_or_else(|| format!("file{}", request.files.len()));
+            request.files.push((filename, data));
+            continue;
+        }
+        let value = String::from_utf8_lossy(&data);
+        let value = value.trim();
+        if predict_field(&mut request.params, &name, value)? {
+            continue;
+        }
+        match name.as_str() {
+            "dpi" => request.dpi = number_field(&name, value, 1.0, MAX_DPI)?,
+            "model_id" if !value.is_empty() => request.model_id = Some(value.to_string()),
+            "dedupe" => perceptual = match value {
+                "" | "exact" => false,
+                "perceptual" => true,
+                _ => return Err(ApiError::InvalidField(format!("Invalid 'dedupe': expected 'exact' or 'perceptual', got '{}'", value))),
+            },
+            "dedupe_distance" if !value.is_empty() => distance = match value.parse::<u32>() {
+                Ok(v) if v <= imagehash::MAX_DISTANCE => v,
+                _ => return Err(ApiError::InvalidField(format!("Invalid 'dedupe_distance': expected an integer in 0..={}, got '{}'", imagehash::MAX_DISTANCE, value))),
+            },
+            _ => {}
+        }
+    }
+    if request.files.is_empty() {
+        return Err(ApiError::MissingField("file"));
+    }
+    request.dedupe_distance = perceptual.then_some(distance);
+    Ok(request)
+}
+
+// Which batch files repeat an earlier one. Perceptual matching decodes every image, so it runs
+// off the async workers; the files are handed back alongside.
+#[cfg(feature = "with-ocr")]
+async fn batch_duplicates(files: Vec<(String, Vec<u8>)>, distance: Option<u32>) -> Result<(Vec<(String, Vec<u8>)>, Vec<Option<usize>>), ApiError> {
+    web::block(move || {
+        let duplicates = imagehash::find_duplicates(files.iter().map(|(_, bytes)| bytes.as_slice()), distance);
+        (files, duplicates)
+    })
+    .await
+    .map_err(|e| ApiError::Internal(format!("Duplicate check failed: {}", e)))
+}
+
+// A batch file's entry in `results`: its lines, the error that stopped it, or the earlier file
+// it duplicates
+#[cfg(feature = "with-ocr")]
+async fn batch_entry(state: &AppState, pool: &Arc<OcrPool>, filename: String, bytes: Vec<u8>, duplicate_of: Option<usize>, params: PredictParams, dpi: f32) -> serde_json::Value {
+    if let Some(index) = duplicate_of {
+        return serde_json::json!({"filename": filename, "duplicate_of": index});
+    }
+    match batch_item(state, pool, bytes, params, dpi).await {
+        Ok(result) => serde_json::json!({"filename": filename, "result": result}),
+        Err(e) => serde_json::json!({"filename": filename, "error": {"code": e.code(), "message": e.to_string()}}),
+    }
+}
+
+// One batch file: decoded (or rasterized) and run page by page
+#[cfg(feature = "with-ocr")]
+async fn batch_item(state: &AppState, pool: &Arc<OcrPool>, bytes: Vec<u8>, params: PredictParams, dpi: f32) -> Result<Vec<Vec<serde_json::Value>>, ApiError> {
+    let started = Instant::now();
+    let pages = if pdf::is_pdf(&bytes) { render_pdf(bytes, dpi).await? } else { decode::frames(&bytes)? };
+    let ocr = Ocr::new(pool, &state.inference);
+    let (page_count, size) = (pages.len(), pages.first().map(|p| p.dimensions()).unwrap_or_default());
+    let mut result: Vec<Vec<serde_json::Value>> = Vec::with_capacity(pages.len());
+    for page in pages {
+        let (width, height) = page.dimensions();
+        let mut regions = run_ocr(&ocr, page, params).await?;
+        regions.iter_mut().for_each(|r| r.clip_to(width, height));
+        result.push(regions.iter().map(Region::to_legacy).collect());
+    }
+    log_ocr("POST /api/ocr/batch (file)", size, page_count, result.iter().map(Vec::len).sum(), started.elapsed(), "");
+    Ok(result)
+}
+
+// Most files one batch request may carry, from OCR_MAX_BATCH (default 50)
 #[cfg(feature = "with-ocr")]
-type OcrInner = Arc<OAROCR>;
+fn max_batch() -> usize {
+    env::var("OCR_MAX_BATCH").ok().and_then(|v| v.trim().parse::<usize>().ok()).unwrap_or(50).max(1)
+}
 
 #[cfg(not(feature = "with-ocr"))]
-type OcrInner = ();
+#[post("/api/ocr/batch")]
+async fn batch(_payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
+}
 
-#[derive(Clone)]
-struct AppState {
-    // Store OCR pipeline as Arc so it can be cloned cheaply across handlers
-    ocr: Arc<Mutex<Option<OcrInner>>>,
+#[cfg(not(feature = "with-ocr"))]
+#[post("/api/ocr/batch_stream")]
+async fn batch_stream(_payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
 }
 
-#[derive(Serialize)]
-struct Message { message: String }
+#[cfg(feature = "with-ocr")]
+#[derive(Deserialize)]
+struct Base64Request {
+    image_base64: String,
+    det_db_thresh: Option<f32>,
+    cls_thresh: Option<f32>,
+    use_cls: Option<bool>,
+    det_limit_side_len: Option<u32>,
+    model_id: Option<String>,
+}
 
-#[derive(Serialize)]
-struct ModelStatus { loaded: bool }
+/// JSON alternative to recognize: `{"image_base64": "...", "det_db_thresh", "cls_thresh",
+/// "use_cls", "det_limit_side_len", "model_id"}`, all but the image optional and each checked as
+/// recognize checks its field. A `data:...;base64,` prefix is accepted.
+/// Returns the same `{"result": [...]}` shape; invalid base64 and undecodable image bytes are
+/// distinct 400s.
+#[cfg(feature = "with-ocr")]
+#[post("/api/ocr/base64")]
+async fn recognize_base64(body: web::Json<Base64Request>, state: web::Data<AppState>) -> impl Responder {
+    let _slot = match admit(&state) { Ok(slot) => slot, Err(e) => return e.error_response() };
+    let body = body.into_inner();
+    let mut params = PredictParams::default();
+    let fields = [
+        ("det_db_thresh", body.det_db_thresh.map(|v| v.to_string())),
+        ("cls_thresh", body.cls_thresh.map(|v| v.to_string())),
+        ("use_cls", body.use_cls.map(|v| v.to_string())),
+        ("det_limit_side_len", body.det_limit_side_len.map(|v| v.to_string())),
+    ];
+    // Checked exactly as recognize's form fields are
+    for (name, value) in fields {
+        if let Some(value) = value
+            && let Err(e) = predict_field(&mut params, name, &value)
+        {
+            return e.error_response();
+        }
+    }
 
-#[get("/api/health/")]
-async fn health() -> impl Responder {
-    HttpResponse::Ok().json(serde_json::json!({"status": "ok"}))
+    // Drop a data URL header ("data:image/png;base64,") and any line breaks in the payload
+    let encoded = match body.image_base64.trim().strip_prefix("data:") {
+        Some(rest) => rest.split_once(',').map(|(_, data)| data).unwrap_or(""),
+        None => body.image_base64.trim(),
+    };
+    let encoded: String = encoded.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let bytes = match base64::engine::general_purpose::STANDARD.decode(encoded) {
+        Ok(b) => b,
+        Err(e) => return ApiError::InvalidField(format!("Invalid base64: {}", e)).error_response(),
+    };
+
+    let started = Instant::now();
+    let pool = match select_model(&state, body.model_id.as_deref()).await { Ok(p) => p, Err(e) => return e.error_response() };
+    let pages = if pdf::is_pdf(&bytes) {
+        match render_pdf(bytes, 300.0).await { Ok(p) => p, Err(e) => return e.error_response() }
+    } else {
+        match decode::frames(&bytes) { Ok(frames) => frames, Err(e) => return e.error_response() }
+    };
+
+    let (page_count, size) = (pages.len(), pages.first().map(|p| p.dimensions()).unwrap_or_default());
+    let mut result: Vec<Vec<serde_json::Value>> = Vec::with_capacity(pages.len());
+    let ocr = Ocr::new(&pool, &state.inference);
+    for page in pages {
+        let regions = match run_ocr(&ocr, page, params).await { Ok(r) => r, Err(e) => return e.error_response() };
+        result.push(regions.iter().map(|r| r.to_legacy()).collect());
+    }
+    log_ocr("POST /api/ocr/base64", size, page_count, result.iter().map(Vec::len).sum(), started.elapsed(), "");
+    HttpResponse::Ok().json(serde_json::json!({"result": result}))
+}
+
+#[cfg(not(feature = "with-ocr"))]
+#[post("/api/ocr/base64")]
+async fn recognize_base64(_body: web::Json<serde_json::Value>, _state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
 }
 
-// Load model: uses environment variable OCR_MODEL_DIR or a default models path
+/// Runs OCR on the multipart `file` and returns an HTML review fragment
+/// (`text/html`) with each word's background colored by confidence.
 #[cfg(feature = "with-ocr")]
-#[post("/api/ocr/load")]
-async fn load_model(state: web::Data<AppState>) -> impl Responder {
-    let model_dir = env::var("OCR_MODEL_DIR").unwrap_or_else(|_| {
-        // default relative path from repo: backend/rust-onnx/models/ppocrv5
-        // this can be overridden by OCR_MODEL_DIR
-        "../models/ppocrv5".to_string()
-    });
+#[post("/api/ocr/html")]
+async fn html(mut payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    let _slot = match admit(&state) { Ok(slot) => slot, Err(e) => return e.error_response() };
+    let mut file_bytes: Option<Vec<u8>> = None;
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let name = field.content_disposition().get_name().unwrap_or("").to_string();
+        if name == "file" {
+            file_bytes = Some(match read_field(&mut field, &name).await { Ok(d) => d, Err(e) => return e.error_response() });
+        }
+    }
+
+    let bytes = match file_bytes { Some(b) => b, None => return ApiError::MissingField("file").error_response(), };
+    let dyn_img = match decode::rgb_image(&bytes) { Ok(img) => img, Err(e) => return e.error_response() };
+    let (width, height) = dyn_img.dimensions();
+    let pool = match select_model(&state, None).await { Ok(p) => p, Err(e) => return e.error_response() };
+    let regions = match run_ocr(&Ocr::new(&pool, &state.inference), dyn_img, PredictParams::default()).await { Ok(r) => r, Err(e) => return e.error_response() };
 
-    let det = format!("{}/pp-ocrv5_mobile_det.onnx", model_dir);
-    let rec = format!("{}/pp-ocrv5_mobile_rec.onnx", model_dir);
-    let dict = format!("{}/ppocrv5_dict.txt", model_dir);
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(export::confidence_html(&regions, width, height))
+}
 
-    match OAROCRBuilder::new(&det, &rec, &dict).build() {
-        Ok(ocr) => {
-            let arc_ocr = Arc::new(ocr);
-            let mut guard = state.ocr.lock().unwrap();
-            *guard = Some(arc_ocr);
-            HttpResponse::Ok().json(Message{ message: "OCR model loaded successfully".into() })
+/// Runs OCR on the multipart `file` (optional det_db_thresh, cls_thresh, use_cls,
+/// det_limit_side_len, model_id as in recognize) and returns `{"regions": [{"points", "text", "score", "image"}]}` with each line cut
+/// out as a base64 PNG (null when the box has no area). Four-point boxes are perspective-warped to
+/// an upright rectangle, so slanted lines come out straight.
+#[cfg(feature = "with-ocr")]
+#[post("/api/ocr/crops")]
+async fn crops(mut payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    let _slot = match admit(&state) { Ok(slot) => slot, Err(e) => return e.error_response() };
+    let mut file_bytes: Option<Vec<u8>> = None;
+    let mut params = PredictParams::default();
+    let mut model_id: Option<String> = None;
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let name = field.content_disposition().get_name().unwrap_or("").to_string();
+        let data = match read_field(&mut field, &name).await { Ok(d) => d, Err(e) => return e.error_response() };
+        if name == "file" {
+            file_bytes = Some(data);
+            continue;
         }
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to build model: {}", e)})),
+        let value = String::from_utf8_lossy(&data);
+        let value = value.trim();
+        match predict_field(&mut params, &name, value) {
+            Ok(false) if name == "model_id" && !value.is_empty() => model_id = Some(value.to_string()),
+            Ok(_) => {}
+            Err(e) => return e.error_response(),
+        }
+    }
+
+    let bytes = match file_bytes { Some(b) => b, None => return ApiError::MissingField("file").error_response(), };
+    let img = match decode::rgb_image(&bytes) { Ok(img) => img, Err(e) => return e.error_response() };
+    let pool = match select_model(&state, model_id.as_deref()).await { Ok(p) => p, Err(e) => return e.error_response() };
+    let regions = match run_ocr(&Ocr::new(&pool, &state.inference), img.clone(), params).await { Ok(r) => r, Err(e) => return e.error_response() };
+
+    // Cropping and PNG encoding are CPU work proportional to the line count
+    let encoded = web::block(move || {
+        regions
+            .iter()
+            .map(|r| {
+                let mut png = Vec::new();
+                let image = model::crop_polygon(&img, &r.points).and_then(|crop| {
+                    PngEncoder::new(&mut png).write_image(crop.as_raw(), crop.width(), crop.height(), ColorType::Rgb8.into()).ok()?;
+                    Some(base64::engine::general_purpose::STANDARD.encode(&png))
+                });
+                serde_json::json!({"points": r.points, "text": r.text, "score": r.score, "image": image})
+            })
+            .collect::<Vec<_>>()
+    })
+    .await;
+    match encoded {
+        Ok(regions) => HttpResponse::Ok().json(serde_json::json!({"regions": regions})),
+        Err(e) => ApiError::Internal(format!("Task error: {}", e)).error_response(),
     }
 }
 
 #[cfg(not(feature = "with-ocr"))]
-#[post("/api/ocr/load")]
-async fn load_model(_state: web::Data<AppState>) -> impl Responder {
-    HttpResponse::NotImplemented().json(serde_json::json!({"error":"ocr-service built without feature 'with-ocr'; enable it to use native OCR"}))
+#[post("/api/ocr/crops")]
+async fn crops(_payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
 }
 
+#[cfg(not(feature = "with-ocr"))]
+#[post("/api/ocr/html")]
+async fn html(_payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
+}
+
+/// Straightens the multipart `file`: the dominant text angle is estimated from the detected
+/// lines and the image is rotated so they run horizontally. Returns the PNG, with the applied
+/// rotation in `X-Deskew-Angle` (degrees, counter-clockwise positive; 0 when no text was found).
 #[cfg(feature = "with-ocr")]
-#[post("/api/ocr/unload")]
-async fn unload_model(state: web::Data<AppState>) -> impl Responder {
-    let mut guard = state.ocr.lock().unwrap();
-    *guard = None;
-    HttpResponse::Ok().json(Message{ message: "OCR model unloaded".into() })
+#[post("/api/ocr/deskew")]
+async fn deskew_image(mut payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    let _slot = match admit(&state) { Ok(slot) => slot, Err(e) => return e.error_response() };
+    let mut file_bytes: Option<Vec<u8>> = None;
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let name = field.content_disposition().get_name().unwrap_or("").to_string();
+        if name == "file" {
+            file_bytes = Some(match read_field(&mut field, &name).await { Ok(d) => d, Err(e) => return e.error_response() });
+        }
+    }
+
+    let bytes = match file_bytes { Some(b) => b, None => return ApiError::MissingField("file").error_response(), };
+    let dyn_img = match decode::rgb_image(&bytes) { Ok(img) => img, Err(e) => return e.error_response() };
+    let pool = match select_model(&state, None).await { Ok(p) => p, Err(e) => return e.error_response() };
+    let regions = match run_ocr(&Ocr::new(&pool, &state.inference), dyn_img.clone(), PredictParams::default()).await { Ok(r) => r, Err(e) => return e.error_response() };
+
+    let skew = deskew::estimate_angle(regions.iter().map(|r| r.points.as_slice())).unwrap_or(0.0);
+    let straight = match web::block(move || deskew::straighten(&dyn_img, skew)).await {
+        Ok(img) => img,
+        Err(e) => return ApiError::Internal(format!("Task error: {}", e)).error_response(),
+    };
+
+    let mut buf = Vec::new();
+    if let Err(e) = PngEncoder::new(&mut buf).write_image(straight.as_raw(), straight.width(), straight.height(), ColorType::Rgb8.into()) {
+        return ApiError::Internal(format!("Failed to encode PNG: {}", e)).error_response();
+    }
+    HttpResponse::Ok()
+        .content_type("image/png")
+        .insert_header(("X-Deskew-Angle", format!("{:.2}", skew)))
+        .body(buf)
 }
 
 #[cfg(not(feature = "with-ocr"))]
-#[post("/api/ocr/unload")]
-async fn unload_model(_state: web::Data<AppState>) -> impl Responder {
-    HttpResponse::NotImplemented().json(serde_json::json!({"error":"ocr-service built without feature 'with-ocr'; enable it to use native OCR"}))
+#[post("/api/ocr/deskew")]
+async fn deskew_image(_payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
 }
 
+/// Anonymized layout thumbnail of the multipart `file`: text is detected (not read) and each box is
+/// painted as a filled shape, so the PNG shows where text is but none of it. Optional fields:
+/// `fill` box color ("#rrggbb", default #808080), `background` ("blank" white (default), "blur"
+/// for a heavy blur of the page, or "#rrggbb"), `max_side` thumbnail size (32..=4096, default 512),
+/// `det_db_thresh`, `det_limit_side_len` and `model_id` as in recognize.
 #[cfg(feature = "with-ocr")]
-#[get("/api/ocr/model_status")]
-async fn model_status(state: web::Data<AppState>) -> impl Responder {
-    let guard = state.ocr.lock().unwrap();
-    HttpResponse::Ok().json(ModelStatus{ loaded: guard.is_some() })
+#[post("/api/ocr/layout_preview")]
+async fn layout_preview(mut payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    let _slot = match admit(&state) { Ok(slot) => slot, Err(e) => return e.error_response() };
+    let mut file_bytes: Option<Vec<u8>> = None;
+    let mut params = PredictParams::default();
+    let mut model_id: Option<String> = None;
+    let mut fill = preview::DEFAULT_FILL;
+    let mut background = preview::Background::Color([255, 255, 255]);
+    let mut max_side = preview::DEFAULT_MAX_SIDE;
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let name = field.content_disposition().get_name().unwrap_or("").to_string();
+        let data = match read_field(&mut field, &name).await { Ok(d) => d, Err(e) => return e.error_response() };
+        if name == "file" {
+            file_bytes = Some(data);
+            continue;
+        }
+        let value = String::from_utf8_lossy(&data);
+        let value = value.trim();
+        match predict_field(&mut params, &name, value) {
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(e) => return e.error_response(),
+        }
+        match name.as_str() {
+            "model_id" if !value.is_empty() => model_id = Some(value.to_string()),
+            "fill" if !value.is_empty() => fill = match preview::parse_hex(value) {
+                Some(c) => c,
+                None => return ApiError::InvalidField(format!("Invalid 'fill': expected a #rrggbb color, got '{}'", value)).error_response(),
+            },
+            "background" if !value.is_empty() => background = match preview::Background::parse(value) {
+                Some(b) => b,
+                None => return ApiError::InvalidField(format!("Invalid 'background': expected 'blank', 'blur' or a #rrggbb color, got '{}'", value)).error_response(),
+            },
+            "max_side" if !value.is_empty() => max_side = match value.parse::<u32>() {
+                Ok(v) if (32..=4096).contains(&v) => v,
+                _ => return ApiError::InvalidField(format!("Invalid 'max_side': expected an integer in 32..=4096, got '{}'", value)).error_response(),
+            },
+            _ => {}
+        }
+    }
+
+    let bytes = match file_bytes { Some(b) => b, None => return ApiError::MissingField("file").error_response(), };
+    let img = match decode::rgb_image(&bytes) { Ok(img) => img, Err(e) => return e.error_response() };
+    let pool = match select_model(&state, model_id.as_deref()).await { Ok(p) => p, Err(e) => return e.error_response() };
+    let regions = match run_detection(&Ocr::new(&pool, &state.inference), img.clone(), params).await { Ok(r) => r, Err(e) => return e.error_response() };
+
+    let rendered = web::block(move || {
+        let thumb = preview::render(&img, &regions, fill, &background, max_side);
+        let mut buf = Vec::new();
+        PngEncoder::new(&mut buf).write_image(thumb.as_raw(), thumb.width(), thumb.height(), ColorType::Rgb8.into()).map(|_| buf)
+    })
+    .await;
+    match rendered {
+        Ok(Ok(png)) => HttpResponse::Ok().content_type("image/png").body(png),
+        Ok(Err(e)) => ApiError::Internal(format!("Failed to encode PNG: {}", e)).error_response(),
+        Err(e) => ApiError::Internal(format!("Task error: {}", e)).error_response(),
+    }
 }
 
 #[cfg(not(feature = "with-ocr"))]
-#[get("/api/ocr/model_status")]
-async fn model_status(_state: web::Data<AppState>) -> impl Responder {
-    HttpResponse::Ok().json(serde_json::json!({"loaded": false, "note": "ocr-service built without feature 'with-ocr'"}))
+#[post("/api/ocr/layout_preview")]
+async fn layout_preview(_payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
 }
 
-/// Accepts multipart form with `file` and optional form fields:
-/// det_db_thresh (f32), cls_thresh (f32), use_cls (bool)
+/// Runs OCR on the multipart `file` and returns the lines as a flat transcript in reading order:
+/// `{"lines": [{"text", "x", "y", "height"}], "full_text"}`, with each line's top-left corner
+/// and height in image pixels so edits can be mapped back onto the image.
 #[cfg(feature = "with-ocr")]
-#[post("/api/ocr/")]
-async fn recognize(mut payload: Multipart, state: web::Data<AppState>) -> impl Responder {
-    // collect fields
+#[post("/api/ocr/transcript")]
+async fn transcript(mut payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    let _slot = match admit(&state) { Ok(slot) => slot, Err(e) => return e.error_response() };
     let mut file_bytes: Option<Vec<u8>> = None;
-    let mut det_db_thresh: f32 = 0.3;
-    let mut cls_thresh: f32 = 0.9;
-    let mut use_cls: bool = true;
-
     while let Ok(Some(mut field)) = payload.try_next().await {
-        let content_disposition = field.content_disposition();
-        let name = content_disposition
-            .get_name()
-            .map(|s| s.to_string())
-            .unwrap_or_default();
-
+        let name = field.content_disposition().get_name().unwrap_or("").to_string();
         if name == "file" {
-            let mut data = Vec::new();
-            while let Some(chunk) = field.next().await {
-                let chunk = chunk.unwrap();
-                data.extend_from_slice(&chunk);
-            }
-            file_bytes = Some(data);
-        } else if name == "det_db_thresh" {
-            let mut d = Vec::new();
-            while let Some(chunk) = field.next().await { d.extend_from_slice(&chunk.unwrap()); }
-            if let Ok(s) = std::str::from_utf8(&d) { if let Ok(v) = s.parse::<f32>() { det_db_thresh = v; } }
-        } else if name == "cls_thresh" {
-            let mut d = Vec::new();
-            while let Some(chunk) = field.next().await { d.extend_from_slice(&chunk.unwrap()); }
-            if let Ok(s) = std::str::from_utf8(&d) { if let Ok(v) = s.parse::<f32>() { cls_thresh = v; } }
-        } else if name == "use_cls" {
-            let mut d = Vec::new();
-            while let Some(chunk) = field.next().await { d.extend_from_slice(&chunk.unwrap()); }
-            if let Ok(s) = std::str::from_utf8(&d) { if let Ok(v) = s.parse::<bool>() { use_cls = v; } }
-        }
-    }
-
-    let bytes = match file_bytes { Some(b) => b, None => return HttpResponse::BadRequest().json(serde_json::json!({"error":"missing file"})), };
-
-    // Note: PDF support is not implemented here. Return helpful error matching python behaviour.
-    if let Ok(name) = infer_image_format(&bytes) {
-        // OK image
-    } else {
-        return HttpResponse::BadRequest().json(serde_json::json!({"error":"Unsupported file type or PDF is not supported by Rust service yet"}));
+            file_bytes = Some(match read_field(&mut field, &name).await { Ok(d) => d, Err(e) => return e.error_response() });
+        }
     }
 
-    // Decode image
-    let dyn_img = match load_from_memory(&bytes) {
-        Ok(d) => d.to_rgb8(),
-        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({"error": format!("Failed to decode image: {}", e)})),
-    };
+    let bytes = match file_bytes { Some(b) => b, None => return ApiError::MissingField("file").error_response(), };
+    let dyn_img = match decode::rgb_image(&bytes) { Ok(img) => img, Err(e) => return e.error_response() };
+    let pool = match select_model(&state, None).await { Ok(p) => p, Err(e) => return e.error_response() };
+    let regions = match run_ocr(&Ocr::new(&pool, &state.inference), dyn_img, PredictParams::default()).await { Ok(r) => r, Err(e) => return e.error_response() };
 
-    // Clone Arc<OAROCR> out of the lock then drop the lock before blocking.
-    let arc_opt = {
-        let guard = state.ocr.lock().unwrap();
-        guard.as_ref().cloned()
-    };
-    let arc_ocr = match arc_opt {
-        Some(a) => a,
-        None => return HttpResponse::BadRequest().json(serde_json::json!({"error":"Model not loaded"})),
-    };
+    HttpResponse::Ok().json(export::transcript(&regions))
+}
 
-    // Run OCR in blocking thread because predict is CPU-heavy
-    let img_vec = vec![dyn_img.clone()];
-    let arc_for_thread = arc_ocr.clone();
-    let res = web::block(move || arc_for_thread.predict(img_vec)).await;
-    let results = match res {
-        Ok(Ok(mut vec_res)) => vec_res.remove(0),
-        Ok(Err(e)) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("OCR error: {}", e)})),
-        Err(e) => return HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Task error: {}", e)})),
-    };
+#[cfg(not(feature = "with-ocr"))]
+#[post("/api/ocr/transcript")]
+async fn transcript(_payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
+}
 
-    // Convert result into Python-compatible structure
-    // Python format: {"result": [ [box_points, [text,score]], ... ] }
-    let mut lines: Vec<serde_json::Value> = Vec::new();
-    for region in results.text_regions.iter() {
-        let box_points: Vec<Vec<f32>> = region.bounding_box.points.iter().map(|p| vec![p.x, p.y]).collect();
-        let text = region.text.as_ref().map(|s| s.to_string()).unwrap_or_default();
-        let score = region.confidence.unwrap_or(0.0);
-        lines.push(serde_json::json!([box_points, [text, score]]));
+/// Recognition only, for boxes the caller already has (e.g. corrected by hand): multipart
+/// `file`, `boxes` as a JSON list of polygons `[[[x, y], ...], ...]` and optional `model_id`.
+/// Every point must lie within the image, or the 400 names the first offending box (0-based
+/// index). Returns `{"result": [[box, [text, score]], ...]}` in the same order as `boxes`.
+#[cfg(feature = "with-ocr")]
+#[post("/api/ocr/recognize_with_boxes")]
+async fn recognize_with_boxes(payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    recognize_boxes_request(payload, state, false).await
+}
+
+/// recognize_with_boxes for quadrilaterals from a layout analyzer or annotation tool: each box
+/// must have exactly 4 points, all within the image, or the 400 names the first offending box
+/// (0-based index). Same fields and response.
+#[cfg(feature = "with-ocr")]
+#[post("/api/ocr/recognize_boxes")]
+async fn recognize_boxes(payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    recognize_boxes_request(payload, state, true).await
+}
+
+#[cfg(feature = "with-ocr")]
+async fn recognize_boxes_request(mut payload: Multipart, state: web::Data<AppState>, quads_only: bool) -> HttpResponse {
+    let _slot = match admit(&state) { Ok(slot) => slot, Err(e) => return e.error_response() };
+    let mut file_bytes: Option<Vec<u8>> = None;
+    let mut boxes: Option<Vec<Vec<[f32; 2]>>> = None;
+    let mut model_id: Option<String> = None;
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let name = field.content_disposition().get_name().unwrap_or("").to_string();
+        let data = match read_field(&mut field, &name).await { Ok(d) => d, Err(e) => return e.error_response() };
+        match name.as_str() {
+            "file" => file_bytes = Some(data),
+            "boxes" => match serde_json::from_slice::<Vec<Vec<[f32; 2]>>>(&data) {
+                Ok(b) if b.iter().flatten().flatten().all(|v| v.is_finite()) => boxes = Some(b),
+                Ok(_) => return ApiError::InvalidField("Invalid 'boxes': coordinates must be finite numbers".into()).error_response(),
+                Err(e) => return ApiError::InvalidField(format!("Invalid 'boxes': expected a JSON list of polygons [[[x, y], ...], ...]: {}", e)).error_response(),
+            },
+            "model_id" if !data.is_empty() => model_id = Some(String::from_utf8_lossy(&data).trim().to_string()),
+            _ => {}
+        }
+    }
+
+    let bytes = match file_bytes { Some(b) => b, None => return ApiError::MissingField("file").error_response(), };
+    let boxes = match boxes { Some(b) => b, None => return ApiError::MissingField("boxes").error_response(), };
+    let dyn_img = match decode::rgb_image(&bytes) { Ok(img) => img, Err(e) => return e.error_response() };
+    if let Err(e) = check_boxes(&boxes, dyn_img.dimensions(), quads_only) {
+        return e.error_response();
     }
+    let pool = match select_model(&state, model_id.as_deref()).await { Ok(p) => p, Err(e) => return e.error_response() };
 
-    HttpResponse::Ok().json(serde_json::json!({"result": [lines]}))
+    let polygons = boxes.clone();
+    let read = match Ocr::new(&pool, &state.inference).run("OCR", move |model| model.recognize_polygons(&dyn_img, &polygons)).await {
+        Ok(read) => read,
+        Err(e) => return e.error_response(),
+    };
+
+    let result: Vec<serde_json::Value> = boxes
+        .iter()
+        .zip(read)
+        .map(|(points, (text, score))| serde_json::json!([points, [text, score]]))
+        .collect();
+    HttpResponse::Ok().json(serde_json::json!({"result": result}))
 }
 
 #[cfg(not(feature = "with-ocr"))]
-#[post("/api/ocr/")]
-async fn recognize(_payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
-    HttpResponse::NotImplemented().json(serde_json::json!({"error":"ocr-service built without feature 'with-ocr'; enable it to use native OCR"}))
+#[post("/api/ocr/recognize_with_boxes")]
+async fn recognize_with_boxes(_payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
+}
+
+#[cfg(not(feature = "with-ocr"))]
+#[post("/api/ocr/recognize_boxes")]
+async fn recognize_boxes(_payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
 }
 
-// Very small helper to try to infer whether bytes are image-like
-fn infer_image_format(bytes: &[u8]) -> Result<(), ()> {
-    if image::guess_format(bytes).is_ok() { Ok(()) } else { Err(()) }
+// Every point inside the width x height image, so a box can't ask for a crop larger than the
+// image; with `quads_only` (recognize_boxes), also 4 points per box
+#[cfg(feature = "with-ocr")]
+fn check_boxes(boxes: &[Vec<[f32; 2]>], (width, height): (u32, u32), quads_only: bool) -> Result<(), ApiError> {
+    for (i, points) in boxes.iter().enumerate() {
+        if quads_only && points.len() != 4 {
+            return Err(ApiError::InvalidField(format!("Invalid 'boxes': box {} has {} points, expected 4", i, points.len())));
+        }
+        if let Some([x, y]) = points.iter().find(|[x, y]| !(0.0..=width as f32).contains(x) || !(0.0..=height as f32).contains(y)) {
+            return Err(ApiError::InvalidField(format!("Invalid 'boxes': box {} has point ({}, {}) outside the {}x{} image", i, x, y, width, height)));
+        }
+    }
+    Ok(())
 }
 
-#[derive(Deserialize)]
-struct OcrResultWrapper { result: serde_json::Value }
+// Upper bound for PDF rasterization; a letter page at 1200 dpi is already ~135 MP
+#[cfg(feature = "with-ocr")]
+const MAX_DPI: f32 = 1200.0;
+
+// `coords` form field: whether boxes are normalized to 0..=1 (`normalized`) or in pixels (`pixel`)
+fn coords_field(value: &str) -> Result<bool, ApiError> {
+    match value {
+        "pixel" => Ok(false),
+        "normalized" => Ok(true),
+        _ => Err(ApiError::InvalidField(format!("Invalid 'coords': expected 'pixel' or 'normalized', got '{}'", value))),
+    }
+}
+
+// A result line's box points divided by its page size, for coords=normalized
+#[cfg(feature = "with-ocr")]
+fn normalize_box(line: &mut serde_json::Value, (width, height): (u32, u32)) {
+    let Some(points) = line.get_mut(0).and_then(|b| b.as_array_mut()) else { return };
+    for point in points.iter_mut() {
+        if let (Some(x), Some(y)) = (point[0].as_f64(), point[1].as_f64()) {
+            *point = serde_json::json!([x / width.max(1) as f64, y / height.max(1) as f64]);
+        }
+    }
+}
 
-// draw endpoint: takes file + ocr_result (string JSON) and returns PNG image bytes
+// Parse a numeric form field, rejecting NaN/inf and values outside [min, max] with a 400 naming the field
+fn number_field(name: &str, value: &str, min: f32, max: f32) -> Result<f32, ApiError> {
+    match value.trim().parse::<f32>() {
+        Ok(v) if v.is_finite() && v >= min && v <= max => Ok(v),
+        _ => Err(ApiError::InvalidField(format!("Invalid '{}': expected a number between {} and {}, got '{}'", name, min, max, value.trim()))),
+    }
+}
+
+// draw endpoint: takes file + ocr_result (string JSON) and returns PNG image bytes.
+// Recognized text is written above each box; `side_by_side=true` instead puts the texts on a
+// white panel to the right of the image, like PaddleOCR's draw_ocr_box_txt.
+// `ocr_result` may be in either result schema (`result` or v2 `regions`); only page 1 is drawn.
+// Boxes may be in pixels or normalized to 0..=1: `coords` (pixel|normalized) says which, else
+// the result's `meta.coords`, else all-within-0..=1 boxes are taken as normalized.
+// `deskew=true` levels a slightly rotated scan: the image and boxes are turned by the median
+// long-edge angle of the drawn boxes, at most DRAW_MAX_DESKEW degrees either way, and the turn
+// is reported in `X-Deskew-Angle` (degrees, counter-clockwise positive, as /api/ocr/deskew).
+// `color_by_score=true` outlines each box on a red-yellow-green scale by its score (0.5 or below
+// red, 1.0 green; gray without a score) instead of plain red, and `fill=true` also shades it with
+// that color; `X-Score-Legend` gives the scale's stops as `score=#rrggbb` pairs for drawing a key.
 #[post("/api/ocr/draw")]
-async fn draw(mut payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
+async fn draw(mut payload: Multipart, state: web::Data<AppState>) -> impl Responder {
     let mut file_bytes: Option<Vec<u8>> = None;
     let mut ocr_result_str: Option<String> = None;
     let mut drop_score: f32 = 0.5;
+    let mut side_by_side = false;
+    let mut normalized_coords: Option<bool> = None;
+    let mut deskew = false;
+    let mut color_by_score = false;
+    let mut fill = false;
 
     while let Ok(Some(mut field)) = payload.try_next().await {
-        let name = field.content_disposition().get_name().unwrap_or("");
+        let name = field.content_disposition().get_name().unwrap_or("").to_string();
+        let data = match read_field(&mut field, &name).await { Ok(d) => d, Err(e) => return e.error_response() };
         if name == "file" {
-            let mut data = Vec::new();
-            while let Some(chunk) = field.next().await { data.extend_from_slice(&chunk.unwrap()); }
             file_bytes = Some(data);
         } else if name == "ocr_result" {
-            let mut data = Vec::new();
-            while let Some(chunk) = field.next().await { data.extend_from_slice(&chunk.unwrap()); }
             if let Ok(s) = String::from_utf8(data) { ocr_result_str = Some(s); }
         } else if name == "drop_score" {
-            let mut d = Vec::new(); while let Some(chunk) = field.next().await { d.extend_from_slice(&chunk.unwrap()); }
-            if let Ok(s) = std::str::from_utf8(&d) { if let Ok(v) = s.parse::<f32>() { drop_score = v; } }
+            drop_score = match number_field("drop_score", &String::from_utf8_lossy(&data), 0.0, 1.0) { Ok(v) => v, Err(e) => return e.error_response() };
+        } else if name == "side_by_side" && let Ok(s) = std::str::from_utf8(&data) && let Ok(v) = s.trim().parse::<bool>() {
+            side_by_side = v;
+        } else if name == "coords" {
+            normalized_coords = match coords_field(String::from_utf8_lossy(&data).trim()) { Ok(v) => Some(v), Err(e) => return e.error_response() };
+        } else if name == "deskew" && let Ok(s) = std::str::from_utf8(&data) && let Ok(v) = s.trim().parse::<bool>() {
+            deskew = v;
+        } else if name == "color_by_score" && let Ok(s) = std::str::from_utf8(&data) && let Ok(v) = s.trim().parse::<bool>() {
+            color_by_score = v;
+        } else if name == "fill" && let Ok(s) = std::str::from_utf8(&data) && let Ok(v) = s.trim().parse::<bool>() {
+            fill = v;
         }
     }
 
-    let bytes = match file_bytes { Some(b) => b, None => return HttpResponse::BadRequest().json(serde_json::json!({"error":"missing file"})), };
-    let ocr_json = match ocr_result_str { Some(s) => s, None => return HttpResponse::BadRequest().json(serde_json::json!({"error":"missing ocr_result"})), };
+    let bytes = match file_bytes { Some(b) => b, None => return ApiError::MissingField("file").error_response(), };
+    let ocr_json = match ocr_result_str { Some(s) => s, None => return ApiError::MissingField("ocr_result").error_response(), };
+    // Held through decoding, drawing and encoding, the memory-heavy part
+    let _slot = match enter(&state.draws) { Ok(slot) => slot, Err(e) => return e.error_response() };
 
     // decode image
-    let dyn_img = match load_from_memory(&bytes) { Ok(d) => d.to_rgb8(), Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({"error": format!("Failed to decode image: {}", e)})), };
+    let mut dyn_img = match decode::rgb_image(&bytes) { Ok(img) => img, Err(e) => return e.error_response() };
 
     // parse ocr_result JSON and convert into the expected format used by visualization
-    let parsed: serde_json::Value = match serde_json::from_str(&ocr_json) { Ok(v) => v, Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({"error": format!("Invalid ocr_result JSON: {}", e)})), };
+    let parsed: serde_json::Value = match serde_json::from_str(&ocr_json) { Ok(v) => v, Err(e) => return ApiError::InvalidField(format!("Invalid ocr_result JSON: {}", e)).error_response(), };
+    let parsed = match schema::to_legacy(&parsed) { Ok(p) => p, Err(e) => return e.error_response() };
 
-    // Build a minimal OAROCRResult that visualization functions can use would be heavy; for now use simple drawing: draw bounding boxes from parsed data
-    use imageproc::drawing::draw_hollow_rect_mut;
+    // Draw each detected quadrilateral as-is so rotated or skewed lines aren't inflated to their bounding rect
+    use imageproc::drawing::{draw_hollow_polygon_mut, draw_hollow_rect_mut, draw_polygon_mut, draw_text_mut};
+    use imageproc::point::Point;
     use imageproc::rect::Rect;
-    use image::Rgb;
-
-    let mut output = dyn_img.clone();
-    if let Some(lines) = parsed.get("result") {
-        // Expecting result to be [ lines ] (for single page)
-        let lines_arr = if lines.is_array() && !lines.as_array().unwrap().is_empty() { &lines.as_array().unwrap()[0] } else { lines };
-        if let Some(arr) = lines_arr.as_array() {
-            for item in arr.iter() {
-                // each item like [box_points, [text,score]]
-                if let Some(box_points) = item.get(0) {
-                    if let Some(pts) = box_points.as_array() {
-                        // compute bounding rect
-                        let xs: Vec<i32> = pts.iter().filter_map(|p| p.as_array().and_then(|pa| pa.get(0)).and_then(|x| x.as_f64()).map(|x| x as i32)).collect();
-                        let ys: Vec<i32> = pts.iter().filter_map(|p| p.as_array().and_then(|pa| pa.get(1)).and_then(|y| y.as_f64()).map(|y| y as i32)).collect();
-                        if !xs.is_empty() && !ys.is_empty() {
-                            let x_min = *xs.iter().min().unwrap();
-                            let x_max = *xs.iter().max().unwrap();
-                            let y_min = *ys.iter().min().unwrap();
-                            let y_max = *ys.iter().max().unwrap();
-                            let rect = Rect::at(x_min, y_min).of_size((x_max - x_min) as u32, (y_max - y_min) as u32);
-                            draw_hollow_rect_mut(&mut output, rect, Rgb([255u8, 0u8, 0u8]));
-                        }
-                    }
-                }
+    use image::{GrayImage, Luma, Rgb, RgbImage};
+
+    let font = font::label_font();
+    if side_by_side && font.is_none() {
+        return ApiError::Internal("No font available for text labels; set FONT_PATH to a TrueType font with CJK coverage".into()).error_response();
+    }
+
+    // (box points, recognized text, score) for every line that passes drop_score
+    let mut boxes: Vec<(Vec<Point<f32>>, String, Option<f64>)> = Vec::new();
+    // The image is a single page, so only the first page of the result applies
+    let pages = match result_pages(&parsed) { Ok(p) => p, Err(e) => return e.error_response() };
+    if let Some((_, arr)) = pages.first() {
+        for item in arr.iter() {
+            // each item like [box_points, [text,score]]; lines without a score are always drawn
+            let score = item.get(1).and_then(|t| t.get(1)).and_then(|s| s.as_f64());
+            if score.is_some_and(|s| (s as f32) < drop_score) {
+                continue;
+            }
+            let Some(pts) = item.get(0).and_then(|b| b.as_array()) else { continue };
+            let points: Vec<Point<f32>> = pts
+                .iter()
+                .filter_map(|p| {
+                    let pa = p.as_array()?;
+                    Some(Point::new(pa.first()?.as_f64()? as f32, pa.get(1)?.as_f64()? as f32))
+                })
+                .collect();
+            if points.is_empty() {
+                continue;
+            }
+            let text = item.get(1).and_then(|t| t.get(0)).and_then(|t| t.as_str()).unwrap_or("").to_string();
+            boxes.push((points, text, score));
+        }
+    }
+
+    let (width, height) = dyn_img.dimensions();
+    // Normalized boxes (recognize's coords=normalized) are scaled back up to this image. Without a
+    // `coords` field, the result's meta.coords decides, else boxes entirely within 0..=1 are
+    // taken as normalized: in pixels they'd be too small to draw anyway
+    let normalized = normalized_coords.unwrap_or_else(|| match parsed.get("meta").and_then(|m| m.get("coords")).and_then(|c| c.as_str()) {
+        Some(coords) => coords == "normalized",
+        None => !boxes.is_empty() && boxes.iter().flat_map(|(points, _, _)| points).all(|p| (0.0..=1.0).contains(&p.x) && (0.0..=1.0).contains(&p.y)),
+    });
+    if normalized {
+        for (points, _, _) in boxes.iter_mut() {
+            points.iter_mut().for_each(|p| *p = Point::new(p.x * width as f32, p.y * height as f32));
+        }
+    }
+    let skew = if deskew {
+        let corners: Vec<Vec<[f32; 2]>> = boxes.iter().map(|(points, _, _)| points.iter().map(|p| [p.x, p.y]).collect()).collect();
+        let skew = deskew::estimate_angle(corners.iter().map(Vec::as_slice)).unwrap_or(0.0).clamp(-DRAW_MAX_DESKEW, DRAW_MAX_DESKEW);
+        for (points, _, _) in boxes.iter_mut() {
+            points.iter_mut().for_each(|p| {
+                let [x, y] = deskew::straighten_point([p.x, p.y], (width, height), skew);
+                *p = Point::new(x, y);
+            });
+        }
+        dyn_img = deskew::straighten(&dyn_img, skew);
+        Some(skew)
+    } else {
+        None
+    };
+    let (width, height) = dyn_img.dimensions();
+    let mut output = if side_by_side {
+        let mut canvas = RgbImage::from_pixel(width * 2, height, Rgb([255u8, 255u8, 255u8]));
+        image::imageops::replace(&mut canvas, &dyn_img, 0, 0);
+        canvas
+    } else {
+        dyn_img.clone()
+    };
+
+    let text_color = Rgb([0u8, 0u8, 255u8]);
+    let outline = |canvas: &mut RgbImage, points: &[Point<f32>], color: Rgb<u8>| {
+        // Client boxes may repeat points or close the ring, which the polygon drawing rejects
+        let mut polygon = points.to_vec();
+        polygon.dedup();
+        if polygon.len() > 1 && polygon.first() == polygon.last() {
+            polygon.pop();
+        }
+        if polygon.len() >= 3 {
+            draw_hollow_polygon_mut(canvas, &polygon, color);
+        } else {
+            // degenerate box: fall back to the bounding rect
+            let x_min = points.iter().map(|p| p.x).fold(f32::MAX, f32::min) as i32;
+            let x_max = points.iter().map(|p| p.x).fold(f32::MIN, f32::max) as i32;
+            let y_min = points.iter().map(|p| p.y).fold(f32::MAX, f32::min) as i32;
+            let y_max = points.iter().map(|p| p.y).fold(f32::MIN, f32::max) as i32;
+            let rect = Rect::at(x_min, y_min).of_size((x_max - x_min).max(1) as u32, (y_max - y_min).max(1) as u32);
+            draw_hollow_rect_mut(canvas, rect, color);
+        }
+    };
+
+    // Blend `color` into the box's area, working on its bounding rect only
+    let shade = |canvas: &mut RgbImage, points: &[Point<f32>], color: Rgb<u8>| {
+        let x0 = points.iter().map(|p| p.x).fold(f32::MAX, f32::min).max(0.0) as i32;
+        let y0 = points.iter().map(|p| p.y).fold(f32::MAX, f32::min).max(0.0) as i32;
+        let x1 = (points.iter().map(|p| p.x).fold(f32::MIN, f32::max).ceil() as i32).min(width as i32);
+        let y1 = (points.iter().map(|p| p.y).fold(f32::MIN, f32::max).ceil() as i32).min(height as i32);
+        if points.len() < 3 || x1 <= x0 || y1 <= y0 {
+            return;
+        }
+        let mut polygon: Vec<Point<i32>> = points.iter().map(|p| Point::new(p.x.round() as i32 - x0, p.y.round() as i32 - y0)).collect();
+        polygon.dedup();
+        if polygon.len() > 1 && polygon.first() == polygon.last() {
+            polygon.pop();
+        }
+        if polygon.len() < 3 {
+            return;
+        }
+        let mut mask = GrayImage::new((x1 - x0) as u32, (y1 - y0) as u32);
+        draw_polygon_mut(&mut mask, &polygon, Luma([255u8]));
+        for (mx, my, m) in mask.enumerate_pixels() {
+            if m[0] == 0 {
+                continue;
             }
+            let pixel = canvas.get_pixel_mut(mx + x0 as u32, my + y0 as u32);
+            for c in 0..3 {
+                pixel[c] = (pixel[c] as f32 * (1.0 - DRAW_FILL_ALPHA) + color[c] as f32 * DRAW_FILL_ALPHA).round() as u8;
+            }
+        }
+    };
+
+    for (points, text, score) in boxes.iter() {
+        let color = if color_by_score { score_color(*score) } else { Rgb([255u8, 0u8, 0u8]) };
+        if fill {
+            shade(&mut output, points, color);
+        }
+        outline(&mut output, points, color);
+        let Some(font) = font else { continue };
+        if text.is_empty() {
+            continue;
+        }
+        let x_min = points.iter().map(|p| p.x).fold(f32::MAX, f32::min);
+        let y_min = points.iter().map(|p| p.y).fold(f32::MAX, f32::min);
+        let y_max = points.iter().map(|p| p.y).fold(f32::MIN, f32::max);
+        // Font size follows the line height so labels stay legible on both tiny and huge text
+        let size = ((y_max - y_min) * 0.8).clamp(10.0, 64.0);
+        if side_by_side {
+            // Right panel: same layout as the image, text written inside its box
+            let shifted: Vec<Point<f32>> = points.iter().map(|p| Point::new(p.x + width as f32, p.y)).collect();
+            outline(&mut output, &shifted, color);
+            draw_text_mut(&mut output, text_color, (x_min + width as f32) as i32, y_min as i32, size, font, text);
+        } else {
+            // Above the box, or just inside it when the box touches the top edge
+            let y = if y_min >= size { y_min - size } else { y_min };
+            draw_text_mut(&mut output, text_color, x_min as i32, y as i32, size, font, text);
         }
     }
 
@@ -264,78 +2331,462 @@ async fn draw(mut payload: Multipart, _state: web::Data<AppState>) -> impl Respo
         let w = output.width();
         let h = output.height();
         let data = output.as_raw();
-        let mut encoder = PngEncoder::new(&mut buf);
+        let encoder = PngEncoder::new(&mut buf);
         encoder.write_image(data, w, h, ColorType::Rgb8.into())
     };
     match encode_res {
-        Ok(_) => HttpResponse::Ok().content_type("image/png").body(buf),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({"error": format!("Failed to encode PNG: {}", e)})),
+        Ok(_) => {
+            let mut resp = HttpResponse::Ok();
+            if let Some(skew) = skew {
+                resp.insert_header(("X-Deskew-Angle", format!("{:.2}", skew)));
+            }
+            if color_by_score {
+                resp.insert_header(("X-Score-Legend", score_legend()));
+            }
+            resp.content_type("image/png").body(buf)
+        }
+        Err(e) => ApiError::Internal(format!("Failed to encode PNG: {}", e)).error_response(),
+    }
+}
+
+// draw's `deskew` leaves steeper angles partly uncorrected: past this a page is more likely laid
+// out rotated on purpose than scanned crooked
+const DRAW_MAX_DESKEW: f32 = 15.0;
+
+// draw's `fill` opacity, light enough that the text underneath stays readable
+const DRAW_FILL_ALPHA: f32 = 0.3;
+
+// color_by_score's scale: red at or below the first stop, through yellow, to green at 1.0
+const SCORE_STOPS: [f64; 3] = [0.5, 0.75, 1.0];
+
+// Box color for color_by_score; lines without a score are gray
+fn score_color(score: Option<f64>) -> image::Rgb<u8> {
+    let Some(score) = score else { return image::Rgb([128, 128, 128]) };
+    let t = ((score - SCORE_STOPS[0]) / (SCORE_STOPS[2] - SCORE_STOPS[0])).clamp(0.0, 1.0);
+    let red = (2.0 * (1.0 - t)).min(1.0) * 255.0;
+    let green = (2.0 * t).min(1.0) * 255.0;
+    image::Rgb([red.round() as u8, green.round() as u8, 0])
+}
+
+// X-Score-Legend: each scale stop and the unscored color, e.g. "0.50=#ff0000,...,none=#808080"
+fn score_legend() -> String {
+    let hex = |c: image::Rgb<u8>| format!("#{:02x}{:02x}{:02x}", c[0], c[1], c[2]);
+    let stops = SCORE_STOPS.iter().map(|&s| format!("{:.2}={}", s, hex(score_color(Some(s)))));
+    stops.chain(std::iter::once(format!("none={}", hex(score_color(None))))).collect::<Vec<_>>().join(",")
+}
+
+/// Searchable PDF from multipart `file` and `ocr_result` (as for draw): the image is the visible
+/// page and each line's text sits invisibly over its box, so it can be selected and searched.
+/// Needs a CJK-capable font (FONT_PATH, else fonts/simfang.ttf), which is embedded.
+#[post("/api/ocr/searchable_pdf")]
+async fn make_searchable_pdf(mut payload: Multipart) -> impl Responder {
+    let mut file_bytes: Option<Vec<u8>> = None;
+    let mut ocr_result_str: Option<String> = None;
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let name = field.content_disposition().get_name().unwrap_or("").to_string();
+        let data = match read_field(&mut field, &name).await { Ok(d) => d, Err(e) => return e.error_response() };
+        match name.as_str() {
+            "file" => file_bytes = Some(data),
+            "ocr_result" => ocr_result_str = String::from_utf8(data).ok(),
+            _ => {}
+        }
+    }
+
+    let bytes = match file_bytes { Some(b) => b, None => return ApiError::MissingField("file").error_response(), };
+    let ocr_json = match ocr_result_str { Some(s) => s, None => return ApiError::MissingField("ocr_result").error_response(), };
+    let img = match decode::rgb_image(&bytes) { Ok(img) => img, Err(e) => return e.error_response() };
+    let parsed: serde_json::Value = match serde_json::from_str(&ocr_json) { Ok(v) => v, Err(e) => return ApiError::InvalidField(format!("Invalid ocr_result JSON: {}", e)).error_response(), };
+    let parsed = match schema::to_legacy(&parsed) { Ok(p) => p, Err(e) => return e.error_response() };
+    let pages = match result_pages(&parsed) { Ok(p) => p, Err(e) => return e.error_response() };
+    let Some(font) = font::label_font() else {
+        return ApiError::Internal("No font available for the text layer; set FONT_PATH to a TrueType font with CJK coverage".into()).error_response();
+    };
+
+    // The image is a single page, so only the first page of the result applies
+    let lines: Vec<searchable_pdf::Line> = pages
+        .first()
+        .map(|(_, lines)| *lines)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|item| {
+            let points = item
+                .get(0)?
+                .as_array()?
+                .iter()
+                .filter_map(|p| Some([p.get(0)?.as_f64()? as f32, p.get(1)?.as_f64()? as f32]))
+                .collect();
+            let text = item.get(1)?.get(0)?.as_str()?.to_string();
+            Some(searchable_pdf::Line { points, text })
+        })
+        .collect();
+
+    match web::block(move || searchable_pdf::build(&img, &lines, font)).await {
+        Ok(Ok(pdf)) => HttpResponse::Ok().content_type("application/pdf").body(pdf),
+        Ok(Err(e)) => ApiError::Internal(e).error_response(),
+        Err(e) => ApiError::Internal(format!("Task error: {}", e)).error_response(),
     }
 }
 
 // ocr2text endpoint
+// Takes either result schema: the Python-format `result` or v2 `regions`.
+// Optional "sort": "none" (default, detection order) or "reading_order" (top to bottom, then left to right).
+// Optional "paragraph": true sorts in reading order, joins side-by-side boxes with a space and puts a
+// blank line where the vertical gap exceeds 1.5x the median line height.
 #[post("/api/ocr/ocr2text")]
 async fn ocr2text(body: web::Json<serde_json::Value>) -> impl Responder {
-    // Mirror python behavior: accept {"result": ...}
-    if body.get("result").is_none() {
-        return HttpResponse::BadRequest().json(serde_json::json!({"error":"Invalid OCR result format"}));
+    // A non-string sort is as invalid as an unknown one
+    let sort = body.get("sort").map(|v| v.as_str().unwrap_or_default());
+    let layout = match text_layout(sort, body.get("paragraph").and_then(|v| v.as_bool()) == Some(true)) { Ok(l) => l, Err(e) => return e.error_response() };
+    match result_text(&body, layout) {
+        Ok(full_text) => HttpResponse::Ok().json(serde_json::json!({"text": full_text})),
+        Err(e) => e.error_response(),
     }
-    let result_data = body.get("result").unwrap();
+}
+
+// ocr2text's and text's "sort" and "paragraph" options; paragraph wins over sort
+fn text_layout(sort: Option<&str>, paragraph: bool) -> Result<Layout, ApiError> {
+    let layout = match sort {
+        None | Some("none") => Layout::Detection,
+        Some("reading_order") => Layout::ReadingOrder,
+        Some(_) => return Err(ApiError::InvalidField("Invalid 'sort': expected 'none' or 'reading_order'".into())),
+    };
+    Ok(if paragraph { Layout::Paragraphs } else { layout })
+}
+
+// Text of a result in either schema, pages one after another
+fn result_text(body: &serde_json::Value, layout: Layout) -> Result<String, ApiError> {
+    let body = schema::to_legacy(body)?;
+    let pages = result_pages(&body)?;
     let mut all_text_lines: Vec<String> = Vec::new();
+    for (_, lines) in pages {
+        all_text_lines.extend(layout::page_text(lines, layout));
+    }
+    Ok(all_text_lines.join("\n"))
+}
+
+/// Recognize and ocr2text in one call, for when only the text is wanted: runs OCR on the
+/// multipart `file` (optional det_db_thresh, cls_thresh, use_cls, det_limit_side_len and model_id
+/// as in recognize) and returns `{"text": ...}` assembled as ocr2text would, with its `sort` and
+/// `paragraph` options as form fields.
+#[cfg(feature = "with-ocr")]
+#[post("/api/ocr/text")]
+async fn recognize_text(mut payload: Multipart, state: web::Data<AppState>) -> impl Responder {
+    let _slot = match admit(&state) { Ok(slot) => slot, Err(e) => return e.error_response() };
+    let mut file_bytes: Option<Vec<u8>> = None;
+    let mut params = PredictParams::default();
+    let mut model_id: Option<String> = None;
+    let (mut sort, mut paragraph) = (None, false);
+    while let Ok(Some(mut field)) = payload.try_next().await {
+        let name = field.content_disposition().get_name().unwrap_or("").to_string();
+        let data = match read_field(&mut field, &name).await { Ok(d) => d, Err(e) => return e.error_response() };
+        if name == "file" {
+            file_bytes = Some(data);
+            continue;
+        }
+        let value = String::from_utf8_lossy(&data);
+        let value = value.trim();
+        match predict_field(&mut params, &name, value) {
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(e) => return e.error_response(),
+        }
+        match name.as_str() {
+            "model_id" if !value.is_empty() => model_id = Some(value.to_string()),
+            "sort" if !value.is_empty() => sort = Some(value.to_string()),
+            "paragraph" => if let Ok(v) = value.parse::<bool>() { paragraph = v; },
+            _ => {}
+        }
+    }
+    let layout = match text_layout(sort.as_deref(), paragraph) { Ok(l) => l, Err(e) => return e.error_response() };
+
+    let bytes = match file_bytes { Some(b) => b, None => return ApiError::MissingField("file").error_response() };
+    let img = match decode::rgb_image(&bytes) { Ok(img) => img, Err(e) => return e.error_response() };
+    let (width, height) = img.dimensions();
+    let pool = match select_model(&state, model_id.as_deref()).await { Ok(p) => p, Err(e) => return e.error_response() };
+    let mut regions = match run_ocr(&Ocr::new(&pool, &state.inference), img, params).await { Ok(r) => r, Err(e) => return e.error_response() };
+    regions.iter_mut().for_each(|r| r.clip_to(width, height));
+
+    let result = serde_json::json!({"result": [regions.iter().map(Region::to_legacy).collect::<Vec<_>>()]});
+    match result_text(&result, layout) {
+        Ok(full_text) => HttpResponse::Ok().json(serde_json::json!({"text": full_text})),
+        Err(e) => e.error_response(),
+    }
+}
+
+#[cfg(not(feature = "with-ocr"))]
+#[post("/api/ocr/text")]
+async fn recognize_text(_payload: Multipart, _state: web::Data<AppState>) -> impl Responder {
+    ApiError::FeatureDisabled.error_response()
+}
+
+/// Text of a posted `{"result": ...}` (same shapes as ocr2text) laid out on a monospace grid:
+/// boxes are binned into rows by their tops, each line is indented to the column its left edge
+/// falls in (a column being the median character width of the lines), and vertical gaps become
+/// blank rows, so tables and forms keep a rough shape. Needs the image's `"width"` in pixels,
+/// else the result's `meta.width`. Returns `{"text": ...}`; pages follow one another.
+#[post("/api/ocr/layout_text")]
+async fn layout_text(body: web::Json<serde_json::Value>) -> impl Responder {
+    let width = match body.get("width").or_else(|| body.get("meta").and_then(|m| m.get("width"))) {
+        None => return ApiError::MissingField("width").error_response(),
+        Some(v) => match v.as_f64().filter(|w| w.is_finite() && *w > 0.0) {
+            Some(w) => w as f32,
+            None => return ApiError::InvalidField(format!("Invalid 'width': expected a positive number of pixels, got {}", v)).error_response(),
+        },
+    };
+    let body = match schema::to_legacy(&body) { Ok(b) => b, Err(e) => return e.error_response() };
+    let pages = match result_pages(&body) { Ok(p) => p, Err(e) => return e.error_response() };
+    let rows: Vec<String> = pages.into_iter().flat_map(|(_, lines)| layout::page_grid(lines, width)).collect();
+    HttpResponse::Ok().json(serde_json::json!({"text": rows.join("\n")}))
+}
+
+/// One row per line of a posted `{"result": ...}` (same shapes as ocr2text) as CSV or TSV
+/// (`"format"`, default csv), with columns page,line_index,text,score,x1,y1,...,x4,y4.
+#[post("/api/ocr/export")]
+async fn export_table(body: web::Json<serde_json::Value>) -> impl Responder {
+    let format = match body.get("format").map(|v| v.as_str()) {
+        None | Some(Some("csv")) => table::Format::Csv,
+        Some(Some("tsv")) => table::Format::Tsv,
+        Some(_) => return ApiError::InvalidField("Invalid 'format': expected 'csv' or 'tsv'".into()).error_response(),
+    };
+    let body = match schema::to_legacy(&body) { Ok(b) => b, Err(e) => return e.error_response() };
+    let pages = match result_pages(&body) { Ok(p) => p, Err(e) => return e.error_response() };
+
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .body(table::write(&pages, format))
+}
+
+/// Validate a hand-edited `{"result": ...}` and return it in canonical form, ready for draw,
+/// searchable_pdf, hocr and export: numeric coordinates rounded to 0.01 and clamped at 0 (and to
+/// the optional `width`/`height`), 4-point boxes reordered clockwise from the top-left, a missing
+/// score taken as 1.0. Any malformed line is a 400 naming its page and line.
+#[post("/api/ocr/finalize")]
+async fn finalize_result(body: web::Json<serde_json::Value>) -> impl Responder {
+    let Some(result) = body.get("result") else {
+        return ApiError::InvalidField("Invalid OCR result format".into()).error_response();
+    };
+    let size = match (body.get("width"), body.get("height")) {
+        (None, None) => None,
+        (Some(w), Some(h)) => match (w.as_f64(), h.as_f64()) {
+            (Some(w), Some(h)) if w > 0.0 && h > 0.0 => Some((w, h)),
+            _ => return ApiError::InvalidField("'width' and 'height' should be positive numbers".into()).error_response(),
+        },
+        _ => return ApiError::InvalidField("send both 'width' and 'height', or neither".into()).error_response(),
+    };
+    match finalize::finalize(result, size) {
+        Ok(result) => HttpResponse::Ok().json(serde_json::json!({"result": result})),
+        Err(e) => ApiError::InvalidField(format!("Invalid OCR result format - {}", e)).error_response(),
+    }
+}
+
+/// Dictionary correction of a posted `{"result": ..., "words": [...]}`: tokens within
+/// `max_distance` edits (optional; default 1 for short tokens, 2 otherwise) of a listed word are
+/// replaced by it. Returns `{"result"}` in the posted shape with boxes and scores unchanged, plus
+/// `corrections`: `[{page, line, from, to}]` (0-based page and line).
+#[post("/api/ocr/autocorrect")]
+async fn autocorrect_result(body: web::Json<serde_json::Value>) -> impl Responder {
+    let words: Vec<String> = match body.get("words").and_then(|w| w.as_array()) {
+        Some(list) => list.iter().filter_map(|w| w.as_str().map(str::to_string)).collect(),
+        None => return ApiError::InvalidField("missing 'words': expected a list of dictionary words".into()).error_response(),
+    };
+    let max_distance = match body.get("max_distance") {
+        None => None,
+        Some(v) => match v.as_u64() {
+            Some(d) if d <= 5 => Some(d as usize),
+            _ => return ApiError::InvalidField("Invalid 'max_distance': expected an integer between 0 and 5".into()).error_response(),
+        },
+    };
+    // Validates the shape the same way ocr2text does
+    if let Err(e) = result_pages(&body) {
+        return e.error_response();
+    }
+
+    let dict = autocorrect::Dictionary::new(&words, max_distance);
+    let mut result = body["result"].clone();
+    let corrections = autocorrect::correct_result(&mut result, &dict);
+    let corrections: Vec<serde_json::Value> = corrections
+        .iter()
+        .map(|c| serde_json::json!({"page": c.page, "line": c.line, "from": c.from, "to": c.to}))
+        .collect();
+    HttpResponse::Ok().json(serde_json::json!({"result": result, "corrections": corrections}))
+}
+
+/// hOCR document for a posted `{"result": ...}` (same shapes as ocr2text). Optional `"width"` and
+/// `"height"` give the page size; without them each page spans its lines.
+#[post("/api/ocr/hocr")]
+async fn hocr_document(body: web::Json<serde_json::Value>) -> impl Responder {
+    let dimension = |key: &str| body.get(key).and_then(|v| v.as_u64()).and_then(|v| u32::try_from(v).ok());
+    let size = match (dimension("width"), dimension("height")) {
+        (Some(w), Some(h)) => Some((w, h)),
+        (None, None) if body.get("width").is_none() && body.get("height").is_none() => None,
+        _ => return ApiError::InvalidField("'width' and 'height' must both be given as non-negative integers".into()).error_response(),
+    };
+    let body = match schema::to_legacy(&body) { Ok(b) => b, Err(e) => return e.error_response() };
+    let pages = match result_pages(&body) { Ok(p) => p, Err(e) => return e.error_response() };
+
+    HttpResponse::Ok()
+        .content_type("application/xhtml+xml; charset=utf-8")
+        .body(hocr::document(&pages, size))
+}
+
+// Pages of a posted Python-format `{"result": ...}` as (1-based page number, lines). Mirrors the
+// Python backend: multi-page results are `[{"page": 1, "result": [lines]}, ...]`, a single page
+// is `[lines]`. An empty result is valid and has no pages.
+fn result_pages(body: &serde_json::Value) -> Result<Vec<(u64, &[serde_json::Value])>, ApiError> {
+    let result_data = match body.get("result") {
+        Some(r) => r,
+        None => return Err(ApiError::InvalidField("Invalid OCR result format".into())),
+    };
+    let pages = match result_data.as_array() {
+        Some(arr) => arr,
+        None => return Err(ApiError::InvalidField("Invalid OCR result format - 'result' should be an array".into())),
+    };
+    if pages.is_empty() {
+        return Ok(Vec::new());
+    }
 
     // Detect multi-page result like [{"page":1, "result": [...]}, ...]
-    if result_data.is_array() && !result_data.as_array().unwrap().is_empty() && result_data.as_array().unwrap()[0].is_object() && result_data.as_array().unwrap()[0].get("page").is_some() {
-        for page in result_data.as_array().unwrap().iter() {
-            let page_result = page.get("result").unwrap_or(&serde_json::Value::Null);
-            if page_result.is_array() {
-                if let Some(first) = page_result.get(0) {
-                    if first.is_array() {
-                        for line in first.as_array().unwrap().iter() {
-                            if let Some(txt) = line.get(1).and_then(|t| t.get(0)).and_then(|s| s.as_str()) {
-                                if !txt.trim().is_empty() { all_text_lines.push(txt.to_string()); }
-                            }
-                        }
-                    }
+    if pages[0].is_object() && pages[0].get("page").is_some() {
+        pages
+            .iter()
+            .enumerate()
+            .map(|(i, page)| {
+                let number = page.get("page").and_then(|n| n.as_u64()).unwrap_or(i as u64 + 1);
+                let invalid = || ApiError::InvalidField(format!("Invalid OCR result format - page {} should have a 'result' list", number));
+                // A page without text comes back as `"result": []` or `"result": [null]`
+                match page.get("result").and_then(|r| r.as_array()).ok_or_else(invalid)?.first() {
+                    None | Some(serde_json::Value::Null) => Ok((number, &[][..])),
+                    Some(first) => Ok((number, first.as_array().ok_or_else(invalid)?.as_slice())),
                 }
-            }
-        }
+            })
+            .collect()
     } else {
         // single page expected: result[0] -> lines
-        if result_data.is_array() && !result_data.as_array().unwrap().is_empty() {
-            if let Some(page0) = result_data.as_array().unwrap().get(0) {
-                if page0.is_array() {
-                    for line in page0.as_array().unwrap().iter() {
-                        if let Some(txt) = line.get(1).and_then(|t| t.get(0)).and_then(|s| s.as_str()) {
-                            if !txt.trim().is_empty() { all_text_lines.push(txt.to_string()); }
-                        }
-                    }
-                }
-            }
+        match pages[0].as_array() {
+            Some(page0) => Ok(vec![(1, page0.as_slice())]),
+            None => Err(ApiError::InvalidField("Invalid OCR result format - expected a list of lines or pages".into())),
         }
     }
+}
+
+// Poll RSS and unload every model once it passes OCR_MEMORY_LIMIT_MB while no OCR request is in flight
+#[cfg(feature = "with-ocr")]
+fn spawn_memory_watch(state: AppState) {
+    let Some(limit) = state.memory.limit_mb() else { return };
+    if memory::rss_mb().is_none() {
+        log::warn!("OCR_MEMORY_LIMIT_MB is set but process memory can't be read on this platform; ignoring it");
+        return;
+    }
+    actix_rt::spawn(async move {
+        loop {
+            actix_rt::time::sleep(state.memory.poll_interval()).await;
+            if memory::rss_mb().is_none_or(|rss| rss <= limit) || state.jobs.depth() > 0 {
+                continue;
+            }
+            let parked = state.ocr.lock().unwrap().park_all();
+            if !parked.is_empty() {
+                state.memory.record("unloaded", &parked);
+            }
+        }
+    });
+}
+
+// Largest JSON body accepted (base64 uploads)
+const JSON_BODY_LIMIT: usize = 32 * 1024 * 1024;
 
-    let full_text = all_text_lines.join("\n");
-    HttpResponse::Ok().json(serde_json::json!({"text": full_text}))
+/// Stop the server gracefully: new connections are refused and in-flight requests get up to
+/// OCR_SHUTDOWN_GRACE_SECS (default 10) to finish. Needs the `X-Admin-Token` header to match
+/// OCR_ADMIN_TOKEN (the endpoint is disabled while that's unset); answers before the stop completes.
+#[post("/api/admin/shutdown")]
+async fn admin_shutdown(req: HttpRequest, state: web::Data<AppState>) -> impl Responder {
+    if let Err(e) = shutdown::authorize(&req) {
+        return e.error_response();
+    }
+    match state.server.get() {
+        Some(handle) => shutdown::begin(handle),
+        None => return ApiError::Internal("server is not running yet".into()).error_response(),
+    }
+    log::info!("Shutdown requested via /api/admin/shutdown");
+    HttpResponse::Ok().json(serde_json::json!({"message": "shutting down"}))
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    env_logger::init();
-    let state = AppState{ ocr: Arc::new(Mutex::new(None)) };
+    logfile::init();
+    trace::init();
+    let config = config::init().map_err(std::io::Error::other)?;
+    if let Some(path) = &config.source {
+        log::info!("Loaded config from {}", path.display());
+    }
+    settings::init();
+    let state = AppState::from_env();
+
+    #[cfg(feature = "with-ocr")]
+    spawn_memory_watch(state.clone());
 
-    HttpServer::new(move || {
+    // Host and port from OCR_HOST/OCR_PORT or the config (default 127.0.0.1:8081); port 0 lets the OS pick
+    // a free one, as does a preferred port that's already taken. The address actually bound is printed as
+    // "LISTENING_ON=<host>:<port>" so a parent process (the desktop app's sidecar) can discover it from stdout.
+    let preferred_port = config.port;
+    let listener = std::net::TcpListener::bind((config.host.as_str(), preferred_port))
+        .or_else(|_| std::net::TcpListener::bind((config.host.as_str(), 0)))?;
+    println!("LISTENING_ON={}", listener.local_addr()?);
+    if !listener.local_addr()?.ip().is_loopback() && !auth::enabled() {
+        log::warn!("Listening on {} without OCR_API_KEY: anyone who can reach this address can use the OCR endpoints", listener.local_addr()?);
+    }
+
+    let server_handle = state.server.clone();
+    let server = HttpServer::new(move || {
         App::new()
-            .wrap(Logger::default())
+            .wrap(actix_web::middleware::from_fn(auth::require_api_key))
+            .wrap(actix_web::middleware::from_fn(ratelimit::limit))
+            .wrap(actix_web::middleware::from_fn(pretty::pretty_json))
+            .wrap(cors::from_env())
+            .wrap(actix_web::middleware::from_fn(request_id::tag))
+            .wrap(Logger::new(r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T %{X-Request-Id}o"#))
             .app_data(web::Data::new(state.clone()))
+            // base64 images in JSON bodies are far over actix's 2 MB default
+            .app_data(web::JsonConfig::default().limit(JSON_BODY_LIMIT))
             .service(health)
+            .service(prometheus_metrics)
             .service(load_model)
             .service(unload_model)
             .service(model_status)
+            .service(available_models)
+            .service(get_settings)
+            .service(put_settings)
+            .service(stats)
             .service(recognize)
+            .service(recognize_base64)
+            .service(batch)
+            .service(batch_stream)
+            .service(html)
+            .service(crops)
+            .service(transcript)
+            .service(layout_preview)
+            .service(deskew_image)
+            .service(recognize_with_boxes)
+            .service(recognize_boxes)
             .service(draw)
             .service(ocr2text)
+            .service(recognize_text)
+            .service(layout_text)
+            .service(make_searchable_pdf)
+            .service(export_table)
+            .service(hocr_document)
+            .service(autocorrect_result)
+            .service(finalize_result)
+            .service(admin_shutdown)
     })
-    .bind(("127.0.0.1", 8081))?
-    .run()
-    .await
+    .listen(listener)?
+    // Signals are handled by shutdown::on_signals, which drains on SIGINT too
+    .disable_signals()
+    .shutdown_timeout(shutdown::grace_period().as_secs())
+    .run();
+    let _ = server_handle.set(server.handle());
+    shutdown::on_signals(server.handle());
+    server.await
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,61 @@
+// Memory-pressure unloading for small devices: with OCR_MEMORY_LIMIT_MB set, the process RSS is
+// polled every OCR_MEMORY_POLL_SECS (default 5) and, once over the limit with no OCR request in
+// flight, every model is unloaded. The next request that needs one reloads it from its directory.
+//
+// RSS is read from /proc/self/status, so the limit only takes effect on Linux.
+
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub struct MemoryGuard {
+    limit_mb: Option<u64>,
+    poll: Duration,
+    last_action: Mutex<Option<serde_json::Value>>,
+}
+
+impl MemoryGuard {
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().and_then(|v| v.trim().parse::<u64>().ok());
+        MemoryGuard {
+            limit_mb: var("OCR_MEMORY_LIMIT_MB").filter(|&mb| mb > 0),
+            poll: Duration::from_secs(var("OCR_MEMORY_POLL_SECS").unwrap_or(5).max(1)),
+            last_action: Mutex::new(None),
+        }
+    }
+
+    pub fn limit_mb(&self) -> Option<u64> {
+        self.limit_mb
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        self.poll
+    }
+
+    /// Log an auto-unload or reload and keep it as the last action for model_status
+    pub fn record(&self, action: &str, model_ids: &[String]) {
+        let rss = rss_mb();
+        log::info!("memory guard: {} {:?} (rss {:?} MB, limit {:?} MB)", action, model_ids, rss, self.limit_mb);
+        let at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        *self.last_action.lock().unwrap() = Some(serde_json::json!({
+            "action": action,
+            "model_ids": model_ids,
+            "rss_mb": rss,
+            "at": at,
+        }));
+    }
+
+    pub fn status(&self) -> serde_json::Value {
+        serde_json::json!({
+            "limit_mb": self.limit_mb,
+            "rss_mb": rss_mb(),
+            "last_action": *self.last_action.lock().unwrap(),
+        })
+    }
+}
+
+/// Resident set size of this process in MiB, where the platform exposes it
+pub fn rss_mb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kb = status.lines().find_map(|l| l.strip_prefix("VmRSS:"))?.trim().trim_end_matches("kB").trim().parse::<u64>().ok()?;
+    Some(kb / 1024)
+}
@@ -0,0 +1,43 @@
+// Prometheus metrics served at GET /metrics.
+//
+// Counters and the inference histogram are updated by `recognize`; the model-loaded gauge is
+// refreshed from the registry when scraped so it always matches /api/ocr/model_status.
+
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounter, IntGauge, Registry, TextEncoder};
+
+pub struct Metrics {
+    registry: Registry,
+    pub requests: IntCounter,
+    pub failures: IntCounter,
+    // Only observed with native OCR; still exported (empty) without it
+    #[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+    pub inference_seconds: Histogram,
+    pub model_loaded: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let requests = IntCounter::new("ocr_requests_total", "OCR recognize requests received").unwrap();
+        let failures = IntCounter::new("ocr_request_failures_total", "OCR recognize requests answered with an error status").unwrap();
+        let inference_seconds = Histogram::with_opts(
+            HistogramOpts::new("ocr_inference_duration_seconds", "Time spent in the OCR pipeline per recognize request")
+                .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0]),
+        )
+        .unwrap();
+        let model_loaded = IntGauge::new("ocr_model_loaded", "1 when at least one OCR model is loaded, else 0").unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(failures.clone())).unwrap();
+        registry.register(Box::new(inference_seconds.clone())).unwrap();
+        registry.register(Box::new(model_loaded.clone())).unwrap();
+        Metrics { registry, requests, failures, inference_seconds, model_loaded }
+    }
+
+    /// All metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut buf = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+}
@@ -0,0 +1,370 @@
+// Loaded OCR model files plus the pipelines built from them.
+//
+// OAROCR fixes its thresholds when the pipeline is built, so per-request settings are served by
+// building (and keeping) one pipeline per distinct parameter set.
+
+use image::{DynamicImage, GrayImage, RgbImage};
+use image::imageops::{self, FilterType};
+use oar_ocr::core::config::{OrtExecutionProvider, OrtSessionConfig};
+use oar_ocr::core::OrtInfer;
+use oar_ocr::core::traits::StandardPredictor;
+use oar_ocr::predictor::{
+    DocOrientationClassifier, DocOrientationClassifierBuilder, TextDetPredictor, TextDetPredictorBuilder, TextRecPredictor, TextRecPredictorBuilder,
+};
+use oar_ocr::prelude::*;
+use oar_ocr::processors::NormalizeImage;
+use oar_ocr::utils::{Point2f, get_rotate_crop_image};
+use std::sync::{Arc, Mutex};
+
+// Upper bound on pipelines kept alive per model; the oldest variant is dropped first
+const MAX_PIPELINES: usize = 8;
+
+// Detector input sizing (PP-OCR det default: longest side capped at 960, multiples of 32)
+pub const DET_LIMIT_SIDE_LEN: u32 = 960;
+pub const MIN_DET_LIMIT_SIDE_LEN: u32 = 32;
+pub const MAX_DET_LIMIT_SIDE_LEN: u32 = 4000;
+// Side of the blank square used to warm a freshly loaded model
+const WARMUP_SIDE: u32 = 64;
+const DET_SIZE_MULTIPLE: u32 = 32;
+
+/// Size a `width` x `height` page is resized to for detection: longest side shrunk to at most
+/// `limit_side_len` (never enlarged), each side rounded to a multiple of 32
+pub fn det_input_size(width: u32, height: u32, limit_side_len: u32) -> (u32, u32) {
+    let scale = (limit_side_len as f32 / width.max(height) as f32).min(1.0);
+    let round = |v: u32| (((v as f32 * scale) / DET_SIZE_MULTIPLE as f32).round() as u32).max(1) * DET_SIZE_MULTIPLE;
+    (round(width), round(height))
+}
+
+/// Request-level knobs that change how the pipeline is built
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PredictParams {
+    /// DB post-process binarization threshold
+    pub det_db_thresh: f32,
+    /// Minimum confidence for the text line orientation classifier to rotate a line
+    pub cls_thresh: f32,
+    /// Run the text line orientation classifier; when false the stage is skipped entirely
+    pub use_cls: bool,
+    /// Longest side the image is shrunk to before detection; the detector maps boxes back to
+    /// the input size itself
+    pub det_limit_side_len: u32,
+}
+
+// Thresholds from the config's [defaults], else PP-OCR's
+impl Default for PredictParams {
+    fn default() -> Self {
+        // Saved settings over the startup config
+        let config = crate::config::get();
+        let saved = crate::settings::get();
+        PredictParams {
+            det_db_thresh: saved.det_db_thresh.map_or(config.det_db_thresh, |v| v as f32),
+            cls_thresh: saved.cls_thresh.map_or(config.cls_thresh, |v| v as f32),
+            use_cls: saved.use_cls.unwrap_or(config.use_cls),
+            det_limit_side_len: DET_LIMIT_SIDE_LEN,
+        }
+    }
+}
+
+impl PredictParams {
+    // cls_thresh has no effect without the classifier, so don't build a separate pipeline for it
+    fn normalized(mut self) -> Self {
+        if !self.use_cls {
+            self.cls_thresh = PredictParams::default().cls_thresh;
+        }
+        self
+    }
+}
+
+/// ONNX Runtime execution provider, selected with OCR_EP. CUDA and DirectML need the service
+/// built with the matching `cuda` / `directml` feature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExecutionProvider {
+    Cpu,
+    Cuda,
+    DirectMl,
+}
+
+impl ExecutionProvider {
+    /// OCR_EP or the config's `provider` (`cpu`, `cuda`, `directml`), CPU when unset
+    pub fn from_config() -> Result<Self, String> {
+        let v = &crate::config::get().provider;
+        match v.trim().to_ascii_lowercase().as_str() {
+            "" | "cpu" => Ok(ExecutionProvider::Cpu),
+            "cuda" => Ok(ExecutionProvider::Cuda),
+            "directml" | "dml" => Ok(ExecutionProvider::DirectMl),
+            _ => Err(format!("Unsupported OCR_EP '{}': expected cpu, cuda or directml", v)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExecutionProvider::Cpu => "cpu",
+            ExecutionProvider::Cuda => "cuda",
+            ExecutionProvider::DirectMl => "directml",
+        }
+    }
+
+    // Sessions fall back to CPU for any op the accelerator can't run
+    fn session_config(&self) -> Option<OrtSessionConfig> {
+        let accelerator = match self {
+            ExecutionProvider::Cpu => return None,
+            ExecutionProvider::Cuda => OrtExecutionProvider::CUDA {
+                device_id: None,
+                gpu_mem_limit: None,
+                arena_extend_strategy: None,
+                cudnn_conv_algo_search: None,
+                do_copy_in_default_stream: None,
+                cudnn_conv_use_max_workspace: None,
+            },
+            ExecutionProvider::DirectMl => OrtExecutionProvider::DirectML { device_id: None },
+        };
+        Some(OrtSessionConfig::new().with_execution_providers(vec![accelerator, OrtExecutionProvider::CPU]))
+    }
+}
+
+pub struct OcrModel {
+    det: String,
+    rec: String,
+    dict: String,
+    // Text line orientation classifier; `use_cls` is a no-op when the model dir doesn't ship one
+    cls: Option<String>,
+    // Whole-page orientation classifier for `auto_rotate`, when the model dir ships one
+    doc_ori: Option<String>,
+    provider: ExecutionProvider,
+    pipelines: Mutex<Vec<(PredictParams, Arc<OAROCR>)>>,
+    // Bare detection session for the debug probability map, created on first use
+    det_session: Mutex<Option<OrtInfer>>,
+    // Standalone recognizer for caller-supplied boxes, created on first use
+    recognizer: Mutex<Option<Arc<TextRecPredictor>>>,
+    // Standalone detector for pages recognized past their time budget, built for the
+    // (det_db_thresh, det_limit_side_len) it was last asked for
+    detector: Mutex<Option<(f32, u32, Arc<TextDetPredictor>)>>,
+    // Built from `doc_ori` on first use
+    orientation: Mutex<Option<Arc<DocOrientationClassifier>>>,
+}
+
+impl OcrModel {
+    /// Build the default pipeline up front so missing or broken model files fail the load.
+    /// If `provider` can't be initialized the model is loaded on CPU instead; `provider()`
+    /// reports what was actually used.
+    pub fn load(det: String, rec: String, dict: String, cls: Option<String>, doc_ori: Option<String>, provider: ExecutionProvider) -> OcrResult<Self> {
+        let mut model = OcrModel {
+            det,
+            rec,
+            dict,
+            cls,
+            doc_ori,
+            provider,
+            pipelines: Mutex::new(Vec::new()),
+            det_session: Mutex::new(None),
+            recognizer: Mutex::new(None),
+            detector: Mutex::new(None),
+            orientation: Mutex::new(None),
+        };
+        if let Err(e) = model.pipeline(&PredictParams::default()) {
+            if provider == ExecutionProvider::Cpu {
+                return Err(e);
+            }
+            model.provider = ExecutionProvider::Cpu;
+            model.pipeline(&PredictParams::default())?;
+        }
+        Ok(model)
+    }
+
+    pub fn provider(&self) -> ExecutionProvider {
+        self.provider
+    }
+
+    /// One throwaway predict on a blank page through the default pipeline, so ONNX Runtime's
+    /// lazy allocations happen now rather than on the first real request. Blocking.
+    pub fn warm_up(&self) -> OcrResult<()> {
+        let blank = RgbImage::from_pixel(WARMUP_SIDE, WARMUP_SIDE, image::Rgb([255, 255, 255]));
+        self.pipeline(&PredictParams::default())?.predict(&[blank])?;
+        Ok(())
+    }
+
+    /// Pipeline configured for `params`, building it on first use. Blocking.
+    pub fn pipeline(&self, params: &PredictParams) -> OcrResult<Arc<OAROCR>> {
+        let params = params.normalized();
+        if let Some((_, ocr)) = self.pipelines.lock().unwrap().iter().find(|(p, _)| *p == params) {
+            return Ok(ocr.clone());
+        }
+
+        let ocr = Arc::new(self.builder(&params).build()?);
+        let mut pipelines = self.pipelines.lock().unwrap();
+        if pipelines.len() >= MAX_PIPELINES {
+            pipelines.remove(0);
+        }
+        pipelines.push((params, ocr.clone()));
+        Ok(ocr)
+    }
+
+    /// The detector's raw per-pixel text probability, i.e. the map DB post-processing thresholds
+    /// with det_db_thresh before forming boxes, scaled back to the input size. Blocking.
+    pub fn det_prob_map(&self, img: &RgbImage, limit_side_len: u32) -> OcrResult<GrayImage> {
+        let (w, h) = img.dimensions();
+        let (det_w, det_h) = det_input_size(w, h, limit_side_len);
+        let resized = imageops::resize(img, det_w, det_h, FilterType::Triangle);
+        let input = NormalizeImage::new(None, None, None, None)?.normalize_batch_to(vec![DynamicImage::ImageRgb8(resized)])?;
+
+        let mut session = self.det_session.lock().unwrap();
+        if session.is_none() {
+            *session = Some(OrtInfer::with_auto_input_name(&self.det)?);
+        }
+        let output = session.as_ref().unwrap().infer_4d(&input)?;
+
+        // output is [batch, 1, height, width] with values in [0, 1]
+        let shape = output.shape();
+        let (map_h, map_w) = (shape[2] as u32, shape[3] as u32);
+        let map = GrayImage::from_fn(map_w, map_h, |x, y| {
+            image::Luma([(output[[0, 0, y as usize, x as usize]].clamp(0.0, 1.0) * 255.0).round() as u8])
+        });
+        Ok(imageops::resize(&map, w, h, FilterType::Triangle))
+    }
+
+    /// Recognition only: crop each polygon out of `img` and read it, skipping detection.
+    /// Results are (text, score) in the order of `polygons`. Four-point polygons are
+    /// perspective-rectified; anything else is cropped to its bounding rect. Boxes that are
+    /// empty or fall outside the image come back as ("", 0.0). Blocking.
+    pub fn recognize_polygons(&self, img: &RgbImage, polygons: &[Vec<[f32; 2]>]) -> OcrResult<Vec<(String, f32)>> {
+        let crops: Vec<Option<RgbImage>> = polygons.iter().map(|p| crop_polygon(img, p)).collect();
+        let inputs: Vec<RgbImage> = crops.iter().flatten().cloned().collect();
+        if inputs.is_empty() {
+            return Ok(vec![(String::new(), 0.0); polygons.len()]);
+        }
+
+        let mut read = self.recognize_crops(inputs)?.into_iter();
+        Ok(crops
+            .iter()
+            .map(|crop| match crop {
+                Some(_) => read.next().unwrap_or((String::new(), 0.0)),
+                None => (String::new(), 0.0),
+            })
+            .collect())
+    }
+
+    /// Recognition of crops that are already cut out and turned to read left to right, as
+    /// (text, score) in order. Blocking.
+    pub fn recognize_crops(&self, crops: Vec<RgbImage>) -> OcrResult<Vec<(String, f32)>> {
+        if crops.is_empty() {
+            return Ok(Vec::new());
+        }
+        let recognizer = self.recognizer()?;
+        let rec = recognizer.predict(crops, None)?;
+        Ok(rec.rec_text.iter().zip(rec.rec_score.iter()).map(|(t, s)| (t.to_string(), *s)).collect())
+    }
+
+    /// Detection only: the text boxes `params` would find in `img`, without reading them, each with
+    /// the detector's box score. Blocking.
+    pub fn detect(&self, img: &RgbImage, params: &PredictParams) -> OcrResult<Vec<(Vec<[f32; 2]>, f32)>> {
+        let detector = self.detector(params)?;
+        let res = detector.predict(vec![img.clone()], None)?;
+        let scores = res.dt_scores.first();
+        Ok(res
+            .dt_polys
+            .first()
+            .map(|polys| {
+                polys
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| (b.points.iter().map(|p| [p.x, p.y]).collect(), scores.and_then(|s| s.get(i)).copied().unwrap_or(0.0)))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Clockwise degrees (0, 90, 180 or 270) that turn `img` upright, from the document
+    /// orientation classifier. None when the model dir has no classifier. Blocking.
+    pub fn page_orientation(&self, img: &RgbImage) -> OcrResult<Option<u32>> {
+        let Some(path) = &self.doc_ori else { return Ok(None) };
+        let classifier = {
+            let mut orientation = self.orientation.lock().unwrap();
+            match orientation.as_ref() {
+                Some(c) => c.clone(),
+                None => {
+                    let mut builder = DocOrientationClassifierBuilder::new().topk(1);
+                    if let Some(config) = self.provider.session_config() {
+                        builder = builder.ort_session(config);
+                    }
+                    let built = Arc::new(builder.build(std::path::Path::new(path))?);
+                    *orientation = Some(built.clone());
+                    built
+                }
+            }
+        };
+        let result = classifier.predict(vec![img.clone()], None)?;
+        // Labels are the angles themselves ("0", "90", "180", "270")
+        let angle = result.label_names.first().and_then(|l| l.first()).and_then(|l| l.parse::<u32>().ok());
+        Ok(Some(angle.filter(|a| a % 90 == 0 && *a < 360).unwrap_or(0)))
+    }
+
+    fn recognizer(&self) -> OcrResult<Arc<TextRecPredictor>> {
+        let mut recognizer = self.recognizer.lock().unwrap();
+        if let Some(r) = recognizer.as_ref() {
+            return Ok(r.clone());
+        }
+        let dict = std::fs::read_to_string(&self.dict).map_err(|e| OCRError::ConfigError {
+            message: format!("Failed to load character dictionary from {}: {}", self.dict, e),
+        })?;
+        let mut builder = TextRecPredictorBuilder::new().character_dict(dict.lines().map(|l| l.to_string()).collect());
+        if let Some(config) = self.provider.session_config() {
+            builder = builder.ort_session(config);
+        }
+        let built = Arc::new(builder.build(std::path::Path::new(&self.rec))?);
+        *recognizer = Some(built.clone());
+        Ok(built)
+    }
+
+    fn detector(&self, params: &PredictParams) -> OcrResult<Arc<TextDetPredictor>> {
+        let mut detector = self.detector.lock().unwrap();
+        if let Some((thresh, limit, d)) = detector.as_ref()
+            && *thresh == params.det_db_thresh
+            && *limit == params.det_limit_side_len
+        {
+            return Ok(d.clone());
+        }
+        let mut builder = TextDetPredictorBuilder::new().thresh(params.det_db_thresh).limit_side_len(params.det_limit_side_len);
+        if let Some(config) = self.provider.session_config() {
+            builder = builder.ort_session(config);
+        }
+        let built = Arc::new(builder.build(std::path::Path::new(&self.det))?);
+        *detector = Some((params.det_db_thresh, params.det_limit_side_len, built.clone()));
+        Ok(built)
+    }
+
+    fn builder(&self, params: &PredictParams) -> OAROCRBuilder {
+        let builder = OAROCRBuilder::new(self.det.clone(), self.rec.clone(), self.dict.clone())
+            .text_det_threshold(params.det_db_thresh)
+            .text_det_limit_side_len(params.det_limit_side_len);
+        let builder = match &self.cls {
+            Some(cls) if params.use_cls => builder
+                .textline_orientation_classify_model_path(cls)
+                .textline_orientation_threshold(params.cls_thresh)
+                .use_textline_orientation(true),
+            _ => builder.use_textline_orientation(false),
+        };
+        // Applied last so it also reaches the orientation classifier configured above
+        match self.provider.session_config() {
+            Some(config) => builder.global_ort_session(config),
+            None => builder,
+        }
+    }
+}
+
+/// Image region for one polygon, None when it has no usable area. Four-point polygons are
+/// warped to an upright rectangle (tall results are turned to read left to right); others are
+/// cropped to their bounding rect.
+pub fn crop_polygon(img: &RgbImage, polygon: &[[f32; 2]]) -> Option<RgbImage> {
+    if polygon.len() == 4 {
+        let points: Vec<Point2f> = polygon.iter().map(|p| Point2f::new(p[0], p[1])).collect();
+        return get_rotate_crop_image(img, &points).ok().filter(|c| c.width() > 0 && c.height() > 0);
+    }
+    let (w, h) = img.dimensions();
+    let x0 = polygon.iter().map(|p| p[0]).fold(f32::MAX, f32::min).max(0.0) as u32;
+    let y0 = polygon.iter().map(|p| p[1]).fold(f32::MAX, f32::min).max(0.0) as u32;
+    let x1 = (polygon.iter().map(|p| p[0]).fold(f32::MIN, f32::max).max(0.0) as u32).min(w);
+    let y1 = (polygon.iter().map(|p| p[1]).fold(f32::MIN, f32::max).max(0.0) as u32).min(h);
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+    Some(imageops::crop_imm(img, x0, y0, x1 - x0, y1 - y0).to_image())
+}
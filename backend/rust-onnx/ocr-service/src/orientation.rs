@@ -0,0 +1,34 @@
+// Coarse page orientation for `auto_rotate` when the model dir has no document orientation
+// classifier: text lines run across the page, so detected boxes that are mostly taller than
+// wide mean a quarter turn, and between a rotation and its upside-down twin the one that reads
+// with more confidence wins.
+
+use crate::region::Region;
+use image::{RgbImage, imageops};
+
+/// `img` turned clockwise by `degrees` (0, 90, 180 or 270)
+pub fn rotate(img: RgbImage, degrees: u32) -> RgbImage {
+    match degrees {
+        90 => imageops::rotate90(&img),
+        180 => imageops::rotate180(&img),
+        270 => imageops::rotate270(&img),
+        _ => img,
+    }
+}
+
+/// True when most boxes are taller than wide, i.e. the lines run down the page
+pub fn looks_sideways(regions: &[Region]) -> bool {
+    let tall = regions
+        .iter()
+        .filter(|r| {
+            let (x0, y0, x1, y1) = r.bounds();
+            y1 - y0 > x1 - x0
+        })
+        .count();
+    tall * 2 > regions.len()
+}
+
+/// Confidence-weighted character count; the correctly oriented page scores highest
+pub fn reading_score(regions: &[Region]) -> f32 {
+    regions.iter().map(|r| r.score * r.text.chars().count() as f32).sum()
+}
@@ -0,0 +1,56 @@
+// PDF input for recognize: rasterize every page with pdfium.
+//
+// pdfium is bound at runtime, first from next to the working directory (where the desktop bundle
+// ships it) and then from the system library path, so the service still starts without it and
+// only PDF uploads fail.
+
+use image::RgbImage;
+use pdfium_render::prelude::*;
+use std::sync::Mutex;
+
+// pdfium is not re-entrant; render one document at a time
+static PDFIUM_LOCK: Mutex<()> = Mutex::new(());
+
+pub enum PdfError {
+    // The upload is not a usable PDF (client error)
+    Invalid(String),
+    // pdfium itself is unavailable or failed (server error)
+    Backend(String),
+}
+
+pub fn is_pdf(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"%PDF")
+}
+
+/// Render each page to RGB at `dpi`. Blocking.
+pub fn render_pages(bytes: &[u8], dpi: f32) -> Result<Vec<RgbImage>, PdfError> {
+    let _guard = PDFIUM_LOCK.lock().unwrap();
+    let bindings = Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./"))
+        .or_else(|_| Pdfium::bind_to_system_library())
+        .map_err(|e| PdfError::Backend(format!("PDF support unavailable, pdfium library not found: {}", e)))?;
+    let pdfium = Pdfium::new(bindings);
+
+    let document = pdfium.load_pdf_from_byte_slice(bytes, None).map_err(|e| match e {
+        PdfiumError::PdfiumLibraryInternalError(PdfiumInternalError::PasswordError) => {
+            PdfError::Invalid("PDF is encrypted".into())
+        }
+        e => PdfError::Invalid(format!("Failed to open PDF: {}", e)),
+    })?;
+
+    let pages = document.pages();
+    if pages.is_empty() {
+        return Err(PdfError::Invalid("PDF has no pages".into()));
+    }
+
+    // PDF user space is 72 units per inch
+    let config = PdfRenderConfig::new().scale_page_by_factor(dpi / 72.0);
+    pages
+        .iter()
+        .enumerate()
+        .map(|(i, page)| {
+            page.render_with_config(&config)
+                .map(|bitmap| bitmap.as_image().to_rgb8())
+                .map_err(|e| PdfError::Invalid(format!("Failed to render PDF page {}: {}", i + 1, e)))
+        })
+        .collect()
+}
@@ -0,0 +1,202 @@
+// Pool of independently loaded models so concurrent requests don't share one pipeline.
+//
+// Each request checks a model out for the duration of its OCR work and hands it back when the
+// checkout is dropped; when every model is busy, callers wait on the semaphore instead of piling
+// onto the same instance.
+
+use crate::model::{ExecutionProvider, OcrModel};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Generic over the model only so tests can pool stand-ins; the service pools `OcrModel`s
+pub struct OcrPool<M = OcrModel> {
+    idle: Mutex<Vec<Arc<M>>>,
+    permits: Arc<Semaphore>,
+    size: usize,
+    provider: ExecutionProvider,
+    info: LoadInfo,
+}
+
+/// Where to load a model from: its directory plus the det/rec/dict files, each relative to `dir`
+/// unless absolute
+#[derive(Clone)]
+pub struct ModelSource {
+    pub dir: String,
+    pub det: String,
+    pub rec: String,
+    pub dict: String,
+}
+
+impl ModelSource {
+    /// Full path of `file` (one of det/rec/dict)
+    pub fn path(&self, file: &str) -> String {
+        std::path::Path::new(&self.dir).join(file).to_string_lossy().into_owned()
+    }
+}
+
+/// Where a pool's models came from and when, for model_status
+pub struct LoadInfo {
+    pub model_dir: String,
+    /// Model file names as configured, relative to `model_dir` unless absolute
+    pub det: String,
+    pub rec: String,
+    pub dict: String,
+    pub loaded_at: SystemTime,
+    /// Time to build every worker's model
+    pub load_time: Duration,
+    /// Time spent warming the models after building them; None when OCR_SKIP_WARMUP is set
+    pub warmup_time: Option<Duration>,
+    /// The model dir has a document orientation classifier, which auto_rotate then uses
+    pub orientation_classifier: bool,
+}
+
+impl OcrPool {
+    /// `models` must be non-empty and all loaded on the same execution provider
+    pub fn new(models: Vec<OcrModel>, info: LoadInfo) -> Self {
+        let provider = models[0].provider();
+        OcrPool::with_provider(models, provider, info)
+    }
+
+    /// Pool size from OCR_WORKERS or the config file, 1 by default
+    pub fn configured_workers() -> usize {
+        crate::config::get().workers
+    }
+}
+
+impl<M> OcrPool<M> {
+    /// `models` must be non-empty
+    pub fn with_provider(models: Vec<M>, provider: ExecutionProvider, info: LoadInfo) -> Self {
+        let size = models.len();
+        OcrPool {
+            idle: Mutex::new(models.into_iter().map(Arc::new).collect()),
+            permits: Arc::new(Semaphore::new(size)),
+            size,
+            provider,
+            info,
+        }
+    }
+
+    pub fn info(&self) -> &LoadInfo {
+        &self.info
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn provider(&self) -> ExecutionProvider {
+        self.provider
+    }
+
+    /// Wait for an idle model and check it out
+    pub async fn acquire(self: &Arc<Self>) -> PooledModel<M> {
+        // The semaphore is never closed, so acquiring can't fail
+        let permit = self.permits.clone().acquire_owned().await.unwrap();
+        let model = self.idle.lock().unwrap().pop().expect("a permit guarantees an idle model");
+        PooledModel { pool: self.clone(), model: Some(model), _permit: permit }
+    }
+}
+
+/// A checked-out model; returned to the pool on drop
+pub struct PooledModel<M = OcrModel> {
+    pool: Arc<OcrPool<M>>,
+    model: Option<Arc<M>>,
+    // Released after Drop has put the model back in `idle`
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<M> PooledModel<M> {
+    /// Handle to move into a blocking task; keep `self` alive until that task finishes
+    pub fn model(&self) -> Arc<M> {
+        self.model.as_ref().unwrap().clone()
+    }
+}
+
+impl<M> Drop for PooledModel<M> {
+    fn drop(&mut self) {
+        if let Some(model) = self.model.take() {
+            self.pool.idle.lock().unwrap().push(model);
+        }
+    }
+}
+
+/// Models by id, in load order; the most recently loaded one is the default. Models unloaded
+/// under memory pressure stay in their place with their source but no pool, so they can be
+/// reloaded on the next request that needs them without changing which model is the default.
+#[derive(Default)]
+pub struct ModelRegistry {
+    models: Vec<(String, ModelSource, Option<Arc<OcrPool>>)>,
+}
+
+/// What a request's model choice resolves to
+pub enum Resolved {
+    Loaded(Arc<OcrPool>),
+    // Unloaded under memory pressure; reload from (id, source)
+    Parked(String, ModelSource),
+    Unknown,
+    Empty,
+}
+
+impl ModelRegistry {
+    /// Add or replace `id` (loaded from `source`), making it the default
+    pub fn insert(&mut self, id: String, source: ModelSource, pool: Arc<OcrPool>) {
+        self.models.retain(|(known, _, _)| *known != id);
+        self.models.push((id, source, Some(pool)));
+    }
+
+    /// Put a reloaded pool back for parked `id`, keeping its place in the load order; false if
+    /// `id` isn't parked (unloaded meanwhile, or already reloaded)
+    pub fn restore(&mut self, id: &str, pool: Arc<OcrPool>) -> bool {
+        match self.models.iter_mut().find(|(known, _, slot)| known == id && slot.is_none()) {
+            Some((_, _, slot)) => {
+                *slot = Some(pool);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop one model, loaded or parked; false if `id` wasn't known
+    pub fn remove(&mut self, id: &str) -> bool {
+        let before = self.models.len();
+        self.models.retain(|(known, _, _)| known != id);
+        self.models.len() != before
+    }
+
+    pub fn clear(&mut self) {
+        self.models.clear();
+    }
+
+    /// The most recently loaded model, None while it's parked
+    pub fn default_model(&self) -> Option<(&str, Arc<OcrPool>)> {
+        self.models.last().and_then(|(id, _, pool)| Some((id.as_str(), pool.clone()?)))
+    }
+
+    /// `id`, or the default when None
+    pub fn resolve(&self, id: Option<&str>) -> Resolved {
+        let entry = match id {
+            Some(id) => self.models.iter().find(|(known, _, _)| known == id),
+            None => self.models.last(),
+        };
+        match entry {
+            Some((_, _, Some(pool))) => Resolved::Loaded(pool.clone()),
+            Some((id, source, None)) => Resolved::Parked(id.clone(), source.clone()),
+            None if id.is_some() => Resolved::Unknown,
+            None => Resolved::Empty,
+        }
+    }
+
+    /// Unload every model, remembering where each came from; returns the parked ids
+    pub fn park_all(&mut self) -> Vec<String> {
+        self.models.iter_mut().filter_map(|(id, _, pool)| pool.take().map(|_| id.clone())).collect()
+    }
+
+    pub fn parked_ids(&self) -> impl Iterator<Item = &str> {
+        self.models.iter().filter(|(_, _, pool)| pool.is_none()).map(|(id, _, _)| id.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Arc<OcrPool>)> {
+        self.models.iter().filter_map(|(id, _, pool)| Some((id.as_str(), pool.as_ref()?)))
+    }
+}
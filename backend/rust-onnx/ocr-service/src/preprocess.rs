@@ -0,0 +1,53 @@
+// Optional clean-up of the decoded page before recognize runs OCR on it, applied in this order:
+// downscale to `max_side`, grayscale, contrast. Boxes found on the downscaled page are mapped back
+// with `Region::scaled(1.0 / factor)` so they line up with the uploaded image.
+
+use image::{Rgb, RgbImage, imageops};
+use image::imageops::FilterType;
+
+pub const MIN_SIDE: u32 = 32;
+pub const MAX_CONTRAST: f32 = 10.0;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Preprocess {
+    /// Longest side after downscaling; larger pages are shrunk keeping their aspect ratio
+    pub max_side: Option<u32>,
+    pub grayscale: bool,
+    /// Contrast factor around mid-gray; 1.0 leaves the page unchanged
+    pub contrast: Option<f32>,
+}
+
+impl Preprocess {
+    pub fn is_noop(&self) -> bool {
+        self.max_side.is_none() && !self.grayscale && self.contrast.is_none_or(|c| c == 1.0)
+    }
+
+    /// The processed page and the factor it was scaled by (1.0 when not downscaled)
+    pub fn apply(&self, img: RgbImage) -> (RgbImage, f32) {
+        let (w, h) = img.dimensions();
+        let longest = w.max(h);
+        let (mut img, factor) = match self.max_side {
+            Some(max_side) if longest > max_side => {
+                let factor = max_side as f32 / longest as f32;
+                let (nw, nh) = (((w as f32 * factor).round() as u32).max(1), ((h as f32 * factor).round() as u32).max(1));
+                (imageops::resize(&img, nw, nh, FilterType::Triangle), factor)
+            }
+            _ => (img, 1.0),
+        };
+        if self.grayscale {
+            let gray = imageops::grayscale(&img);
+            img = RgbImage::from_fn(img.width(), img.height(), |x, y| {
+                let v = gray.get_pixel(x, y)[0];
+                Rgb([v, v, v])
+            });
+        }
+        if let Some(c) = self.contrast.filter(|&c| c != 1.0) {
+            for p in img.pixels_mut() {
+                for v in p.0.iter_mut() {
+                    *v = ((*v as f32 - 128.0) * c + 128.0).round().clamp(0.0, 255.0) as u8;
+                }
+            }
+        }
+        (img, factor)
+    }
+}
@@ -0,0 +1,36 @@
+// `?pretty=true` on any JSON endpoint: the body is re-serialized with indentation for people
+// reading responses or saving them to files. Machine clients keep getting compact JSON.
+
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use actix_web::middleware::Next;
+
+pub async fn pretty_json(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let wanted = wants_pretty(req.query_string());
+    let res = next.call(req).await?;
+    let is_json = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !wanted || !is_json {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let bytes = body::to_bytes(body).await.map_err(|e| actix_web::error::ErrorInternalServerError(e.into()))?;
+    // Anything that doesn't parse goes out as it came
+    let pretty = serde_json::from_slice::<serde_json::Value>(&bytes)
+        .ok()
+        .and_then(|v| serde_json::to_vec_pretty(&v).ok())
+        .unwrap_or_else(|| bytes.to_vec());
+    res.headers_mut().remove(CONTENT_LENGTH);
+    Ok(ServiceResponse::new(req, res.set_body(pretty).map_into_boxed_body()))
+}
+
+// `pretty`, `pretty=true` or `pretty=1` among the query parameters
+fn wants_pretty(query: &str) -> bool {
+    query.split('&').any(|pair| matches!(pair, "pretty" | "pretty=true" | "pretty=1"))
+}
@@ -0,0 +1,76 @@
+// Anonymized layout thumbnail: where the text is, drawn as filled boxes, without any of the text
+// itself, for sharing a document's layout. Boxes are painted over a flat color or a heavy blur of
+// the page; the blur is strong enough that only large shapes and colors survive.
+
+use crate::region::Region;
+use image::{Rgb, RgbImage, imageops};
+use imageproc::drawing::{draw_filled_rect_mut, draw_polygon_mut};
+use imageproc::point::Point;
+use imageproc::rect::Rect;
+
+pub const DEFAULT_MAX_SIDE: u32 = 512;
+pub const DEFAULT_FILL: [u8; 3] = [128, 128, 128];
+
+pub enum Background {
+    Color([u8; 3]),
+    Blur,
+}
+
+impl Background {
+    /// "blank" (white), "blur", or a "#rrggbb" color
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "blank" => Some(Background::Color([255, 255, 255])),
+            "blur" => Some(Background::Blur),
+            other => parse_hex(other).map(Background::Color),
+        }
+    }
+}
+
+/// "#rrggbb" or "rrggbb"
+pub fn parse_hex(value: &str) -> Option<[u8; 3]> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// The page shrunk so its longest side is at most `max_side`, with every region filled in `fill`
+pub fn render(img: &RgbImage, regions: &[Region], fill: [u8; 3], background: &Background, max_side: u32) -> RgbImage {
+    let (w, h) = img.dimensions();
+    let scale = (max_side as f32 / w.max(h) as f32).min(1.0);
+    let (tw, th) = (((w as f32 * scale).round() as u32).max(1), ((h as f32 * scale).round() as u32).max(1));
+
+    let mut canvas = match background {
+        Background::Color(color) => RgbImage::from_pixel(tw, th, Rgb(*color)),
+        Background::Blur => {
+            let thumb = imageops::resize(img, tw, th, imageops::FilterType::Triangle);
+            // Relative to the thumbnail so the result looks the same at any size
+            imageops::blur(&thumb, tw.max(th) as f32 / 40.0)
+        }
+    };
+
+    for region in regions.iter() {
+        let mut points: Vec<Point<i32>> = region
+            .points
+            .iter()
+            .map(|p| Point::new((p[0] * scale).round() as i32, (p[1] * scale).round() as i32))
+            .collect();
+        points.dedup();
+        if points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+        if points.len() >= 3 {
+            draw_polygon_mut(&mut canvas, &points, Rgb(fill));
+        } else {
+            // Degenerate after scaling: still mark the spot
+            let (x0, y0, x1, y1) = region.bounds();
+            let (x, y) = ((x0 * scale) as i32, (y0 * scale) as i32);
+            let rect = Rect::at(x, y).of_size((((x1 - x0) * scale) as u32).max(1), (((y1 - y0) * scale) as u32).max(1));
+            draw_filled_rect_mut(&mut canvas, rect, Rgb(fill));
+        }
+    }
+    canvas
+}
@@ -0,0 +1,87 @@
+// Per-client rate limit on the OCR endpoints, so a frontend stuck in a retry loop can't starve
+// the machine. OCR_RATE_LIMIT is the sustained requests per second each client IP may make
+// (fractions allowed; unset or 0 disables the limit), with bursts of up to that many at once
+// (at least one). Keyed by IP alone, so local callers on different ports share one bucket.
+// Health, metrics and admin endpoints aren't limited.
+
+use crate::error::ApiError;
+use actix_web::ResponseError;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+const LIMITED_PREFIX: &str = "/api/ocr/";
+
+// Past this many tracked clients, those whose bucket has refilled are forgotten
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+pub struct Limiter {
+    // Tokens added per second, and the bucket size
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Limiter {
+    /// `rate` requests per second per client, in bursts of up to `rate` (at least one)
+    pub fn new(rate: f64) -> Self {
+        Limiter { rate, burst: rate.max(1.0), buckets: Mutex::default() }
+    }
+
+    // Take a token for `client`, or say how many seconds until one is available
+    fn take(&self, client: IpAddr) -> Result<(), u64> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            buckets.retain(|_, b| b.tokens + now.duration_since(b.updated).as_secs_f64() * self.rate < self.burst);
+        }
+        let bucket = buckets.entry(client).or_insert(Bucket { tokens: self.burst, updated: now });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * self.rate).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / self.rate).ceil().max(1.0) as u64)
+        }
+    }
+}
+
+pub async fn limit(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    limit_with(limiter(), req, next).await
+}
+
+/// `limit` under `limiter` instead of OCR_RATE_LIMIT's; None lets everything through
+pub async fn limit_with(limiter: Option<&Limiter>, req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let exempt = !req.path().starts_with(LIMITED_PREFIX) || req.method() == Method::OPTIONS;
+    if let Some(limiter) = limiter
+        && !exempt
+        && let Some(addr) = req.peer_addr()
+        && let Err(retry_after) = limiter.take(addr.ip())
+    {
+        let res = ApiError::RateLimited(retry_after).error_response();
+        return Ok(req.into_response(res));
+    }
+    Ok(next.call(req).await?.map_into_boxed_body())
+}
+
+// OCR_RATE_LIMIT, read once; unset, 0 or invalid disables limiting
+fn limiter() -> Option<&'static Limiter> {
+    static LIMITER: OnceLock<Option<Limiter>> = OnceLock::new();
+    LIMITER
+        .get_or_init(|| {
+            let rate = std::env::var("OCR_RATE_LIMIT").ok().and_then(|v| v.trim().parse::<f64>().ok()).filter(|r| r.is_finite() && *r > 0.0)?;
+            Some(Limiter::new(rate))
+        })
+        .as_ref()
+}
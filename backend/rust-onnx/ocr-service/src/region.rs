@@ -0,0 +1,114 @@
+// Shape-independent view of one recognized text line.
+//
+// Handlers convert OAROCR output (or a posted Python-format result) into `Region`s so the
+// renderers and exporters don't each have to walk the nested `[box_points, [text, score]]` arrays.
+
+use oar_ocr::prelude::TextRegion;
+
+#[derive(Clone, Debug)]
+pub struct Region {
+    pub points: Vec<[f32; 2]>,
+    pub text: String,
+    pub score: f32,
+}
+
+impl Region {
+    /// Python-compatible line: `[box_points, [text, score]]`
+    pub fn to_legacy(&self) -> serde_json::Value {
+        let box_points: Vec<Vec<f32>> = self.points.iter().map(|p| vec![p[0], p[1]]).collect();
+        serde_json::json!([box_points, [self.text, self.score]])
+    }
+
+    /// Python-compatible line with `normalize_text` applied to the text. With `with_raw`, a third
+    /// element `{"raw_text": ...}` carries the recognized text when normalization changed it.
+    pub fn to_legacy_normalized(&self, with_raw: bool) -> serde_json::Value {
+        let text = normalize_text(&self.text);
+        let box_points: Vec<Vec<f32>> = self.points.iter().map(|p| vec![p[0], p[1]]).collect();
+        if with_raw && text != self.text {
+            serde_json::json!([box_points, [text, self.score], {"raw_text": self.text}])
+        } else {
+            serde_json::json!([box_points, [text, self.score]])
+        }
+    }
+
+    /// Same line with every point multiplied by `factor`, e.g. to map back from a resized image
+    pub fn scaled(mut self, factor: f32) -> Self {
+        for p in self.points.iter_mut() {
+            p[0] *= factor;
+            p[1] *= factor;
+        }
+        self
+    }
+
+    /// Clamp every point into [0, width] x [0, height]
+    pub fn clip_to(&mut self, width: u32, height: u32) {
+        for p in self.points.iter_mut() {
+            p[0] = p[0].clamp(0.0, width as f32);
+            p[1] = p[1].clamp(0.0, height as f32);
+        }
+    }
+
+    /// Axis-aligned bounds as (x_min, y_min, x_max, y_max)
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        let mut b = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        for p in self.points.iter() {
+            b.0 = b.0.min(p[0]);
+            b.1 = b.1.min(p[1]);
+            b.2 = b.2.max(p[0]);
+            b.3 = b.3.max(p[1]);
+        }
+        if self.points.is_empty() { (0.0, 0.0, 0.0, 0.0) } else { b }
+    }
+}
+
+/// Text for indexing/search: full-width ASCII and the ideographic space folded to their ASCII
+/// forms, whitespace runs collapsed to one space, and the ends trimmed.
+pub fn normalize_text(text: &str) -> String {
+    let folded: String = text
+        .chars()
+        .map(|c| match c {
+            '\u{3000}' => ' ',
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => c,
+        })
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Box centers are snapped to this many pixels before hashing, absorbing detector jitter
+const ID_GRID: f32 = 16.0;
+
+/// Deterministic ids for the lines of one page, for matching boxes across re-runs: 16 hex digits
+/// of blake3 over the page index, the box center snapped to a 16px grid and the normalized text.
+/// A line keeps its id while its text (after normalize_text) stays the same and its center stays
+/// in the same grid cell; any text edit, a move across a cell boundary or a changed page index gives
+/// a new id. Lines that would share an id get "-2", "-3", ... in reading order.
+pub fn stable_ids(page: usize, regions: &[Region]) -> Vec<String> {
+    let mut seen: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    regions
+        .iter()
+        .map(|r| {
+            let (x0, y0, x1, y1) = r.bounds();
+            let cell = (((x0 + x1) / 2.0 / ID_GRID).floor() as i64, ((y0 + y1) / 2.0 / ID_GRID).floor() as i64);
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(&(page as u64).to_le_bytes());
+            hasher.update(&cell.0.to_le_bytes());
+            hasher.update(&cell.1.to_le_bytes());
+            hasher.update(normalize_text(&r.text).as_bytes());
+            let id: String = hasher.finalize().as_bytes()[..8].iter().map(|b| format!("{:02x}", b)).collect();
+            let count = seen.entry(id.clone()).or_insert(0);
+            *count += 1;
+            if *count == 1 { id } else { format!("{}-{}", id, count) }
+        })
+        .collect()
+}
+
+impl From<&TextRegion> for Region {
+    fn from(region: &TextRegion) -> Self {
+        Region {
+            points: region.bounding_box.points.iter().map(|p| [p.x, p.y]).collect(),
+            text: region.text.as_ref().map(|s| s.to_string()).unwrap_or_default(),
+            score: region.confidence.unwrap_or(0.0),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+// Request ids, so a user's error report can be traced through the logs. Every request gets one,
+// returned as `X-Request-Id`; an incoming `X-Request-Id` (from a proxy, or a client retrying) is
+// kept when it's a plain token of up to 64 characters. The request runs inside a `request` span
+// carrying the id, parent of the stage spans OCR_TRACE prints, and the access log line ends with
+// it. Error responses are logged with the id, their code and message.
+
+use crate::error::ErrorDetail;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::Instrument;
+
+const HEADER: &str = "X-Request-Id";
+const MAX_LEN: usize = 64;
+
+pub async fn tag(req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let id = req
+        .headers()
+        .get(HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| is_token(v))
+        .map(str::to_string)
+        .unwrap_or_else(generate);
+    let span = tracing::info_span!("request", request_id = %id, method = %req.method(), path = %req.path());
+    let mut res = next.call(req).instrument(span.clone()).await?.map_into_boxed_body();
+
+    if let Some(error) = res.response().extensions().get::<ErrorDetail>() {
+        let status = res.status();
+        let _entered = span.enter();
+        // The id is repeated on the event for plain log output, which doesn't show span fields
+        if status.is_server_error() {
+            tracing::error!(request_id = %id, status = status.as_u16(), code = error.code, message = %error.message, "request failed");
+        } else {
+            tracing::info!(request_id = %id, status = status.as_u16(), code = error.code, message = %error.message, "request refused");
+        }
+    }
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut().insert(HeaderName::from_static("x-request-id"), value);
+    }
+    Ok(res)
+}
+
+// Letters, digits, '-', '_' and '.', as proxies' request ids are
+fn is_token(value: &str) -> bool {
+    !value.is_empty() && value.len() <= MAX_LEN && value.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
+// 16 hex digits, unique within the process and unlikely to repeat across restarts
+fn generate() -> String {
+    static SEED: OnceLock<[u8; 16]> = OnceLock::new();
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let seed = SEED.get_or_init(|| {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos();
+        let mut seed = [0u8; 16];
+        seed.copy_from_slice(&blake3::hash(&[now.to_le_bytes(), (std::process::id() as u128).to_le_bytes()].concat()).as_bytes()[..16]);
+        seed
+    });
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(seed);
+    hasher.update(&COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    hasher.finalize().to_hex()[..16].to_string()
+}
@@ -0,0 +1,80 @@
+// In-memory LRU of recognize responses, keyed by the image content and every option that shapes
+// the result, so re-running the same image with the same settings skips OCR entirely.
+//
+// Requests that don't name a model are keyed on "the default model", so the whole cache is
+// cleared whenever models are loaded or unloaded.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    image: [u8; 32],
+    // Every form field except the image itself, trimmed, in name order
+    options: Vec<(String, String)>,
+}
+
+impl CacheKey {
+    pub fn new(image: &[u8], options: &BTreeMap<String, String>) -> Self {
+        CacheKey {
+            image: *blake3::hash(image).as_bytes(),
+            options: options.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+        }
+    }
+}
+
+pub struct ResultCache {
+    capacity: usize,
+    // Entries plus their keys from least to most recently used
+    inner: Mutex<(HashMap<CacheKey, serde_json::Value>, VecDeque<CacheKey>)>,
+}
+
+impl ResultCache {
+    pub fn new(capacity: usize) -> Self {
+        ResultCache { capacity, inner: Mutex::new((HashMap::new(), VecDeque::new())) }
+    }
+
+    /// Size from OCR_CACHE_SIZE (entries), 32 by default; 0 disables caching
+    pub fn from_env() -> Self {
+        let size = std::env::var("OCR_CACHE_SIZE").ok().and_then(|v| v.trim().parse::<usize>().ok()).unwrap_or(32);
+        ResultCache::new(size)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<serde_json::Value> {
+        let mut inner = self.inner.lock().unwrap();
+        let (entries, order) = &mut *inner;
+        let value = entries.get(key)?.clone();
+        if let Some(pos) = order.iter().position(|k| k == key) {
+            let k = order.remove(pos).unwrap();
+            order.push_back(k);
+        }
+        Some(value)
+    }
+
+    pub fn put(&self, key: CacheKey, value: serde_json::Value) {
+        if !self.enabled() {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let (entries, order) = &mut *inner;
+        if entries.insert(key.clone(), value).is_some() {
+            order.retain(|k| *k != key);
+        }
+        order.push_back(key);
+        while order.len() > self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                entries.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.0.clear();
+        inner.1.clear();
+    }
+}
@@ -0,0 +1,97 @@
+// Result schema v2: `{"regions": [{"box": [[x, y], ...], "text", "score"}]}`, one object per line
+// instead of the positional `[box_points, [text, score]]` arrays of the Python-compatible format.
+// recognize answers in it on request; the endpoints that read a posted result accept either.
+
+use crate::error::ApiError;
+use actix_web::HttpRequest;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+
+pub const MEDIA_TYPE: &str = "application/vnd.ocr.v2+json";
+
+/// Whether the client asked for v2, by `Accept: application/vnd.ocr.v2+json` or `?schema=v2`.
+/// The query wins over the header; `?schema=v1` forces the legacy format.
+#[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+pub fn wants_v2(req: &HttpRequest) -> Result<bool, ApiError> {
+    let query = req.query_string().split('&').find_map(|pair| pair.strip_prefix("schema="));
+    match query {
+        Some("v2") => return Ok(true),
+        Some("v1") => return Ok(false),
+        Some(other) => return Err(ApiError::InvalidField(format!("Invalid 'schema': expected 'v1' or 'v2', got '{}'", other))),
+        None => {}
+    }
+    let accept = req.headers().get("Accept").and_then(|v| v.to_str().ok()).unwrap_or("");
+    Ok(accept.split(',').any(|media| media.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case(MEDIA_TYPE)))
+}
+
+/// v2 region for a legacy line. Anything in the line's trailing object (`id`, `raw_text`,
+/// `style`, ...) becomes a field of the region; `page` (1-based) is added when given.
+#[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+pub fn region(line: &Value, page: Option<usize>) -> Value {
+    let mut region = Map::new();
+    region.insert("box".into(), line.get(0).cloned().unwrap_or(Value::Array(Vec::new())));
+    region.insert("text".into(), line.get(1).and_then(|t| t.get(0)).cloned().unwrap_or(Value::from("")));
+    region.insert("score".into(), line.get(1).and_then(|t| t.get(1)).cloned().unwrap_or(Value::Null));
+    if let Some(Value::Object(extra)) = line.get(2) {
+        for (key, value) in extra.iter() {
+            region.insert(key.clone(), value.clone());
+        }
+    }
+    if let Some(page) = page {
+        region.insert("page".into(), Value::from(page));
+    }
+    Value::Object(region)
+}
+
+/// `body` with a v2 `regions` list turned into the legacy `result` the readers walk: a single
+/// page as `[lines]`, several (regions carrying `page`) as `[{"page": n, "result": [lines]}]`.
+/// Bodies without `regions`, or that also have `result`, come back unchanged.
+pub fn to_legacy(body: &Value) -> Result<Cow<'_, Value>, ApiError> {
+    let Some(regions) = body.get("regions") else { return Ok(Cow::Borrowed(body)) };
+    if body.get("result").is_some() {
+        return Ok(Cow::Borrowed(body));
+    }
+    let Some(regions) = regions.as_array() else {
+        return Err(ApiError::InvalidField("Invalid OCR result format - 'regions' should be an array".into()));
+    };
+
+    let mut pages: Vec<(u64, Vec<Value>)> = Vec::new();
+    for (i, region) in regions.iter().enumerate() {
+        let Some(fields) = region.as_object() else {
+            return Err(ApiError::InvalidField(format!("Invalid OCR result format - region {} should be an object", i)));
+        };
+        let Some(points) = fields.get("box").filter(|b| b.is_array()) else {
+            return Err(ApiError::InvalidField(format!("Invalid OCR result format - region {} has no 'box'", i)));
+        };
+        let text = fields.get("text").cloned().unwrap_or(Value::from(""));
+        let mut line = vec![points.clone(), match fields.get("score") {
+            Some(score) if !score.is_null() => Value::Array(vec![text, score.clone()]),
+            _ => Value::Array(vec![text]),
+        }];
+        let extra: Map<String, Value> = fields
+            .iter()
+            .filter(|(key, _)| !matches!(key.as_str(), "box" | "text" | "score" | "page"))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        if !extra.is_empty() {
+            line.push(Value::Object(extra));
+        }
+
+        let page = fields.get("page").and_then(|p| p.as_u64()).unwrap_or(1);
+        match pages.iter_mut().find(|(number, _)| *number == page) {
+            Some((_, lines)) => lines.push(Value::Array(line)),
+            None => pages.push((page, vec![Value::Array(line)])),
+        }
+    }
+
+    pages.sort_by_key(|(number, _)| *number);
+    let result = match pages.len() {
+        0 => Value::Array(Vec::new()),
+        1 => Value::Array(vec![Value::Array(pages.pop().unwrap().1)]),
+        _ => pages.into_iter().map(|(page, lines)| serde_json::json!({"page": page, "result": [lines]})).collect(),
+    };
+    let mut legacy = body.as_object().cloned().unwrap_or_default();
+    legacy.remove("regions");
+    legacy.insert("result".into(), result);
+    Ok(Cow::Owned(Value::Object(legacy)))
+}
@@ -0,0 +1,231 @@
+// Searchable PDF: the scanned image as the visible page with the recognized text laid over each
+// box as invisible (render mode 3) text, so viewers can select, search and copy it.
+//
+// One image pixel is one PDF point. Text uses the label font embedded whole as a CID font with
+// one CID per distinct character (mapped to its glyph by CIDToGIDMap) and a ToUnicode map, so
+// copied text comes out right even for characters the font has no glyph for.
+
+use ab_glyph::{Font, FontVec};
+use image::RgbImage;
+use image::codecs::jpeg::JpegEncoder;
+use lopdf::{Dictionary, Document, Object, Stream, dictionary};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+// JPEG quality of the page image; scans stay readable at a fraction of the raw size
+const IMAGE_QUALITY: u8 = 90;
+
+/// One line to place: box corners in image pixels (clockwise from top-left) and its text
+pub struct Line {
+    pub points: Vec<[f32; 2]>,
+    pub text: String,
+}
+
+/// Single-page PDF of `img` with `lines` as invisible text
+pub fn build(img: &RgbImage, lines: &[Line], font: &FontVec) -> Result<Vec<u8>, String> {
+    let (width, height) = (img.width() as f32, img.height() as f32);
+    let mut doc = Document::with_version("1.5");
+
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, IMAGE_QUALITY)
+        .encode_image(img)
+        .map_err(|e| format!("Failed to encode page image: {}", e))?;
+    let image_id = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => img.width() as i64,
+            "Height" => img.height() as i64,
+            "ColorSpace" => "DeviceRGB",
+            "BitsPerComponent" => 8,
+            "Filter" => "DCTDecode",
+        },
+        jpeg,
+    ));
+
+    let units_per_em = font.units_per_em().unwrap_or(1000.0);
+    let ascent = font.ascent_unscaled() / units_per_em;
+    let descent = font.descent_unscaled() / units_per_em;
+    // Character -> CID, assigned from 1 in order of first use
+    let mut cids: BTreeMap<char, u16> = BTreeMap::new();
+
+    let mut content = format!("q {} 0 0 {} 0 0 cm /Im0 Do Q\nBT 3 Tr\n", width, height);
+    for line in lines.iter() {
+        let text = line.text.trim();
+        if text.is_empty() || line.points.is_empty() {
+            continue;
+        }
+        if cids.len() + text.chars().count() > u16::MAX as usize {
+            return Err("Too many distinct characters for one PDF font".to_string());
+        }
+        let codes: Vec<u16> = text
+            .chars()
+            .map(|c| {
+                let next = cids.len() as u16 + 1;
+                *cids.entry(c).or_insert(next)
+            })
+            .collect();
+        let natural_width: f32 = text.chars().map(|c| font.h_advance_unscaled(font.glyph_id(c)) / units_per_em).sum();
+
+        let Some((origin, dir, length, box_height)) = baseline(&line.points, height) else { continue };
+        // Size the font so its ascent..descent spans the box, and stretch the run to its width
+        let size = box_height / (ascent - descent).max(0.1);
+        let scale = if natural_width > 0.0 { 100.0 * length / (natural_width * size) } else { 100.0 };
+        // Baseline sits above the box bottom by the descent, along the box's up direction
+        let lift = -descent * size;
+        let x = origin[0] - dir[1] * lift;
+        let y = origin[1] + dir[0] * lift;
+        let hex: String = codes.iter().map(|cid| format!("{:04X}", cid)).collect();
+        content.push_str(&format!(
+            "/F0 {:.2} Tf {:.2} Tz {:.4} {:.4} {:.4} {:.4} {:.2} {:.2} Tm <{}> Tj\n",
+            size, scale, dir[0], dir[1], -dir[1], dir[0], x, y, hex
+        ));
+    }
+    content.push_str("ET\n");
+
+    let font_id = add_font(&mut doc, font, &cids, ascent, descent);
+    let mut content = Stream::new(Dictionary::new(), content.into_bytes());
+    content.compress().map_err(|e| format!("Failed to compress page content: {}", e))?;
+    let content_id = doc.add_object(content);
+
+    let pages_id = doc.new_object_id();
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+        "Contents" => content_id,
+        "Resources" => dictionary! {
+            "XObject" => dictionary! { "Im0" => image_id },
+            "Font" => dictionary! { "F0" => font_id },
+        },
+    });
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! {
+        "Type" => "Pages",
+        "Kids" => vec![page_id.into()],
+        "Count" => 1,
+    }));
+    let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    doc.trailer.set("Root", catalog_id);
+
+    let mut out = Vec::new();
+    doc.save_to(&mut out).map_err(|e| format!("Failed to write PDF: {}", e))?;
+    Ok(out)
+}
+
+// Baseline of a box in PDF space (y up): bottom-left corner, unit direction along the text,
+// run length and box height. Four-point boxes keep their rotation; others use the bounding rect.
+fn baseline(points: &[[f32; 2]], page_height: f32) -> Option<([f32; 2], [f32; 2], f32, f32)> {
+    let flip = |p: [f32; 2]| [p[0], page_height - p[1]];
+    let (top_left, top_right, bottom_left) = if points.len() == 4 {
+        (flip(points[0]), flip(points[1]), flip(points[3]))
+    } else {
+        let x0 = points.iter().map(|p| p[0]).fold(f32::MAX, f32::min);
+        let x1 = points.iter().map(|p| p[0]).fold(f32::MIN, f32::max);
+        let y0 = points.iter().map(|p| p[1]).fold(f32::MAX, f32::min);
+        let y1 = points.iter().map(|p| p[1]).fold(f32::MIN, f32::max);
+        (flip([x0, y0]), flip([x1, y0]), flip([x0, y1]))
+    };
+    let (dx, dy) = (top_right[0] - top_left[0], top_right[1] - top_left[1]);
+    let length = dx.hypot(dy);
+    let box_height = (top_left[0] - bottom_left[0]).hypot(top_left[1] - bottom_left[1]);
+    if length < 1.0 || box_height < 1.0 {
+        return None;
+    }
+    Some((bottom_left, [dx / length, dy / length], length, box_height))
+}
+
+// Type0 font over the whole TrueType file, with glyph, width and Unicode mappings for `cids`
+fn add_font(doc: &mut Document, font: &FontVec, cids: &BTreeMap<char, u16>, ascent: f32, descent: f32) -> lopdf::ObjectId {
+    let file_id = doc.add_object(font_file(font).clone());
+    let descriptor_id = doc.add_object(dictionary! {
+        "Type" => "FontDescriptor",
+        "FontName" => "OCRText",
+        "Flags" => 4,
+        "FontBBox" => vec![0.into(), ((descent * 1000.0) as i64).into(), 1000.into(), ((ascent * 1000.0) as i64).into()],
+        "ItalicAngle" => 0,
+        "Ascent" => (ascent * 1000.0) as i64,
+        "Descent" => (descent * 1000.0) as i64,
+        "CapHeight" => (ascent * 1000.0) as i64,
+        "StemV" => 80,
+        "FontFile2" => file_id,
+    });
+
+    let units_per_em = font.units_per_em().unwrap_or(1000.0);
+    let mut by_cid: Vec<(u16, char)> = cids.iter().map(|(&c, &cid)| (cid, c)).collect();
+    by_cid.sort();
+    let widths: Vec<Object> = by_cid
+        .iter()
+        .flat_map(|&(cid, c)| {
+            let advance = (font.h_advance_unscaled(font.glyph_id(c)) / units_per_em * 1000.0).round() as i64;
+            [Object::Integer(cid as i64), Object::Array(vec![Object::Integer(advance)])]
+        })
+        .collect();
+    // Two big-endian bytes per CID, from CID 0, giving its glyph id
+    let mut cid_to_gid = vec![0u8; 2 * (by_cid.len() + 1)];
+    for &(cid, c) in by_cid.iter() {
+        let gid = font.glyph_id(c).0.to_be_bytes();
+        cid_to_gid[2 * cid as usize..2 * cid as usize + 2].copy_from_slice(&gid);
+    }
+    let mut cid_to_gid = Stream::new(Dictionary::new(), cid_to_gid);
+    let _ = cid_to_gid.compress();
+    let cid_to_gid_id = doc.add_object(cid_to_gid);
+    let cid_font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "CIDFontType2",
+        "BaseFont" => "OCRText",
+        "CIDSystemInfo" => dictionary! {
+            "Registry" => Object::string_literal("Adobe"),
+            "Ordering" => Object::string_literal("Identity"),
+            "Supplement" => 0,
+        },
+        "FontDescriptor" => descriptor_id,
+        "DW" => 1000,
+        "W" => widths,
+        "CIDToGIDMap" => cid_to_gid_id,
+    });
+
+    let mut to_unicode = Stream::new(Dictionary::new(), to_unicode_cmap(&by_cid).into_bytes());
+    let _ = to_unicode.compress();
+    let to_unicode_id = doc.add_object(to_unicode);
+
+    doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type0",
+        "BaseFont" => "OCRText",
+        "Encoding" => "Identity-H",
+        "DescendantFonts" => vec![cid_font_id.into()],
+        "ToUnicode" => to_unicode_id,
+    })
+}
+
+// The compressed font program is the same for every request, so it's built once
+fn font_file(font: &FontVec) -> &'static Stream {
+    static FONT_FILE: OnceLock<Stream> = OnceLock::new();
+    FONT_FILE.get_or_init(|| {
+        let data = font.as_slice().to_vec();
+        let mut stream = Stream::new(dictionary! { "Length1" => data.len() as i64 }, data);
+        let _ = stream.compress();
+        stream
+    })
+}
+
+fn to_unicode_cmap(by_cid: &[(u16, char)]) -> String {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+    // bfchar blocks hold at most 100 entries
+    for chunk in by_cid.chunks(100) {
+        cmap.push_str(&format!("{} beginbfchar\n", chunk.len()));
+        for (cid, c) in chunk {
+            let mut utf16 = [0u16; 2];
+            let hex: String = c.encode_utf16(&mut utf16).iter().map(|u| format!("{:04X}", u)).collect();
+            cmap.push_str(&format!("<{:04X}> <{}>\n", cid, hex));
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+    cmap
+}
@@ -0,0 +1,90 @@
+// Recognition defaults saved through `/api/ocr/settings`, so users who always OCR with the same
+// thresholds needn't send them on every request and keep them across sidecar restarts.
+//
+// Stored as JSON at OCR_SETTINGS_PATH, else `ocr-settings.json` next to the binary. A setting
+// left unset (or null) falls back to the startup config, so the file only holds what was chosen.
+
+use crate::error::ApiError;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
+
+const FILE_NAME: &str = "ocr-settings.json";
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+// f64 so saved values read back as they were sent (0.4 rather than 0.4000000059604645)
+pub struct Settings {
+    pub det_db_thresh: Option<f64>,
+    pub cls_thresh: Option<f64>,
+    pub use_cls: Option<bool>,
+    pub drop_score: Option<f64>,
+}
+
+impl Settings {
+    #[cfg_attr(not(feature = "with-ocr"), allow(dead_code))]
+    pub fn is_empty(&self) -> bool {
+        self.det_db_thresh.is_none() && self.cls_thresh.is_none() && self.use_cls.is_none() && self.drop_score.is_none()
+    }
+
+    fn validate(&self) -> Result<(), ApiError> {
+        let thresholds = [("det_db_thresh", self.det_db_thresh), ("cls_thresh", self.cls_thresh), ("drop_score", self.drop_score)];
+        for (name, value) in thresholds {
+            if let Some(v) = value && !(0.0..=1.0).contains(&v) {
+                return Err(ApiError::InvalidField(format!("Invalid '{}': expected a number between 0 and 1, got {}", name, v)));
+            }
+        }
+        Ok(())
+    }
+}
+
+static SETTINGS: OnceLock<RwLock<Settings>> = OnceLock::new();
+
+fn path() -> PathBuf {
+    match std::env::var("OCR_SETTINGS_PATH") {
+        Ok(p) if !p.trim().is_empty() => PathBuf::from(p.trim()),
+        _ => std::env::current_exe().ok().and_then(|exe| Some(exe.parent()?.join(FILE_NAME))).unwrap_or_else(|| PathBuf::from(FILE_NAME)),
+    }
+}
+
+fn cell() -> &'static RwLock<Settings> {
+    SETTINGS.get_or_init(RwLock::default)
+}
+
+/// Load the saved settings at startup. A missing file means nothing was saved yet; an unreadable
+/// or invalid one is logged and ignored rather than keeping the service from starting, and the
+/// next save replaces it.
+pub fn init() {
+    let path = path();
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => return log::warn!("Ignoring settings file {}: {}", path.display(), e),
+    };
+    let settings = match serde_json::from_str::<Settings>(&text).map_err(|e| e.to_string()).and_then(|s| s.validate().map(|_| s).map_err(|e| e.to_string())) {
+        Ok(s) => s,
+        Err(e) => return log::warn!("Ignoring settings file {}: {}", path.display(), e),
+    };
+    log::info!("Loaded settings from {}", path.display());
+    *cell().write().unwrap() = settings;
+}
+
+/// The saved settings (unset fields fall back to the config)
+pub fn get() -> Settings {
+    cell().read().unwrap().clone()
+}
+
+/// Validate, write and apply `settings`, replacing the saved ones. The file is written to a
+/// temporary name and renamed over the old one so a crash can't leave it half-written.
+pub fn put(settings: Settings) -> Result<(), ApiError> {
+    settings.validate()?;
+    let path = path();
+    let json = serde_json::to_vec_pretty(&settings).map_err(|e| ApiError::Internal(e.to_string()))?;
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, json)
+        .and_then(|_| std::fs::rename(&tmp, &path))
+        .map_err(|e| ApiError::Internal(format!("Failed to save settings to {}: {}", path.display(), e)))?;
+    *cell().write().unwrap() = settings;
+    Ok(())
+}
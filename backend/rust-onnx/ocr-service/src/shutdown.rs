@@ -0,0 +1,64 @@
+// Graceful shutdown: stop accepting connections, let in-flight requests finish for up to
+// OCR_SHUTDOWN_GRACE_SECS, then exit. Triggered by POST /api/admin/shutdown, SIGTERM, or SIGINT
+// (Ctrl-C; actix's own handler would force-stop on that, so it's replaced by this one).
+
+use crate::error::ApiError;
+use actix_web::HttpRequest;
+use actix_web::dev::ServerHandle;
+use std::time::Duration;
+
+const DEFAULT_GRACE_SECS: u64 = 10;
+
+/// Longest the server waits for in-flight requests once a shutdown starts
+pub fn grace_period() -> Duration {
+    let secs = std::env::var("OCR_SHUTDOWN_GRACE_SECS").ok().and_then(|v| v.trim().parse::<u64>().ok()).unwrap_or(DEFAULT_GRACE_SECS);
+    Duration::from_secs(secs)
+}
+
+/// The shutdown endpoint is off unless OCR_ADMIN_TOKEN is set, and then needs it in X-Admin-Token
+pub fn authorize(req: &HttpRequest) -> Result<(), ApiError> {
+    let expected = match std::env::var("OCR_ADMIN_TOKEN") {
+        Ok(token) if !token.is_empty() => token,
+        _ => return Err(ApiError::Forbidden("admin endpoints are disabled; set OCR_ADMIN_TOKEN to enable them".into())),
+    };
+    let given = req.headers().get("X-Admin-Token").map(|v| v.as_bytes()).unwrap_or_default();
+    // Compared as hashes so the time taken doesn't depend on how much of the token matched
+    if blake3::hash(given) != blake3::hash(expected.as_bytes()) {
+        return Err(ApiError::Forbidden("invalid X-Admin-Token".into()));
+    }
+    Ok(())
+}
+
+/// Start a graceful stop without waiting for it, so the caller can still answer its request
+pub fn begin(handle: &ServerHandle) {
+    let handle = handle.clone();
+    actix_rt::spawn(async move { handle.stop(true).await });
+}
+
+/// Stop gracefully on SIGINT or SIGTERM (Ctrl-C on Windows)
+pub fn on_signals(handle: ServerHandle) {
+    actix_rt::spawn(async move {
+        wait_for_signal().await;
+        log::info!("Shutdown signal received; draining in-flight requests");
+        handle.stop(true).await;
+    });
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use actix_rt::signal::unix::{SignalKind, signal};
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(s) => s,
+        Err(e) => {
+            log::warn!("Could not listen for SIGTERM: {}", e);
+            let _ = actix_rt::signal::ctrl_c().await;
+            return;
+        }
+    };
+    futures::future::select(Box::pin(actix_rt::signal::ctrl_c()), Box::pin(terminate.recv())).await;
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = actix_rt::signal::ctrl_c().await;
+}
@@ -0,0 +1,63 @@
+// Spreadsheet export of Python-format results: one row per line with its page, text, score and
+// the four box corners.
+
+use serde_json::Value;
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Csv,
+    Tsv,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Tsv => "text/tab-separated-values; charset=utf-8",
+        }
+    }
+
+    fn separator(self) -> char {
+        match self {
+            Format::Csv => ',',
+            Format::Tsv => '\t',
+        }
+    }
+}
+
+const HEADER: [&str; 12] = ["page", "line_index", "text", "score", "x1", "y1", "x2", "y2", "x3", "y3", "x4", "y4"];
+
+/// Header plus one row per `[box_points, [text, score]]` line. `line_index` is 0-based within the
+/// page; corners missing from a box are left empty and any past the fourth are dropped.
+pub fn write(pages: &[(u64, &[Value])], format: Format) -> String {
+    let sep = format.separator().to_string();
+    let mut out = HEADER.join(&sep);
+    out.push_str("\r\n");
+    for (page, lines) in pages.iter() {
+        for (i, line) in lines.iter().enumerate() {
+            let text = line.get(1).and_then(|t| t.get(0)).and_then(|s| s.as_str()).unwrap_or("");
+            let score = line.get(1).and_then(|t| t.get(1)).and_then(|s| s.as_f64()).map(|s| s.to_string()).unwrap_or_default();
+            let mut row = vec![page.to_string(), i.to_string(), field(text, format), score];
+            let points = line.get(0).and_then(|b| b.as_array()).map(|p| p.as_slice()).unwrap_or(&[]);
+            for k in 0..4 {
+                for axis in 0..2 {
+                    let v = points.get(k).and_then(|p| p.get(axis)).and_then(|v| v.as_f64());
+                    row.push(v.map(|v| v.to_string()).unwrap_or_default());
+                }
+            }
+            out.push_str(&row.join(&sep));
+            out.push_str("\r\n");
+        }
+    }
+    out
+}
+
+// CSV quotes fields containing separators, quotes or line breaks (doubling inner quotes); TSV
+// has no quoting, so tabs and line breaks become spaces
+fn field(text: &str, format: Format) -> String {
+    match format {
+        Format::Csv if text.contains([',', '"', '\n', '\r']) => format!("\"{}\"", text.replace('"', "\"\"")),
+        Format::Csv => text.to_string(),
+        Format::Tsv => text.replace(['\t', '\n', '\r'], " "),
+    }
+}
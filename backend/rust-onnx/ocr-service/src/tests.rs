@@ -0,0 +1,1154 @@
+// Handler tests, run against the app's own services without a model loaded. The few that need
+// real OCR are ignored unless the model files are in the configured model directory.
+
+use super::*;
+use actix_web::http::StatusCode;
+use actix_web::test;
+
+const BOUNDARY: &str = "ocr-service-test-boundary";
+
+// A multipart/form-data request to `uri` with the given fields
+fn form(uri: &str, fields: &[(&str, &[u8])]) -> test::TestRequest {
+    let mut body = Vec::new();
+    for (name, data) in fields {
+        body.extend_from_slice(format!("--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"").as_bytes());
+        if *name == "file" {
+            body.extend_from_slice(b"; filename=\"image.png\"\r\nContent-Type: image/png");
+        }
+        body.extend_from_slice(b"\r\n\r\n");
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+    test::TestRequest::post()
+        .uri(uri)
+        .insert_header(("Content-Type", format!("multipart/form-data; boundary={BOUNDARY}")))
+        .set_payload(body)
+}
+
+// A white PNG of the given size
+fn png(width: u32, height: u32) -> Vec<u8> {
+    encoded(width, height, image::ImageFormat::Png)
+}
+
+// A white image of the given size in `format`
+fn encoded(width: u32, height: u32, format: image::ImageFormat) -> Vec<u8> {
+    let img = image::RgbImage::from_pixel(width, height, image::Rgb([255, 255, 255]));
+    let mut out = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut out, format).unwrap();
+    out.into_inner()
+}
+
+#[actix_web::test]
+async fn draw_accepts_closed_and_collapsed_polygons() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(draw)).await;
+    // A ring repeating its first point, a box collapsed to a point and one with repeated corners
+    let result = r#"{"result": [[
+        [[[10, 10], [60, 10], [60, 30], [10, 30], [10, 10]], ["closed", 0.9]],
+        [[[5, 5], [5, 5], [5, 5], [5, 5]], ["point", 0.9]],
+        [[[70, 40], [70, 40], [120, 40], [120, 60], [70, 60]], ["repeated", 0.9]]
+    ]]}"#;
+    let image = png(200, 100);
+    let req = form("/api/ocr/draw", &[("file", &image), ("ocr_result", result.as_bytes())]).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = test::read_body(res).await;
+    let drawn = image::load_from_memory(&body).unwrap().to_rgb8();
+    assert_eq!(drawn.dimensions(), (200, 100));
+    assert_ne!(drawn.get_pixel(35, 10), &image::Rgb([255, 255, 255]));
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn recognize_with_boxes_rejects_boxes_outside_the_image() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(recognize_with_boxes)).await;
+    // The second box reaches past the right edge of the 100x50 image
+    let boxes = b"[[[10, 10], [40, 10], [40, 20], [10, 20]], [[60, 10], [140, 10], [140, 20], [60, 20]]]";
+    let image = png(100, 50);
+    let req = form("/api/ocr/recognize_with_boxes", &[("file", &image), ("boxes", boxes)]).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(body.contains("box 1"), "{body}");
+}
+
+#[actix_web::test]
+async fn model_unavailable_is_a_503() {
+    let res = ApiError::ModelUnavailable("model unavailable: failed to reload 'ppocrv5'".to_string()).error_response();
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = serde_json::from_slice(&actix_web::body::to_bytes(res.into_body()).await.unwrap()).unwrap();
+    assert_eq!(body["error"]["code"], "model_unavailable");
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn recognize_boxes_rejects_boxes_that_are_not_quadrilaterals() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(recognize_boxes)).await;
+    let boxes = b"[[[10, 10], [40, 10], [40, 20], [10, 20]], [[60, 10], [90, 10], [90, 20]]]";
+    let image = png(100, 50);
+    let req = form("/api/ocr/recognize_boxes", &[("file", &image), ("boxes", boxes)]).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+    assert!(body.contains("box 1 has 3 points, expected 4"), "{body}");
+}
+
+#[actix_web::test]
+async fn draw_decodes_bmp_and_tiff_uploads() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(draw)).await;
+    let result = r#"{"result": [[[[[10, 10], [60, 10], [60, 30], [10, 30]], ["line", 0.9]]]]}"#;
+    for format in [image::ImageFormat::Bmp, image::ImageFormat::Tiff] {
+        let image = encoded(120, 80, format);
+        let req = form("/api/ocr/draw", &[("file", &image), ("ocr_result", result.as_bytes())]).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK, "{format:?}");
+        let drawn = image::load_from_memory(&test::read_body(res).await).unwrap();
+        assert_eq!((drawn.width(), drawn.height()), (120, 80), "{format:?}");
+    }
+}
+
+#[actix_web::test]
+async fn draw_rejects_unrecognized_bytes_as_unsupported() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(draw)).await;
+    let result = br#"{"result": [[]]}"#;
+    let req = form("/api/ocr/draw", &[("file", b"not an image, just some text"), ("ocr_result", result)]).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let body: serde_json::Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+    assert_eq!(body["error"]["code"], "unsupported_format");
+}
+
+async fn ocr2text_response(body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+    let app = test::init_service(App::new().service(ocr2text)).await;
+    let req = test::TestRequest::post().uri("/api/ocr/ocr2text").set_json(body).to_request();
+    let res = test::call_service(&app, req).await;
+    let status = res.status();
+    (status, serde_json::from_slice(&test::read_body(res).await).unwrap())
+}
+
+#[actix_web::test]
+async fn ocr2text_returns_empty_text_for_an_empty_result() {
+    let (status, body) = ocr2text_response(serde_json::json!({"result": []})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, serde_json::json!({"text": ""}));
+}
+
+#[actix_web::test]
+async fn ocr2text_rejects_a_result_that_is_not_a_list() {
+    for result in [serde_json::Value::Null, serde_json::json!("string")] {
+        let (status, body) = ocr2text_response(serde_json::json!({"result": result})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{result}");
+        assert_eq!(body["error"]["code"], "invalid_field", "{result}");
+    }
+}
+
+#[actix_web::test]
+async fn ocr2text_rejects_a_malformed_page() {
+    let line = serde_json::json!([[[0, 0], [10, 0], [10, 10], [0, 10]], ["first", 0.9]]);
+    let pages = serde_json::json!([
+        {"page": 1, "result": [[line]]},
+        {"page": 2, "result": []},
+        {"page": 3, "result": "not a page"}
+    ]);
+    let (status, body) = ocr2text_response(serde_json::json!({"result": pages})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"]["message"].as_str().unwrap().contains("page 3"), "{body}");
+
+    let (status, body) = ocr2text_response(serde_json::json!({"result": pages.as_array().unwrap()[..2]})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["text"], "first");
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn inference_never_runs_more_calls_than_its_limit() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let inference = Inference::new(2, Duration::from_secs(5));
+    let (running, most) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+    let calls = (0..8).map(|_| {
+        let (running, most) = (running.clone(), most.clone());
+        inference.run("OCR", move || {
+            most.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            running.fetch_sub(1, Ordering::SeqCst);
+            Ok::<_, String>(())
+        })
+    });
+    let results = futures::future::join_all(calls).await;
+    assert_eq!(most.load(Ordering::SeqCst), 2);
+    assert_eq!(inference.calls(), 8);
+    // Six of the eight had to queue behind the first two
+    let waits: Vec<Duration> = results.into_iter().map(|r| r.unwrap().1).collect();
+    assert!(waits.iter().filter(|w| **w >= Duration::from_millis(40)).count() >= 6, "{waits:?}");
+    assert_eq!(inference.running(), 0);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn inference_times_out_but_lets_the_call_finish() {
+    let inference = Inference::new(1, Duration::from_millis(20));
+    let slow = inference.run("OCR", || {
+        std::thread::sleep(Duration::from_millis(300));
+        Ok::<_, String>(())
+    });
+    let err = slow.await.unwrap_err();
+    assert_eq!(err.error_response().status(), StatusCode::REQUEST_TIMEOUT);
+    assert_eq!(err.to_string(), "OCR timed out after 20ms");
+    // Still running in the background, holding its permit until it's done
+    assert_eq!(inference.running(), 1);
+    actix_rt::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(inference.running(), 0);
+}
+
+#[actix_web::test]
+async fn draw_rejects_images_with_a_zero_pixel_side() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(draw)).await;
+    // Headers that decode fine but declare 1x0 (farbfeld) and 0x0 (PPM) pixels
+    let mut farbfeld = b"farbfeld".to_vec();
+    farbfeld.extend_from_slice(&1u32.to_be_bytes());
+    farbfeld.extend_from_slice(&0u32.to_be_bytes());
+    for image in [farbfeld, b"P6\n0 0\n255\n".to_vec()] {
+        let req = form("/api/ocr/draw", &[("file", &image), ("ocr_result", br#"{"result": [[]]}"#)]).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+        assert_eq!(body["error"]["message"], "image has zero dimensions");
+    }
+}
+
+#[actix_web::test]
+async fn hocr_is_well_formed_xml() {
+    let app = test::init_service(App::new().service(hocr_document)).await;
+    let result = serde_json::json!({"result": [[
+        [[[10, 10], [60, 10], [60, 30], [10, 30]], ["a < b & c", 0.91]],
+        [[[10, 40], [80, 40], [80, 60], [10, 60]], ["second", 0.5]]
+    ]], "width": 200, "height": 100});
+    let req = test::TestRequest::post().uri("/api/ocr/hocr").set_json(result).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+
+    let options = roxmltree::ParsingOptions { allow_dtd: true, ..Default::default() };
+    let doc = roxmltree::Document::parse_with_options(&body, options).unwrap();
+    let class = |name: &str| doc.descendants().filter(|n| n.attribute("class") == Some(name)).collect::<Vec<_>>();
+    assert_eq!(class("ocr_page")[0].attribute("title"), Some("bbox 0 0 200 100; ppageno 0"));
+    assert_eq!(class("ocr_line").len(), 2);
+    let words = class("ocrx_word");
+    assert_eq!(words[0].text(), Some("a < b & c"));
+    assert_eq!(words[0].attribute("title"), Some("bbox 10 10 60 30; x_wconf 91"));
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn clip_boxes_clamps_points_into_the_page() {
+    // A detection box spilling past every edge of a 100x50 page
+    let mut region = Region { points: vec![[-5.0, -3.0], [130.0, -3.0], [130.0, 60.0], [-5.0, 60.0]], text: "edge".into(), score: 0.9 };
+    region.clip_to(100, 50);
+    assert_eq!(region.points, vec![[0.0, 0.0], [100.0, 0.0], [100.0, 50.0], [0.0, 50.0]]);
+    assert_eq!(region.to_legacy()[0], serde_json::json!([[0.0, 0.0], [100.0, 0.0], [100.0, 50.0], [0.0, 50.0]]));
+}
+
+#[actix_web::test]
+async fn draw_refuses_an_image_over_the_upload_cap() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(draw)).await;
+    let image = vec![0u8; max_image_bytes() + 1];
+    let req = form("/api/ocr/draw", &[("file", &image), ("ocr_result", br#"{"result": [[]]}"#)]).to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    let body: serde_json::Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+    assert_eq!(body["error"]["message"], format!("image exceeds {} bytes", max_image_bytes()));
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn recognize_refuses_an_image_over_the_upload_cap() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(recognize)).await;
+    let image = vec![0u8; max_image_bytes() + 1];
+    let res = test::call_service(&app, form("/api/ocr/", &[("file", &image)]).to_request()).await;
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[actix_web::test]
+async fn draw_is_refused_while_its_own_limit_is_full() {
+    let mut state = AppState::from_env();
+    state.draws = Arc::new(JobGate::new(1));
+    let gate = state.draws.clone();
+    let app = test::init_service(App::new().app_data(web::Data::new(state)).service(draw)).await;
+    let image = png(400, 300);
+    let result = r#"{"result": [[[[[10, 10], [60, 10], [60, 30], [10, 30]], ["line", 0.9]]]]}"#;
+    let request = || form("/api/ocr/draw", &[("file", &image), ("ocr_result", result.as_bytes())]).to_request();
+
+    // Another draw holds the only slot
+    let slot = gate.try_enter().unwrap();
+    let res = test::call_service(&app, request()).await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert!(res.headers().contains_key("Retry-After"));
+    drop(slot);
+
+    // A burst gets through one at a time or is turned away; none of it fails otherwise
+    let statuses: Vec<StatusCode> = futures::future::join_all((0..16).map(|_| test::call_service(&app, request()))).await.iter().map(|r| r.status()).collect();
+    assert!(statuses.iter().all(|s| *s == StatusCode::OK || *s == StatusCode::SERVICE_UNAVAILABLE), "{statuses:?}");
+    assert!(statuses.contains(&StatusCode::OK));
+    assert_eq!(gate.depth(), 0);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn max_side_boxes_map_back_onto_the_upload() {
+    let page = image::RgbImage::from_pixel(1000, 400, image::Rgb([200, 120, 40]));
+    let preprocess = preprocess::Preprocess { max_side: Some(250), grayscale: true, contrast: Some(2.0) };
+    let (shrunk, factor) = preprocess.apply(page);
+    assert_eq!((shrunk.dimensions(), factor), ((250, 100), 0.25));
+    let p = shrunk.get_pixel(10, 10);
+    assert!(p[0] == p[1] && p[1] == p[2], "{p:?}");
+
+    // A box found on the shrunk page, scaled back the way recognize does
+    let found = Region { points: vec![[25.0, 10.0], [75.0, 10.0], [75.0, 30.0], [25.0, 30.0]], text: "line".into(), score: 0.9 };
+    let mapped = found.scaled(1.0 / factor);
+    assert_eq!(mapped.points, vec![[100.0, 40.0], [300.0, 40.0], [300.0, 120.0], [100.0, 120.0]]);
+    assert_eq!(mapped.scaled(factor).points[2], [75.0, 30.0]);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn recognize_rejects_out_of_range_preprocessing() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(recognize)).await;
+    let image = png(100, 50);
+    for (field, value, message) in [
+        ("max_side", "16", "Invalid 'max_side': expected an integer of at least 32, got '16'"),
+        ("contrast", "11", "Invalid 'contrast': expected a number between 0 and 10, got '11'"),
+    ] {
+        let res = test::call_service(&app, form("/api/ocr/", &[("file", &image), (field, value.as_bytes())]).to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{field}");
+        let body: serde_json::Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+        assert_eq!(body["error"]["message"], message);
+    }
+}
+
+// health and ocr2text behind the API key check, with `key` as OCR_API_KEY
+async fn with_api_key(key: Option<&'static str>, uri: &str, authorization: Option<&str>) -> StatusCode {
+    let app = test::init_service(
+        App::new()
+            .wrap(actix_web::middleware::from_fn(move |req, next: actix_web::middleware::Next<_>| auth::check_api_key(key, req, next)))
+            .service(health)
+            .service(ocr2text),
+    )
+    .await;
+    let mut req = if uri == "/api/health/" { test::TestRequest::get() } else { test::TestRequest::post().set_json(serde_json::json!({"result": []})) };
+    req = req.uri(uri);
+    if let Some(authorization) = authorization {
+        req = req.insert_header(("Authorization", authorization));
+    }
+    test::call_service(&app, req.to_request()).await.status()
+}
+
+#[actix_web::test]
+async fn api_key_guards_ocr_endpoints_only_when_set() {
+    // Disabled: no header needed
+    assert_eq!(with_api_key(None, "/api/ocr/ocr2text", None).await, StatusCode::OK);
+    // Enabled: missing or wrong key is a 401, the right one passes
+    assert_eq!(with_api_key(Some("secret"), "/api/ocr/ocr2text", None).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(with_api_key(Some("secret"), "/api/ocr/ocr2text", Some("Bearer wrong")).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(with_api_key(Some("secret"), "/api/ocr/ocr2text", Some("Bearer secret")).await, StatusCode::OK);
+    // Health stays open
+    assert_eq!(with_api_key(Some("secret"), "/api/health/", None).await, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn v2_results_round_trip_through_draw_and_ocr2text() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(draw).service(ocr2text)).await;
+    let lines = [
+        serde_json::json!([[[10, 10], [60, 10], [60, 30], [10, 30]], ["first", 0.9], {"id": "p1-0"}]),
+        serde_json::json!([[[10, 40], [90, 40], [90, 60], [10, 60]], ["second", 0.8]]),
+    ];
+    let legacy = serde_json::json!({"result": [lines]});
+    let v2 = serde_json::json!({"regions": lines.iter().map(|line| schema::region(line, None)).collect::<Vec<_>>()});
+    assert_eq!(v2["regions"][0], serde_json::json!({"box": [[10, 10], [60, 10], [60, 30], [10, 30]], "text": "first", "score": 0.9, "id": "p1-0"}));
+    assert_eq!(schema::to_legacy(&v2).unwrap().into_owned(), legacy);
+
+    let image = png(200, 100);
+    let mut drawn = Vec::new();
+    for body in [&legacy, &v2] {
+        let req = form("/api/ocr/draw", &[("file", &image), ("ocr_result", body.to_string().as_bytes())]).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        drawn.push(test::read_body(res).await);
+
+        let req = test::TestRequest::post().uri("/api/ocr/ocr2text").set_json(body).to_request();
+        let text: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(text["text"], "first\nsecond");
+    }
+    assert_eq!(drawn[0], drawn[1]);
+}
+
+#[actix_web::test]
+async fn v2_is_chosen_by_accept_header_or_query() {
+    let req = |uri: &str, accept: &str| test::TestRequest::post().uri(uri).insert_header(("Accept", accept)).to_http_request();
+    assert!(schema::wants_v2(&req("/api/ocr/", schema::MEDIA_TYPE)).unwrap());
+    assert!(!schema::wants_v2(&req("/api/ocr/", "application/json")).unwrap());
+    assert!(schema::wants_v2(&req("/api/ocr/?schema=v2", "application/json")).unwrap());
+    assert!(!schema::wants_v2(&req("/api/ocr/?schema=v1", schema::MEDIA_TYPE)).unwrap());
+    assert!(schema::wants_v2(&req("/api/ocr/?schema=v3", "")).is_err());
+}
+
+#[actix_web::test]
+async fn draw_tells_empty_and_truncated_uploads_apart() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(draw)).await;
+    let truncated = png(120, 80)[..60].to_vec();
+    for (image, message) in [(Vec::new(), "empty image"), (truncated, "truncated or corrupt png image")] {
+        let req = form("/api/ocr/draw", &[("file", &image), ("ocr_result", br#"{"result": [[]]}"#)]).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{message}");
+        let body: serde_json::Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+        assert_eq!(body["error"], serde_json::json!({"code": "decode_failed", "message": message}));
+    }
+}
+
+#[actix_web::test]
+async fn rate_limit_refuses_a_burst_then_recovers() {
+    // Two requests a second per client IP
+    let limiter: &'static ratelimit::Limiter = Box::leak(Box::new(ratelimit::Limiter::new(2.0)));
+    let app = test::init_service(
+        App::new()
+            .wrap(actix_web::middleware::from_fn(move |req, next: actix_web::middleware::Next<_>| ratelimit::limit_with(Some(limiter), req, next)))
+            .service(health)
+            .service(ocr2text),
+    )
+    .await;
+    // Same IP, different ports: one client
+    let ocr2text_from = |port: u16| {
+        test::TestRequest::post()
+            .uri("/api/ocr/ocr2text")
+            .peer_addr(format!("127.0.0.1:{port}").parse().unwrap())
+            .set_json(serde_json::json!({"result": []}))
+            .to_request()
+    };
+
+    assert_eq!(test::call_service(&app, ocr2text_from(5000)).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, ocr2text_from(5001)).await.status(), StatusCode::OK);
+    let res = test::call_service(&app, ocr2text_from(5002)).await;
+    assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(res.headers().get("Retry-After").unwrap(), "1");
+    // Health isn't limited
+    let health_req = test::TestRequest::get().uri("/api/health/").peer_addr("127.0.0.1:5003".parse().unwrap()).to_request();
+    assert_eq!(test::call_service(&app, health_req).await.status(), StatusCode::OK);
+
+    // Half a second refills a token
+    actix_rt::time::sleep(std::time::Duration::from_millis(600)).await;
+    assert_eq!(test::call_service(&app, ocr2text_from(5004)).await.status(), StatusCode::OK);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn batch_dedupe_matches_identical_and_near_identical_images() {
+    // Dark/light stripes, and the same page with the stripes swapped
+    let stripes = |dark_first: bool, format: image::ImageFormat| {
+        let img = image::RgbImage::from_fn(180, 80, |x, _| if ((x / 20) % 2 == 0) == dark_first { image::Rgb([20, 20, 20]) } else { image::Rgb([235, 235, 235]) });
+        let mut out = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut out, format).unwrap();
+        out.into_inner()
+    };
+    let page = stripes(true, image::ImageFormat::Png);
+    let recompressed = stripes(true, image::ImageFormat::Bmp);
+    let distinct = stripes(false, image::ImageFormat::Png);
+    let files = [&page, &page, &recompressed, &distinct];
+
+    let exact = imagehash::find_duplicates(files.iter().map(|f| f.as_slice()), None);
+    assert_eq!(exact, vec![None, Some(0), None, None]);
+    let perceptual = imagehash::find_duplicates(files.iter().map(|f| f.as_slice()), Some(imagehash::DEFAULT_DISTANCE));
+    assert_eq!(perceptual, vec![None, Some(0), Some(0), None]);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn merge_boxes_joins_two_word_boxes_of_one_line() {
+    let rect = |x0: f32, y0: f32, x1: f32, y1: f32, score: f32| Region { points: vec![[x0, y0], [x1, y0], [x1, y1], [x0, y1]], text: String::new(), score };
+    // "Hello" and "world" 10px apart on a 20px line, and a line below them
+    let regions = vec![rect(110.0, 10.0, 180.0, 30.0, 0.8), rect(10.0, 10.0, 100.0, 30.0, 0.9), rect(10.0, 60.0, 120.0, 80.0, 0.7)];
+    let merged = linemerge::merge_adjacent(regions.clone(), linemerge::DEFAULT_GAP);
+    assert_eq!(merged.len(), 2);
+    assert_eq!(merged[0].points, vec![[10.0, 10.0], [180.0, 10.0], [180.0, 30.0], [10.0, 30.0]]);
+    assert!((merged[0].score - 0.85).abs() < 1e-6);
+    assert_eq!(merged[1].points, regions[2].points);
+    // A gap wider than max_gap line heights keeps the words apart
+    assert_eq!(linemerge::merge_adjacent(regions, 0.25).len(), 3);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn model_changes_racing_recognize_answer_cleanly() {
+    let state = web::Data::new(AppState::from_env());
+    let app = test::init_service(App::new().app_data(state.clone()).service(load_model).service(unload_model).service(model_status).service(recognize)).await;
+    let status = || async { test::call_and_read_body_json::<_, _, serde_json::Value>(&app, test::TestRequest::get().uri("/api/ocr/model_status").to_request()).await };
+
+    // A change under way shows in model_status, without a half-loaded model
+    let changing = state.model_changes.lock().await;
+    let during = status().await;
+    assert_eq!((during["changing"].clone(), during["loaded"].clone()), (serde_json::json!(true), serde_json::json!(false)));
+    drop(changing);
+    assert_eq!(status().await["changing"], false);
+
+    // Loads (of a directory with no model files), unloads, recognizes and status checks all at once
+    let missing = std::env::temp_dir().join("ocr-service-test-no-models");
+    let image = png(100, 50);
+    let requests = (0..24).map(|i| match i % 4 {
+        0 => form("/api/ocr/load", &[("model_dir", missing.to_str().unwrap().as_bytes())]).to_request(),
+        1 => form("/api/ocr/unload", &[]).to_request(),
+        2 => form("/api/ocr/", &[("file", &image)]).to_request(),
+        _ => test::TestRequest::get().uri("/api/ocr/model_status").to_request(),
+    });
+    let responses = futures::future::join_all(requests.map(|req| test::call_service(&app, req))).await;
+    for (i, res) in responses.into_iter().enumerate() {
+        let status = res.status();
+        let body: serde_json::Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+        match i % 4 {
+            0 => {
+                assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+                assert!(body["error"]["message"].as_str().unwrap().starts_with("model files not found"), "{body}");
+            }
+            1 => assert_eq!(status, StatusCode::OK),
+            2 => assert_eq!((status, body["error"]["code"].clone()), (StatusCode::BAD_REQUEST, serde_json::json!("model_not_loaded"))),
+            _ => assert_eq!((status, body["loaded"].clone()), (StatusCode::OK, serde_json::json!(false))),
+        }
+    }
+    assert_eq!(status().await["changing"], false);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn recognize_validates_the_prediction_fields() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(recognize)).await;
+    let image = png(100, 50);
+    for (field, value, message) in [
+        ("det_db_thresh", "1.5", "Invalid 'det_db_thresh': expected a number between 0 and 1, got '1.5'"),
+        ("cls_thresh", "high", "Invalid 'cls_thresh': expected a number between 0 and 1, got 'high'"),
+        ("use_cls", "yes", "Invalid 'use_cls': expected true or false, got 'yes'"),
+    ] {
+        let res = test::call_service(&app, form("/api/ocr/", &[("file", &image), (field, value.as_bytes())]).to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{field}");
+        let body: serde_json::Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+        assert_eq!(body["error"]["message"], message);
+    }
+    // Valid values get as far as picking a model
+    let fields: [(&str, &[u8]); 4] = [("file", &image), ("det_db_thresh", b"0.5"), ("cls_thresh", b"0.8"), ("use_cls", b"false")];
+    let res = test::call_service(&app, form("/api/ocr/", &fields).to_request()).await;
+    let body: serde_json::Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+    assert_eq!(body["error"]["code"], "model_not_loaded");
+
+    let mut params = PredictParams::default();
+    for (name, value) in [("det_db_thresh", "0.5"), ("cls_thresh", "0.8"), ("use_cls", "false")] {
+        assert!(predict_field(&mut params, name, value).unwrap());
+    }
+    assert_eq!((params.det_db_thresh, params.cls_thresh, params.use_cls), (0.5, 0.8, false));
+    assert!(!predict_field(&mut params, "dpi", "300").unwrap());
+}
+
+// A PDF of `pages` blank 2x1 inch pages
+#[cfg(feature = "with-ocr")]
+fn pdf(pages: usize) -> Vec<u8> {
+    use lopdf::{Document, Object, dictionary};
+    let mut doc = Document::with_version("1.5");
+    let pages_id = doc.new_object_id();
+    let kids: Vec<Object> = (0..pages)
+        .map(|_| doc.add_object(dictionary! {"Type" => "Page", "Parent" => pages_id, "MediaBox" => vec![0.into(), 0.into(), 144.into(), 72.into()]}).into())
+        .collect();
+    doc.objects.insert(pages_id, Object::Dictionary(dictionary! {"Type" => "Pages", "Kids" => kids, "Count" => pages as i64}));
+    let catalog = doc.add_object(dictionary! {"Type" => "Catalog", "Pages" => pages_id});
+    doc.trailer.set("Root", catalog);
+    let mut out = Vec::new();
+    doc.save_to(&mut out).unwrap();
+    out
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+#[ignore = "needs the pdfium library next to the test binary or on the system library path"]
+async fn pdf_pages_render_one_image_each() {
+    let two_pages = pdf(2);
+    assert!(pdf::is_pdf(&two_pages));
+    let pages = render_pdf(two_pages, 144.0).await.unwrap();
+    assert_eq!(pages.iter().map(|p| p.dimensions()).collect::<Vec<_>>(), vec![(288, 144), (288, 144)]);
+
+    let err = render_pdf(pdf(0), 144.0).await.unwrap_err();
+    assert_eq!(err.error_response().status(), StatusCode::BAD_REQUEST);
+    assert_eq!(err.to_string(), "PDF has no pages");
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn pdf_uploads_are_told_apart_by_their_header() {
+    assert!(pdf::is_pdf(&pdf(1)));
+    assert!(!pdf::is_pdf(&png(10, 10)));
+}
+
+// Time for `jobs` 50ms jobs to get through a pool of `workers` stand-in models, and the most that
+// held a model at once
+#[cfg(feature = "with-ocr")]
+async fn pool_run(workers: usize, jobs: usize) -> (Duration, usize) {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let info = pool::LoadInfo { model_dir: String::new(), det: String::new(), rec: String::new(), dict: String::new(), loaded_at: std::time::SystemTime::now(), load_time: Duration::ZERO, warmup_time: None, orientation_classifier: false };
+    let pool = Arc::new(OcrPool::with_provider((0..workers).collect::<Vec<usize>>(), ExecutionProvider::Cpu, info));
+    let (held, most) = (Arc::new(Mutex::new(Vec::new())), Arc::new(AtomicUsize::new(0)));
+    let started = Instant::now();
+    futures::future::join_all((0..jobs).map(|_| {
+        let (pool, held, most) = (pool.clone(), held.clone(), most.clone());
+        async move {
+            let checkout = pool.acquire().await;
+            let model = *checkout.model();
+            {
+                let mut held = held.lock().unwrap();
+                // Never the same model twice at once
+                assert!(!held.contains(&model));
+                held.push(model);
+                most.fetch_max(held.len(), Ordering::SeqCst);
+            }
+            actix_rt::time::sleep(Duration::from_millis(50)).await;
+            held.lock().unwrap().retain(|&m| m != model);
+        }
+    }))
+    .await;
+    // Every model went back: all of them can be checked out again at once
+    let checkouts = actix_rt::time::timeout(Duration::from_secs(1), futures::future::join_all((0..workers).map(|_| pool.acquire()))).await.unwrap();
+    let mut models: Vec<usize> = checkouts.iter().map(|c| *c.model()).collect();
+    models.sort();
+    assert_eq!(models, (0..workers).collect::<Vec<_>>());
+    (started.elapsed(), most.load(Ordering::SeqCst))
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn pool_hands_each_model_to_one_request_at_a_time() {
+    let (single, most) = pool_run(1, 8).await;
+    assert_eq!(most, 1);
+    let (pooled, most) = pool_run(4, 8).await;
+    assert_eq!(most, 4);
+    // Eight jobs take two rounds on four models instead of eight on one
+    assert!(pooled * 2 < single, "{pooled:?} vs {single:?}");
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn multi_scale_keeps_lines_only_one_scale_found() {
+    let rect = |x0: f32, y0: f32, x1: f32, y1: f32, text: &str, score: f32| Region { points: vec![[x0, y0], [x1, y0], [x1, y1], [x0, y1]], text: text.into(), score };
+    // The 1.0 pass reads the headline only; the 1.5 pass, mapped back, finds it again (a little
+    // less sure) plus the fine print
+    let single = [rect(10.0, 10.0, 300.0, 60.0, "Headline", 0.95)];
+    let upscaled = [rect(16.0, 14.0, 452.0, 91.0, "Headline", 0.9), rect(30.0, 180.0, 120.0, 195.0, "fine print", 0.8)];
+    let mapped: Vec<Region> = upscaled.into_iter().map(|r| r.scaled(1.0 / 1.5)).collect();
+    let merged = multiscale::merge(single.iter().cloned().chain(mapped).collect());
+    assert_eq!(merged.iter().map(|r| (r.text.as_str(), r.score)).collect::<Vec<_>>(), vec![("Headline", 0.95), ("fine print", 0.8)]);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn recognize_caps_the_number_of_scales() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(recognize)).await;
+    let image = png(100, 50);
+    let fields: [(&str, &[u8]); 3] = [("file", &image), ("multi_scale", b"true"), ("scales", b"0.5,0.75,1,1.5,2")];
+    let res = test::call_service(&app, form("/api/ocr/", &fields).to_request()).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+    assert_eq!(body["error"]["message"], "Invalid 'scales': expected between 1 and 4 scales, got 5");
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn recognize_fails_fast_when_the_queue_is_full() {
+    let mut state = AppState::from_env();
+    state.jobs = Arc::new(JobGate::new(2));
+    let gate = state.jobs.clone();
+    let app = test::init_service(App::new().app_data(web::Data::new(state)).service(recognize).service(stats)).await;
+    let image = png(100, 50);
+
+    let held: Vec<_> = (0..2).map(|_| gate.try_enter().unwrap()).collect();
+    let res = test::call_service(&app, form("/api/ocr/", &[("file", &image)]).to_request()).await;
+    assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(res.headers().get("Retry-After").unwrap(), error::BUSY_RETRY_AFTER_SECS.to_string().as_str());
+    let body: serde_json::Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+    assert_eq!(body["error"], serde_json::json!({"code": "busy", "message": "server busy"}));
+    let counts: serde_json::Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/api/ocr/stats").to_request()).await;
+    assert_eq!((counts["queue_depth"].as_u64(), counts["max_queue_depth"].as_u64()), (Some(2), Some(2)));
+
+    // Admitted again once there's room; without a model it then stops there
+    drop(held);
+    let res = test::call_service(&app, form("/api/ocr/", &[("file", &image)]).to_request()).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let counts: serde_json::Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/api/ocr/stats").to_request()).await;
+    assert_eq!(counts["queue_depth"], 0);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn result_cache_hits_identical_requests_only() {
+    let options = |thresh: &str| [("det_db_thresh".to_string(), thresh.to_string())].into_iter().collect::<std::collections::BTreeMap<_, _>>();
+    let (image, other) = (png(100, 50), png(100, 51));
+    let cache = ResultCache::new(2);
+    cache.put(CacheKey::new(&image, &options("0.3")), serde_json::json!({"result": [["first"]]}));
+
+    assert_eq!(cache.get(&CacheKey::new(&image, &options("0.3"))), Some(serde_json::json!({"result": [["first"]]})));
+    assert_eq!(cache.get(&CacheKey::new(&image, &options("0.4"))), None);
+    assert_eq!(cache.get(&CacheKey::new(&other, &options("0.3"))), None);
+
+    // Least recently used goes first: `image` was just read, so `other` at 0.4 is evicted
+    cache.put(CacheKey::new(&other, &options("0.4")), serde_json::json!({}));
+    cache.get(&CacheKey::new(&image, &options("0.3")));
+    cache.put(CacheKey::new(&other, &options("0.5")), serde_json::json!({}));
+    assert!(cache.get(&CacheKey::new(&image, &options("0.3"))).is_some());
+    assert!(cache.get(&CacheKey::new(&other, &options("0.4"))).is_none());
+
+    let disabled = ResultCache::new(0);
+    disabled.put(CacheKey::new(&image, &options("0.3")), serde_json::json!({}));
+    assert!(disabled.get(&CacheKey::new(&image, &options("0.3"))).is_none());
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn det_limit_side_len_sizes_the_detector_input() {
+    // Shrunk to the limit, keeping the aspect ratio, in multiples of 32
+    assert_eq!(model::det_input_size(2000, 1000, 960), (960, 480));
+    assert_eq!(model::det_input_size(2000, 1000, 480), (480, 256));
+    // Never enlarged, only rounded
+    assert_eq!(model::det_input_size(100, 50, 960), (96, 64));
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn recognize_keeps_det_limit_side_len_in_range() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(recognize)).await;
+    let image = png(100, 50);
+    for value in ["16", "5000", "big"] {
+        let res = test::call_service(&app, form("/api/ocr/", &[("file", &image), ("det_limit_side_len", value.as_bytes())]).to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{value}");
+        let body: serde_json::Value = serde_json::from_slice(&test::read_body(res).await).unwrap();
+        assert_eq!(body["error"]["message"], format!("Invalid 'det_limit_side_len': expected an integer in 32..=4000, got '{value}'"));
+    }
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn config_file_model_paths_reach_the_loader() {
+    // No model files here, so the loader stops at the files it was told to build from
+    let config = config::parse("[model]\ndir = \"/no/such/models\"\ndet = \"custom_det.onnx\"\n").unwrap();
+    let source = model_source(&std::collections::HashMap::new(), &config).unwrap();
+    assert_eq!(source.path(&source.det), "/no/such/models/custom_det.onnx");
+    let err = load_pool(&source).err().unwrap();
+    assert!(err.contains("/no/such/models/custom_det.onnx"), "{err}");
+    assert!(err.contains("/no/such/models/pp-ocrv5_mobile_rec.onnx"), "{err}");
+
+    assert!(config::parse("[model]\ndetector = \"x.onnx\"\n").is_err());
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn coalescer_hands_the_leaders_response_to_followers() {
+    let options = [("det_db_thresh".to_string(), "0.3".to_string())].into_iter().collect();
+    let key = CacheKey::new(&png(100, 50), &options);
+    let coalescer = Arc::new(Coalescer::from_env());
+
+    let Some(Join::Leader(leader)) = coalescer.join(&key) else { panic!("first request should lead") };
+    let Some(Join::Follower(receiver)) = coalescer.join(&key) else { panic!("identical request should follow") };
+    let other = CacheKey::new(&png(100, 51), &options);
+    assert!(matches!(coalescer.join(&other), Some(Join::Leader(_))));
+    let waiter = actix_rt::spawn(coalesce::wait(receiver));
+    leader.finish(&serde_json::json!({"result": [["shared"]]}));
+    assert_eq!(waiter.await.unwrap(), Some(serde_json::json!({"result": [["shared"]]})));
+
+    // Finished, so the next request leads again; a leader that fails releases its followers empty-handed
+    let Some(Join::Leader(leader)) = coalescer.join(&key) else { panic!("finished key should lead again") };
+    let Some(Join::Follower(receiver)) = coalescer.join(&key) else { panic!("identical request should follow") };
+    drop(leader);
+    assert_eq!(coalesce::wait(receiver).await, None);
+}
+
+#[actix_web::test]
+async fn multi_frame_uploads_give_one_page_per_frame() {
+    let shades = [40u8, 120, 200];
+
+    let mut tiff = std::io::Cursor::new(Vec::new());
+    let mut encoder = ::tiff::encoder::TiffEncoder::new(&mut tiff).unwrap();
+    for (i, shade) in shades.iter().enumerate() {
+        let width = 20 + 10 * i as u32;
+        encoder.write_image::<::tiff::encoder::colortype::RGB8>(width, 10, &vec![*shade; width as usize * 10 * 3]).unwrap();
+    }
+    let pages = decode::frames(tiff.get_ref()).unwrap();
+    assert_eq!(pages.iter().map(|p| (p.width(), p.get_pixel(0, 0)[0])).collect::<Vec<_>>(), [(20, 40), (30, 120), (40, 200)]);
+
+    let mut gif = Vec::new();
+    let frames = shades.map(|shade| image::Frame::new(image::RgbaImage::from_pixel(16, 8, image::Rgba([shade, shade, shade, 255]))));
+    image::codecs::gif::GifEncoder::new(&mut gif).encode_frames(frames).unwrap();
+    let pages = decode::frames(&gif).unwrap();
+    assert_eq!(pages.iter().map(|p| p.get_pixel(0, 0)[0]).collect::<Vec<_>>(), shades);
+
+    // Single-frame files are the one image
+    assert_eq!(decode::frames(&encoded(16, 8, image::ImageFormat::Tiff)).unwrap().len(), 1);
+    assert_eq!(decode::frames(&encoded(16, 8, image::ImageFormat::Gif)).unwrap().len(), 1);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn vertical_columns_are_read_top_to_bottom() {
+    // A column of three "characters", red over green over blue
+    let mut page = image::RgbImage::from_pixel(40, 120, image::Rgb([255, 255, 255]));
+    for (x, y, pixel) in page.enumerate_pixels_mut() {
+        if (10..30).contains(&x) {
+            *pixel = image::Rgb([[255, 0, 0], [0, 255, 0], [0, 0, 255]][y as usize / 40]);
+        }
+    }
+    let column = [[10.0, 0.0], [30.0, 0.0], [30.0, 120.0], [10.0, 120.0]];
+    let wide = [[0.0, 50.0], [40.0, 50.0], [40.0, 60.0], [0.0, 60.0]];
+
+    assert!(direction::choose(direction::Mode::Auto, &column) == direction::Direction::Vertical);
+    assert!(direction::choose(direction::Mode::Auto, &wide) == direction::Direction::Horizontal);
+    assert!(direction::choose(direction::Mode::Horizontal, &column) == direction::Direction::Horizontal);
+    assert!(direction::choose(direction::Mode::Vertical, &wide) == direction::Direction::Vertical);
+    assert!(direction::Mode::parse("sideways").is_none());
+
+    // Read as vertical, the crop runs left to right in the column's reading order
+    let read = direction::crop(&page, &column, direction::Direction::Vertical).unwrap();
+    assert!(read.width() > read.height());
+    let (w, mid) = (read.width(), read.height() / 2);
+    let order: Vec<_> = [w / 6, w / 2, w * 5 / 6].iter().map(|&x| read.get_pixel(x, mid).0).collect();
+    assert_eq!(order, [[255, 0, 0], [0, 255, 0], [0, 0, 255]]);
+
+    // Read as horizontal, the column stays upright
+    let upright = direction::crop(&page, &column, direction::Direction::Horizontal).unwrap();
+    assert!(upright.height() > upright.width());
+    assert_eq!(upright.get_pixel(upright.width() / 2, upright.height() / 6).0, [255, 0, 0]);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn with_chars_is_null_beside_the_line_score() {
+    let region = Region { points: vec![[0.0, 0.0], [40.0, 0.0], [40.0, 10.0], [0.0, 10.0]], text: "abc".into(), score: 0.8 };
+    let info = || LineInfo { id: None, style: None, direction: None, page_size: (100, 50) };
+    let mut form = RecognizeForm::new(&settings::Settings::default());
+    assert!(schema::region(&form.line(&region, info()), None).get("chars").is_none());
+
+    // Only line scores exist, so chars is null rather than a split of one
+    form.with_chars = true;
+    let v2 = schema::region(&form.line(&region, info()), None);
+    assert_eq!(v2["chars"], serde_json::Value::Null);
+    assert!(v2.as_object().unwrap().contains_key("chars"));
+    assert_eq!(v2["text"], "abc");
+    assert!((v2["score"].as_f64().unwrap() - 0.8).abs() < 1e-6);
+    assert_eq!(form.warnings(), ["the recognizer only reports line-level scores; chars is null"]);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn detect_only_lines_have_no_text_and_the_box_score() {
+    // run_detection's regions: empty text and the detector's confidence
+    let detected = Region { points: vec![[0.0, 0.0], [40.0, 0.0], [40.0, 10.0], [0.0, 10.0]], text: String::new(), score: 0.7 };
+    let info = LineInfo { id: None, style: None, direction: None, page_size: (100, 50) };
+    let mut form = RecognizeForm::new(&settings::Settings::default());
+    form.detect_only = true;
+    let line = form.line(&detected, info);
+    assert_eq!(line[1][0], serde_json::Value::Null);
+
+    let v2 = schema::region(&line, None);
+    assert_eq!(v2["text"], serde_json::Value::Null);
+    assert!((v2["score"].as_f64().unwrap() - 0.7).abs() < 1e-6);
+    assert_eq!(v2["box"], serde_json::json!([[0.0, 0.0], [40.0, 0.0], [40.0, 10.0], [0.0, 10.0]]));
+    assert_eq!(form.echo(None)["detect_only"], true);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn idempotency_keys_are_tied_to_the_image_and_options() {
+    let state = web::Data::new(AppState::from_env());
+    let app = test::init_service(App::new().app_data(state.clone()).service(recognize)).await;
+    let image = png(100, 50);
+    let fields = [("det_db_thresh".to_string(), "0.3".to_string())].into_iter().collect();
+    state.idempotency.store("retry-1".into(), CacheKey::new(&image, &fields), serde_json::json!({"result": [["first"]]}));
+
+    // Answered from the stored response, before any model is needed
+    let req = form("/api/ocr/", &[("file", &image), ("det_db_thresh", b"0.3")]).insert_header(("Idempotency-Key", "retry-1"));
+    let res = test::call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(test::read_body_json::<serde_json::Value, _>(res).await, serde_json::json!({"result": [["first"]]}));
+
+    for (image, thresh) in [(&image, &b"0.6"[..]), (&png(100, 51), b"0.3")] {
+        let req = form("/api/ocr/", &[("file", image), ("det_db_thresh", thresh)]).insert_header(("Idempotency-Key", "retry-1"));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+    }
+    let req = form("/api/ocr/", &[("file", &image), ("det_db_thresh", b"0.3"), ("detect_only", b"true")]).insert_header(("Idempotency-Key", "retry-1"));
+    assert_eq!(test::call_service(&app, req.to_request()).await.status(), StatusCode::CONFLICT);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn cached_results_are_served_without_a_model() {
+    let state = web::Data::new(AppState::from_env());
+    let app = test::init_service(App::new().app_data(state.clone()).service(recognize)).await;
+    let image = png(100, 50);
+    state.results.put(CacheKey::new(&image, &Default::default()), serde_json::json!({"result": [["cached"]], "meta": {"cached": false}}));
+
+    let res = test::call_service(&app, form("/api/ocr/", &[("file", &image)]).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(res).await;
+    assert_eq!(body["result"], serde_json::json!([["cached"]]));
+    assert_eq!(body["meta"]["cached"], true);
+
+    // Other options miss the cache and need the model
+    let res = test::call_service(&app, form("/api/ocr/", &[("file", &image), ("det_db_thresh", b"0.5")]).to_request()).await;
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+// State with the configured model loaded as the default, for the tests that run real OCR
+#[cfg(feature = "with-ocr")]
+fn loaded_state() -> web::Data<AppState> {
+    let state = web::Data::new(AppState::from_env());
+    let source = model_source(&Default::default(), config::get()).unwrap();
+    let (pool, _) = load_pool(&source).expect("model files in the configured model directory");
+    state.ocr.lock().unwrap().insert(DEFAULT_MODEL_ID.into(), source, Arc::new(pool));
+    state
+}
+
+// A page of mixed-size Chinese and Latin text from the repo's reference screenshots
+#[cfg(feature = "with-ocr")]
+fn document() -> Vec<u8> {
+    std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/../../../references/test_document.png")).unwrap()
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+#[ignore = "needs the det/rec model files in the configured model directory"]
+async fn repeated_recognize_is_served_from_the_cache() {
+    let app = test::init_service(App::new().app_data(loaded_state()).service(recognize)).await;
+    let image = document();
+    let mut runs = Vec::new();
+    for _ in 0..2 {
+        let started = std::time::Instant::now();
+        let res = test::call_service(&app, form("/api/ocr/", &[("file", &image)]).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        runs.push((started.elapsed(), body));
+    }
+    let ((first_time, first), (second_time, second)) = (&runs[0], &runs[1]);
+    assert_eq!(first["meta"]["cached"], false);
+    assert_eq!(second["meta"]["cached"], true);
+    assert_eq!(first["result"], second["result"]);
+    assert!(second_time < first_time, "{second_time:?} vs {first_time:?}");
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn reloading_a_parked_model_keeps_the_default() {
+    // Empty pools: the registry only orders them
+    let pool = |dir: &str| {
+        let info = pool::LoadInfo { model_dir: dir.into(), det: String::new(), rec: String::new(), dict: String::new(), loaded_at: std::time::SystemTime::now(), load_time: std::time::Duration::ZERO, warmup_time: None, orientation_classifier: false };
+        Arc::new(OcrPool::with_provider(Vec::new(), ExecutionProvider::Cpu, info))
+    };
+    let source = |dir: &str| ModelSource { dir: dir.into(), det: String::new(), rec: String::new(), dict: String::new() };
+    let resolved_dir = |resolved: Resolved| match resolved {
+        Resolved::Loaded(pool) => format!("loaded {}", pool.info().model_dir),
+        Resolved::Parked(id, source) => format!("parked {} {}", id, source.dir),
+        Resolved::Unknown => "unknown".into(),
+        Resolved::Empty => "empty".into(),
+    };
+
+    let mut models = ModelRegistry::default();
+    models.insert("a".into(), source("/a"), pool("/a"));
+    models.insert("b".into(), source("/b"), pool("/b"));
+    assert_eq!(models.park_all(), ["a", "b"]);
+    assert_eq!(resolved_dir(models.resolve(None)), "parked b /b");
+
+    // A request naming `a` brings it back, but `b` is still the default
+    assert!(models.restore("a", pool("/a")));
+    assert!(!models.restore("a", pool("/a")));
+    assert_eq!(resolved_dir(models.resolve(Some("a"))), "loaded /a");
+    assert_eq!(resolved_dir(models.resolve(None)), "parked b /b");
+    assert!(models.default_model().is_none());
+    assert_eq!(models.parked_ids().collect::<Vec<_>>(), ["b"]);
+
+    assert!(models.restore("b", pool("/b")));
+    assert_eq!(models.default_model().map(|(id, _)| id), Some("b"));
+    assert_eq!(models.iter().map(|(id, _)| id).collect::<Vec<_>>(), ["a", "b"]);
+    assert_eq!(resolved_dir(models.resolve(Some("c"))), "unknown");
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn recognize_waits_for_an_identical_request_in_flight() {
+    let state = web::Data::new(AppState::from_env());
+    let app = test::init_service(App::new().app_data(state.clone()).service(recognize)).await;
+    let image = png(100, 50);
+    // Stands in for an identical request that's running OCR
+    let Some(Join::Leader(leader)) = state.coalescer.join(&CacheKey::new(&image, &Default::default())) else { panic!("should lead") };
+
+    let (res, ()) = futures::join!(test::call_service(&app, form("/api/ocr/", &[("file", &image)]).to_request()), async {
+        actix_rt::time::sleep(std::time::Duration::from_millis(50)).await;
+        leader.finish(&serde_json::json!({"result": [["shared"]], "meta": {}}));
+    });
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(res).await;
+    assert_eq!(body["result"], serde_json::json!([["shared"]]));
+    assert_eq!(body["meta"]["coalesced"], true);
+    assert_eq!(state.inference.calls(), 0);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+#[ignore = "needs the det/rec model files in the configured model directory"]
+async fn identical_concurrent_requests_run_the_model_once() {
+    let image = document();
+    // Model calls one request makes on its own
+    let alone = loaded_state();
+    let app = test::init_service(App::new().app_data(alone.clone()).service(recognize)).await;
+    assert_eq!(test::call_service(&app, form("/api/ocr/", &[("file", &image)]).to_request()).await.status(), StatusCode::OK);
+    let single = alone.inference.calls();
+    assert!(single > 0);
+
+    let state = loaded_state();
+    let app = test::init_service(App::new().app_data(state.clone()).service(recognize)).await;
+    let (first, second) = futures::join!(
+        test::call_service(&app, form("/api/ocr/", &[("file", &image)]).to_request()),
+        test::call_service(&app, form("/api/ocr/", &[("file", &image)]).to_request()),
+    );
+    let first: serde_json::Value = test::read_body_json(first).await;
+    let second: serde_json::Value = test::read_body_json(second).await;
+    assert_eq!(state.inference.calls(), single);
+    assert_eq!(first["result"], second["result"]);
+    assert_eq!(second["meta"]["coalesced"], true);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+#[ignore = "needs the det/rec model files in the configured model directory"]
+async fn detect_only_is_faster_than_a_full_run() {
+    let app = test::init_service(App::new().app_data(loaded_state()).service(recognize)).await;
+    let image = document();
+    let mut timed = Vec::new();
+    // Different fields, so neither is answered from the other's cache entry
+    for detect_only in [&b"false"[..], b"true"] {
+        let started = std::time::Instant::now();
+        let res = test::call_service(&app, form("/api/ocr/", &[("file", &image), ("detect_only", detect_only)]).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        timed.push((started.elapsed(), body));
+    }
+    let ((full_time, full), (detect_time, detected)) = (&timed[0], &timed[1]);
+    assert_eq!(detected["meta"]["recognition_skipped"], true);
+    assert!(full["meta"].get("recognition_skipped").is_none());
+    let boxes = detected["result"][0].as_array().unwrap();
+    assert!(!boxes.is_empty() && boxes.iter().all(|line| line[1][0].is_null()));
+    assert!(detect_time < full_time, "detect_only took {detect_time:?}, a full run {full_time:?}");
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+#[ignore = "needs the det/rec model files in the configured model directory"]
+async fn det_db_thresh_changes_what_is_detected() {
+    let app = test::init_service(App::new().app_data(loaded_state()).service(recognize)).await;
+    let image = document();
+    let mut counts = Vec::new();
+    for thresh in [&b"0.05"[..], b"0.95"] {
+        let res = test::call_service(&app, form("/api/ocr/", &[("file", &image), ("det_db_thresh", thresh)]).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        counts.push(body["result"][0].as_array().unwrap().len());
+    }
+    assert_ne!(counts[0], counts[1], "{counts:?}");
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+#[ignore = "needs the det/rec model files in the configured model directory"]
+async fn multi_scale_finds_more_lines_on_mixed_size_text() {
+    let app = test::init_service(App::new().app_data(loaded_state()).service(recognize)).await;
+    // Large headings over small print and tiny logo captions
+    let image = document();
+    let mut counts = Vec::new();
+    for multi_scale in [&b"false"[..], b"true"] {
+        let res = test::call_service(&app, form("/api/ocr/", &[("file", &image), ("multi_scale", multi_scale)]).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(res).await;
+        counts.push(body["result"][0].as_array().unwrap().len());
+    }
+    assert!(counts[1] > counts[0], "single scale {}, multi_scale {}", counts[0], counts[1]);
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn crops_validates_the_prediction_fields_like_recognize() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(crops)).await;
+    let image = png(100, 50);
+    for (field, value) in [("use_cls", "yes"), ("det_limit_side_len", "5000"), ("det_db_thresh", "2")] {
+        let res = test::call_service(&app, form("/api/ocr/crops", &[("file", &image), (field, value.as_bytes())]).to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{field}");
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert!(body["error"]["message"].as_str().unwrap().starts_with(&format!("Invalid '{field}'")), "{body}");
+    }
+    let fields: [(&str, &[u8]); 3] = [("file", &image), ("use_cls", b"false"), ("det_limit_side_len", b"640")];
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, form("/api/ocr/crops", &fields).to_request()).await).await;
+    assert_eq!(body["error"]["code"], "model_not_loaded");
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn text_validates_the_prediction_fields_like_recognize() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(recognize_text)).await;
+    let image = png(100, 50);
+    for (field, value) in [("use_cls", "yes"), ("det_limit_side_len", "16"), ("cls_thresh", "-1")] {
+        let res = test::call_service(&app, form("/api/ocr/text", &[("file", &image), (field, value.as_bytes())]).to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{field}");
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert!(body["error"]["message"].as_str().unwrap().starts_with(&format!("Invalid '{field}'")), "{body}");
+    }
+    let fields: [(&str, &[u8]); 3] = [("file", &image), ("det_limit_side_len", b"640"), ("sort", b"reading_order")];
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, form("/api/ocr/text", &fields).to_request()).await).await;
+    assert_eq!(body["error"]["code"], "model_not_loaded");
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn layout_preview_validates_the_detector_fields_like_recognize() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(layout_preview)).await;
+    let image = png(100, 50);
+    for (field, value) in [("det_db_thresh", "1.5"), ("det_limit_side_len", "big")] {
+        let res = test::call_service(&app, form("/api/ocr/layout_preview", &[("file", &image), (field, value.as_bytes())]).to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{field}");
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert!(body["error"]["message"].as_str().unwrap().starts_with(&format!("Invalid '{field}'")), "{body}");
+    }
+    let fields: [(&str, &[u8]); 3] = [("file", &image), ("det_limit_side_len", b"640"), ("fill", b"#000000")];
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, form("/api/ocr/layout_preview", &fields).to_request()).await).await;
+    assert_eq!(body["error"]["code"], "model_not_loaded");
+}
+
+#[cfg(feature = "with-ocr")]
+#[actix_web::test]
+async fn base64_checks_its_options_like_recognize() {
+    use base64::Engine;
+
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(recognize_base64)).await;
+    let image = base64::engine::general_purpose::STANDARD.encode(png(100, 50));
+    let post = |body: serde_json::Value| test::TestRequest::post().uri("/api/ocr/base64").set_json(body).to_request();
+    for (field, value) in [("det_limit_side_len", serde_json::json!(5000)), ("det_db_thresh", serde_json::json!(1.5))] {
+        let res = test::call_service(&app, post(serde_json::json!({"image_base64": image, field: value}))).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{field}");
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert!(body["error"]["message"].as_str().unwrap().starts_with(&format!("Invalid '{field}'")), "{body}");
+    }
+    let res = test::call_service(&app, post(serde_json::json!({"image_base64": image, "det_limit_side_len": 640, "det_db_thresh": 0.3}))).await;
+    let body: serde_json::Value = test::read_body_json(res).await;
+    assert_eq!(body["error"]["code"], "model_not_loaded");
+}
+
+#[actix_web::test]
+async fn draw_rejects_a_malformed_ocr_result() {
+    let app = test::init_service(App::new().app_data(web::Data::new(AppState::from_env())).service(draw)).await;
+    let image = png(200, 100);
+    for result in [r#"{"boxes": []}"#, r#"{"result": "not pages"}"#, r#"{"result": [{"page": 1, "result": 3}]}"#] {
+        let res = test::call_service(&app, form("/api/ocr/draw", &[("file", &image), ("ocr_result", result.as_bytes())]).to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{result}");
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["error"]["code"], "invalid_field", "{result}");
+    }
+    // An empty result is still valid: the image comes back without boxes
+    let res = test::call_service(&app, form("/api/ocr/draw", &[("file", &image), ("ocr_result", br#"{"result": []}"#)]).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
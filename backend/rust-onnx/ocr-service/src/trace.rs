@@ -0,0 +1,23 @@
+// Per-request trace of the recognize stages (fetch, decode, ocr with its acquire/predict steps,
+// postprocess, serialize), printed as each span closes with its busy and idle time. They nest
+// under the `request` span that carries the request id (see request_id.rs).
+//
+// Off unless OCR_TRACE is set: `1` prints compact text lines, `json` one JSON object per line.
+// OCR_TRACE_FILTER takes an EnvFilter directive (default `ocr_service=info`); adding e.g.
+// `oar_ocr=debug` interleaves the pipeline's own stage events.
+
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+pub fn init() {
+    let mode = match std::env::var("OCR_TRACE") {
+        Ok(v) if !v.trim().is_empty() && v.trim() != "0" => v.trim().to_ascii_lowercase(),
+        _ => return,
+    };
+    let filter = EnvFilter::try_from_env("OCR_TRACE_FILTER").unwrap_or_else(|_| EnvFilter::new("ocr_service=info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter).with_span_events(FmtSpan::CLOSE);
+    let res = if mode == "json" { builder.json().try_init() } else { builder.compact().try_init() };
+    if let Err(e) = res {
+        log::warn!("OCR_TRACE: could not install the trace subscriber: {}", e);
+    }
+}
@@ -14,18 +14,12 @@ struct AppState {
 fn parse_port_from_line(line: &str) -> Option<u16> {
     println!("[调试] parse_port_from_line 输入: {}", line);
 
-    // 优先检查 [PORT] 标记
-    if line.contains("[PORT]") {
-        // 提取 [PORT] 后面的数字
-        if let Some(start) = line.find("[PORT]") {
-            let after_tag = &line[start..];
-            let numbers: String = after_tag.chars().filter(|c| c.is_numeric()).collect();
-            if let Ok(port) = numbers.parse::<u16>() {
-                if port > 1024 && port < 65535 {
-                    println!("[调试] 从 [PORT] 标记找到有效端口: {}", port);
-                    return Some(port);
-                }
-            }
+    // ocr-service 绑定成功后输出 "LISTENING_ON=127.0.0.1:<port>"，优先按地址解析
+    if let Some(start) = line.find("LISTENING_ON=") {
+        let addr = line[start + "LISTENING_ON=".len()..].split_whitespace().next().unwrap_or("");
+        if let Ok(addr) = addr.parse::<std::net::SocketAddr>() {
+            println!("[调试] 从 LISTENING_ON 找到端口: {}", addr.port());
+            return Some(addr.port());
         }
     }
 
@@ -245,6 +239,13 @@ pub fn run() {
                 }
             }
 
+            // 调试模式下指定了 OCR_DEV_BACKEND_PORT 时不启动 sidecar，由 start_backend 探测外部后端
+            #[cfg(debug_assertions)]
+            if std::env::var("OCR_DEV_BACKEND_PORT").is_ok() {
+                println!("⚠️ 已设置 OCR_DEV_BACKEND_PORT，跳过 sidecar 启动");
+                return Ok(());
+            }
+
             // 启动后端 sidecar
             #[cfg(debug_assertions)]
             println!("启动后端服务...");
@@ -252,7 +253,7 @@ pub fn run() {
             println!("[调试] 准备启动 backend sidecar");
 
             // 使用 Tauri sidecar 启动后端
-            match Command::new_sidecar("paddleocr_backend") {
+            match Command::new_sidecar("ocr-service") {
                 Ok(command) => {
                     #[cfg(debug_assertions)]
                     println!("[调试] Sidecar 命令创建成功");
@@ -385,9 +386,41 @@ pub fn run() {
                 _ => {}
             }
         })
-        .invoke_handler(tauri::generate_handler![get_backend_url, start_backend, read_backend_logs, clear_backend_logs])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .invoke_handler(tauri::generate_handler![get_backend_url, start_backend, read_backend_logs, clear_backend_logs, ocr_clipboard, ocr_screen_region, save_ocr_result, save_ocr_image])
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app_handle, event| {
+            // 窗口关闭之外的退出路径(如系统退出、app.exit)也要终止后端，避免进程泄漏
+            if let tauri::RunEvent::Exit = event {
+                cleanup_backend(app_handle);
+            }
+        });
+}
+
+// 探测 http://127.0.0.1:{port}/api/health/ 是否返回 2xx
+#[cfg(debug_assertions)]
+fn probe_backend_health(port: u16) -> Result<(), String> {
+    use std::io::Read;
+    use std::net::{SocketAddr, TcpStream};
+    use std::time::Duration;
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+    let timeout = Duration::from_secs(2);
+    let mut stream = TcpStream::connect_timeout(&addr, timeout).map_err(|e| format!("connect failed: {}", e))?;
+    stream.set_read_timeout(Some(timeout)).ok();
+    stream.set_write_timeout(Some(timeout)).ok();
+    stream
+        .write_all(format!("GET /api/health/ HTTP/1.0\r\nHost: 127.0.0.1:{}\r\n\r\n", port).as_bytes())
+        .map_err(|e| format!("request failed: {}", e))?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).map_err(|e| format!("read failed: {}", e))?;
+    // 状态行形如 "HTTP/1.1 200 OK"
+    let status = response.lines().next().unwrap_or("");
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(format!("unexpected response: {}", status)),
+    }
 }
 
 #[tauri::command]
@@ -400,11 +433,31 @@ fn start_backend(app_handle: tauri::AppHandle, state: tauri::State<AppState>) ->
         }
     }
 
+    // 调试模式下可通过 OCR_DEV_BACKEND_PORT 使用手动启动的后端，先探测健康检查再返回
+    #[cfg(debug_assertions)]
+    if let Ok(value) = std::env::var("OCR_DEV_BACKEND_PORT") {
+        let port: u16 = value
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid OCR_DEV_BACKEND_PORT: {}", value))?;
+        return match probe_backend_health(port) {
+            Ok(()) => {
+                *state.backend_port.lock().unwrap() = Some(port);
+                append_log_message_and_emit(Some(app_handle.clone()), &format!("start_backend: using dev backend on port {}", port));
+                Ok("dev_backend".into())
+            }
+            Err(e) => {
+                append_log_message_and_emit(Some(app_handle.clone()), &format!("dev backend on port {} not responding: {}", port, e));
+                Err(format!("dev backend on port {} not responding: {}", port, e))
+            }
+        };
+    }
+
     // 复制需要移动到异步任务中的 state
     let backend_port_arc = state.backend_port.clone();
     let backend_child_arc = state.backend_child.clone();
 
-    match Command::new_sidecar("paddleocr_backend") {
+    match Command::new_sidecar("ocr-service") {
         Ok(command) => match command.spawn() {
             Ok((mut rx, child)) => {
                 // 保存句柄
@@ -504,4 +557,274 @@ fn clear_backend_logs() -> Result<(), String> {
     }
 }
 
+// ocr_clipboard / ocr_screen_region 的错误，序列化为 {"kind": "...", "message": "..."} 供前端区分展示
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+enum OcrError {
+    // 剪贴板中没有图片
+    NoImage,
+    // 无法访问剪贴板或图片编码失败
+    Clipboard(String),
+    // 截图区域为空或不在任何显示器上
+    InvalidRegion(String),
+    // 系统拒绝屏幕录制权限（macOS）
+    PermissionDenied(String),
+    // 截屏或图片编码失败
+    Capture(String),
+    // 后端未能启动或在等待时间内未就绪
+    BackendUnavailable(String),
+    // 请求后端或解析响应失败
+    Request(String),
+}
+
+// 等待后端端口就绪的最长时间
+const BACKEND_READY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+// 读取剪贴板中的图片并编码为 PNG 的 base64
+fn clipboard_image_base64() -> Result<String, OcrError> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| OcrError::Clipboard(e.to_string()))?;
+    let image = match clipboard.get_image() {
+        Ok(image) => image,
+        Err(arboard::Error::ContentNotAvailable) => return Err(OcrError::NoImage),
+        Err(e) => return Err(OcrError::Clipboard(e.to_string())),
+    };
+
+    // arboard 返回 RGBA8 像素
+    rgba_png_base64(image.width as u32, image.height as u32, &image.bytes).map_err(OcrError::Clipboard)
+}
+
+// 将 RGBA8 像素编码为 PNG 的 base64
+fn rgba_png_base64(width: u32, height: u32, rgba: &[u8]) -> Result<String, String> {
+    use base64::Engine;
+
+    let mut png_bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png_bytes, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| format!("png encode failed: {}", e))?;
+        writer.write_image_data(rgba).map_err(|e| format!("png encode failed: {}", e))?;
+    }
+    Ok(base64::engine::general_purpose::STANDARD.encode(&png_bytes))
+}
+
+// 返回已知的后端端口；未启动时先调用 start_backend，再轮询等待端口被解析出来
+async fn ensure_backend_port(app_handle: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<u16, OcrError> {
+    if let Some(port) = *state.backend_port.lock().unwrap() {
+        return Ok(port);
+    }
+
+    append_log_message_and_emit(Some(app_handle.clone()), "后端端口未就绪，尝试启动后端");
+    start_backend(app_handle.clone(), state.clone()).map_err(OcrError::BackendUnavailable)?;
+
+    let backend_port = state.backend_port.clone();
+    let port = tauri::async_runtime::spawn_blocking(move || {
+        let started = std::time::Instant::now();
+        while started.elapsed() < BACKEND_READY_TIMEOUT {
+            if let Some(port) = *backend_port.lock().unwrap() {
+                return Some(port);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(200));
+        }
+        None
+    })
+    .await
+    .map_err(|e| OcrError::BackendUnavailable(e.to_string()))?;
+
+    port.ok_or_else(|| OcrError::BackendUnavailable(format!("backend port not detected within {}s", BACKEND_READY_TIMEOUT.as_secs())))
+}
+
+// 识别剪贴板中的图片，返回按行拼接的文本
+#[tauri::command]
+async fn ocr_clipboard(app_handle: tauri::AppHandle, state: tauri::State<'_, AppState>) -> Result<String, OcrError> {
+    let image_base64 = clipboard_image_base64()?;
+    let port = ensure_backend_port(app_handle.clone(), state).await?;
+    append_log_message_and_emit(Some(app_handle.clone()), &format!("ocr_clipboard: 发送剪贴板图片到端口 {}", port));
+    ocr_png_base64(port, image_base64).await
+}
+
+// 将 PNG base64 发送到后端 /api/ocr/base64，返回按行拼接的文本
+async fn ocr_png_base64(port: u16, image_base64: String) -> Result<String, OcrError> {
+    use tauri::api::http::{Body, ClientBuilder, HttpRequestBuilder, ResponseType};
+
+    let url = format!("http://127.0.0.1:{}/api/ocr/base64", port);
+
+    let client = ClientBuilder::new().build().map_err(|e| OcrError::Request(e.to_string()))?;
+    let request = HttpRequestBuilder::new("POST", &url)
+        .map_err(|e| OcrError::Request(e.to_string()))?
+        .body(Body::Json(serde_json::json!({ "image_base64": image_base64 })))
+        .response_type(ResponseType::Json);
+    let response = client.send(request).await.map_err(|e| OcrError::Request(e.to_string()))?;
+    let status = response.status();
+    let data = response.read().await.map_err(|e| OcrError::Request(e.to_string()))?.data;
+    if !status.is_success() {
+        // 后端错误格式为 {"error": {"code", "message"}}
+        let message = data["error"]["message"].as_str().map(str::to_string).unwrap_or_else(|| data.to_string());
+        return Err(OcrError::Request(format!("backend returned {}: {}", status, message)));
+    }
+
+    // result 为按页分组的行列表，每行形如 [box, [text, score]]
+    let pages = data["result"].as_array().ok_or_else(|| OcrError::Request("response has no result".into()))?;
+    let lines: Vec<&str> = pages
+        .iter()
+        .filter_map(|page| page.as_array())
+        .flatten()
+        .filter_map(|line| line[1][0].as_str())
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+// 截取虚拟桌面坐标系中的矩形区域，返回 RGBA8 像素 (width, height, bytes)。
+// 区域可以跨越多个显示器：分别截取与每个显示器相交的部分，再拼接到一张画布上；
+// 各显示器缩放比例不同时（如 Retina 与普通屏混用）按最大比例对齐
+fn capture_screen_region(x: i32, y: i32, width: u32, height: u32) -> Result<(u32, u32, Vec<u8>), OcrError> {
+    use xcap::image::{imageops, RgbaImage};
+
+    if width == 0 || height == 0 {
+        return Err(OcrError::InvalidRegion("region has zero width or height".into()));
+    }
+    ensure_screen_capture_permission()?;
+
+    let capture_err = |e: xcap::XCapError| OcrError::Capture(e.to_string());
+    let (right, bottom) = (x as i64 + width as i64, y as i64 + height as i64);
+    // 每块：(相对区域左上角的偏移, 逻辑尺寸, 截得的图片)
+    let mut pieces: Vec<((u32, u32), (u32, u32), RgbaImage)> = Vec::new();
+    for monitor in xcap::Monitor::all().map_err(capture_err)? {
+        let (mx, my) = (monitor.x().map_err(capture_err)? as i64, monitor.y().map_err(capture_err)? as i64);
+        let (mw, mh) = (monitor.width().map_err(capture_err)? as i64, monitor.height().map_err(capture_err)? as i64);
+        let (left, top) = ((x as i64).max(mx), (y as i64).max(my));
+        let (w, h) = (right.min(mx + mw) - left, bottom.min(my + mh) - top);
+        if w <= 0 || h <= 0 {
+            continue;
+        }
+        let image = monitor
+            .capture_region((left - mx) as u32, (top - my) as u32, w as u32, h as u32)
+            .map_err(capture_err)?;
+        pieces.push((((left - x as i64) as u32, (top - y as i64) as u32), (w as u32, h as u32), image));
+    }
+    if pieces.is_empty() {
+        return Err(OcrError::InvalidRegion(format!("region {}x{} at ({}, {}) is not on any monitor", width, height, x, y)));
+    }
+
+    let scale = pieces.iter().map(|(_, (w, _), image)| image.width() as f32 / *w as f32).fold(1.0f32, f32::max);
+    let scaled = |v: u32| (v as f32 * scale).round() as u32;
+    // 未被任何显示器覆盖的部分保持白色，避免被识别为文字
+    let mut canvas = RgbaImage::from_pixel(scaled(width), scaled(height), xcap::image::Rgba([255, 255, 255, 255]));
+    for ((ox, oy), (w, h), image) in pieces {
+        let image = if image.width() == scaled(w) && image.height() == scaled(h) {
+            image
+        } else {
+            imageops::resize(&image, scaled(w), scaled(h), imageops::FilterType::Triangle)
+        };
+        imageops::replace(&mut canvas, &image, scaled(ox) as i64, scaled(oy) as i64);
+    }
+    Ok((canvas.width(), canvas.height(), canvas.into_raw()))
+}
+
+// macOS 未授予屏幕录制权限时截图不会报错，只会得到桌面壁纸，因此需要事先检查
+#[cfg(target_os = "macos")]
+fn ensure_screen_capture_permission() -> Result<(), OcrError> {
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGPreflightScreenCaptureAccess() -> bool;
+        fn CGRequestScreenCaptureAccess() -> bool;
+    }
+
+    if unsafe { CGPreflightScreenCaptureAccess() } {
+        return Ok(());
+    }
+    // 首次调用时弹出系统授权提示；授权后需要重启应用才生效
+    unsafe { CGRequestScreenCaptureAccess() };
+    Err(OcrError::PermissionDenied(
+        "screen recording permission is required: allow it in System Settings > Privacy & Security > Screen Recording, then restart the app".into(),
+    ))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn ensure_screen_capture_permission() -> Result<(), OcrError> {
+    Ok(())
+}
+
+// 截取屏幕上的矩形区域并识别，返回按行拼接的文本。坐标相对于虚拟桌面（多显示器时为所有显示器组成的整体坐标系）
+#[tauri::command]
+async fn ocr_screen_region(app_handle: tauri::AppHandle, state: tauri::State<'_, AppState>, x: i32, y: i32, width: u32, height: u32) -> Result<String, OcrError> {
+    let (png_width, png_height, rgba) = tauri::async_runtime::spawn_blocking(move || capture_screen_region(x, y, width, height))
+        .await
+        .map_err(|e| OcrError::Capture(e.to_string()))??;
+    let image_base64 = rgba_png_base64(png_width, png_height, &rgba).map_err(OcrError::Capture)?;
+
+    let port = ensure_backend_port(app_handle.clone(), state).await?;
+    append_log_message_and_emit(
+        Some(app_handle.clone()),
+        &format!("ocr_screen_region: 发送 {}x{}@({}, {}) 截图到端口 {}", width, height, x, y, port),
+    );
+    ocr_png_base64(port, image_base64).await
+}
+
+// 保存识别结果的错误，序列化为 {"kind": "...", "message": "..."} 供前端区分展示
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+enum SaveError {
+    // 没有写入权限
+    PermissionDenied(String),
+    // format 不是 txt/json，或内容不是合法 JSON / base64
+    InvalidContent(String),
+    // 其他 IO 错误
+    Io(String),
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::PermissionDenied => SaveError::PermissionDenied(e.to_string()),
+            _ => SaveError::Io(e.to_string()),
+        }
+    }
+}
+
+// 写入文件，必要时先创建父目录
+fn write_creating_dirs(path: &str, bytes: &[u8]) -> Result<(), SaveError> {
+    let path = std::path::Path::new(path);
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    std::fs::write(path, bytes)?;
+    Ok(())
+}
+
+// 将识别结果保存为 txt 或 json；json 会先校验内容能否解析
+#[tauri::command]
+fn save_ocr_result(path: String, content: String, format: String) -> Result<(), SaveError> {
+    match format.to_ascii_lowercase().as_str() {
+        "txt" => {}
+        "json" => {
+            serde_json::from_str::<serde_json::Value>(&content).map_err(|e| SaveError::InvalidContent(format!("content is not valid JSON: {}", e)))?;
+        }
+        other => return Err(SaveError::InvalidContent(format!("unsupported format: {} (expected txt or json)", other))),
+    }
+    write_creating_dirs(&path, content.as_bytes())?;
+    append_log_message_and_emit(None, &format!("save_ocr_result: 已保存到 {}", path));
+    Ok(())
+}
 
+// 保存 /api/ocr/draw 返回的标注图（PNG 的 base64，可带 data: 前缀）
+#[tauri::command]
+fn save_ocr_image(path: String, png_base64: String) -> Result<(), SaveError> {
+    use base64::Engine;
+
+    let encoded = match png_base64.trim().strip_prefix("data:") {
+        Some(rest) => rest.split_once(',').map(|(_, data)| data).unwrap_or(""),
+        None => png_base64.trim(),
+    };
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| SaveError::InvalidContent(format!("invalid base64: {}", e)))?;
+    if !bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Err(SaveError::InvalidContent("data is not a PNG image".into()));
+    }
+    write_creating_dirs(&path, &bytes)?;
+    append_log_message_and_emit(None, &format!("save_ocr_image: 已保存到 {}", path));
+    Ok(())
+}